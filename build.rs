@@ -14,11 +14,30 @@
 
 //! Builds GRPC client from the proto files.
 
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+
+/// Packages whose types get the `serde` implementations of `src/serde_wkt.rs`.
+const SERDE_PACKAGES: [&str; 3] = ["vizier", "google.longrunning", "google.rpc"];
+
 fn main() -> std::io::Result<()> {
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let descriptor_path = out_dir.join("file_descriptor_set.bin");
+
+    let mut config = prost_build::Config::new();
+    // implements `prost::Name` so the `type_url` of operation results can be checked.
+    config.enable_type_names();
+    config.type_name_domain(["."], "type.googleapis.com");
+
     tonic_prost_build::configure()
         .protoc_arg("--experimental_allow_proto3_optional")
         .build_server(false)
-        .compile_protos(
+        .file_descriptor_set_path(&descriptor_path)
+        .compile_with_config(
+            config,
             &[
                 "protos/google/longrunning/operations.proto",
                 "protos/vizier/key_value.proto",
@@ -32,5 +51,66 @@ fn main() -> std::io::Result<()> {
                 "protos/google/rpc/status.proto",
             ],
             &["protos"],
-        )
+        )?;
+
+    // `serde` feature: implements `Serialize`/`Deserialize` in the canonical proto3
+    // JSON mapping for the messages and enums of `SERDE_PACKAGES`.
+    if std::env::var_os("CARGO_FEATURE_SERDE").is_some() {
+        write_serde_impls(&descriptor_path, &out_dir.join("serde_impls.rs"))?;
+    }
+
+    Ok(())
+}
+
+/// Writes an invocation of the `message!` or `enumeration!` macros of
+/// `src/serde_wkt.rs` for every generated type of `SERDE_PACKAGES`.
+fn write_serde_impls(descriptor_path: &Path, impls_path: &Path) -> std::io::Result<()> {
+    fn walk(messages: &[DescriptorProto], module: &str, out: &mut String) {
+        for message in messages {
+            let name = message.name();
+            // map entries are not generated as types
+            if message.options.as_ref().is_some_and(|o| o.map_entry()) {
+                continue;
+            }
+            writeln!(out, "message!({module}::{name});").unwrap();
+
+            let nested = format!("{module}::{}", snake_case(name));
+            for enumeration in &message.enum_type {
+                writeln!(out, "enumeration!({nested}::{});", enumeration.name()).unwrap();
+            }
+            walk(&message.nested_type, &nested, out);
+        }
+    }
+
+    let descriptors = FileDescriptorSet::decode(std::fs::read(descriptor_path)?.as_slice())?;
+
+    let mut out = String::new();
+    for file in descriptors.file {
+        if !SERDE_PACKAGES.contains(&file.package()) {
+            continue;
+        }
+        let module = format!("crate::{}", file.package().replace('.', "::"));
+        for enumeration in &file.enum_type {
+            writeln!(out, "enumeration!({module}::{});", enumeration.name()).unwrap();
+        }
+        walk(&file.message_type, &module, &mut out);
+    }
+
+    std::fs::write(impls_path, out)
+}
+
+/// The module of the nested types of a message, as named by prost.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
 }
@@ -17,24 +17,15 @@ use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use oss_vizier::VizierClient;
+use oss_vizier::benchmarks::Problem;
+use oss_vizier::benchmarks::functions::Himmelblau;
 use oss_vizier::model::study::ToStudyName;
-use oss_vizier::model::study::spec::StudySpecBuilder;
 use oss_vizier::model::trial::ToTrialName;
 use oss_vizier::model::trial::complete::FinalMeasurementOrReason;
 use oss_vizier::prost_types::value::Kind;
-use oss_vizier::vizier::study_spec::metric_spec::GoalType;
-use oss_vizier::vizier::study_spec::parameter_spec::{
-    DoubleValueSpec, ParameterValueSpec, ScaleType,
-};
-use oss_vizier::vizier::study_spec::{MetricSpec, ObservationNoise, ParameterSpec};
+use oss_vizier::vizier::Trial;
 use oss_vizier::vizier::trial::State;
 use oss_vizier::vizier::vizier_service_client::VizierServiceClient;
-use oss_vizier::vizier::{Measurement, Trial, measurement};
-
-/// Hammelblau's function
-fn f(x: f64, y: f64) -> f64 {
-    (x.powi(2) + y - 11.).powi(2) + (x + y.powi(2) - 7.).powi(2)
-}
 
 #[tokio::main]
 async fn main() {
@@ -48,40 +39,8 @@ async fn main() {
 
     let mut client = VizierClient::new(owner, service);
 
-    let study_spec =
-        StudySpecBuilder::new("ALGORITHM_UNSPECIFIED".to_string(), ObservationNoise::Low)
-            .with_metric_specs(vec![MetricSpec {
-                metric_id: "m".to_string(),
-                goal: GoalType::Minimize as i32,
-                safety_config: None,
-            }])
-            .with_parameters(vec![
-                ParameterSpec {
-                    parameter_id: "x".to_string(),
-                    scale_type: ScaleType::Unspecified as i32,
-                    conditional_parameter_specs: vec![],
-                    parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(
-                        DoubleValueSpec {
-                            min_value: -5.0,
-                            max_value: 5.0,
-                            default_value: Some(0.0),
-                        },
-                    )),
-                },
-                ParameterSpec {
-                    parameter_id: "y".to_string(),
-                    scale_type: ScaleType::Unspecified as i32,
-                    conditional_parameter_specs: vec![],
-                    parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(
-                        DoubleValueSpec {
-                            min_value: -5.0,
-                            max_value: 5.0,
-                            default_value: Some(0.0),
-                        },
-                    )),
-                },
-            ])
-            .build();
+    let problem = Himmelblau;
+    let study_spec = problem.study_spec("ALGORITHM_UNSPECIFIED".to_string());
 
     let epoch = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -124,23 +83,14 @@ async fn main() {
 
                     let start = SystemTime::now();
 
-                    let x = parameters["x"];
-                    let y = parameters["y"];
+                    let mut measurement = problem.evaluate(trial).unwrap();
 
-                    let value = f(x, y);
-
-                    let elapsed_duration = start.elapsed().unwrap();
-                    dbg!(&value);
+                    measurement.elapsed_duration =
+                        Some(start.elapsed().unwrap().try_into().unwrap());
+                    dbg!(&measurement);
 
                     let final_measurement_or_reason =
-                        FinalMeasurementOrReason::FinalMeasurement(Measurement {
-                            elapsed_duration: Some(elapsed_duration.try_into().unwrap()),
-                            step_count: 14,
-                            metrics: vec![measurement::Metric {
-                                metric_id: "m".to_string(),
-                                value,
-                            }],
-                        });
+                        FinalMeasurementOrReason::FinalMeasurement(measurement);
 
                     let request = client.mk_complete_trial_request(
                         trial.to_trial_name(),
@@ -175,10 +125,10 @@ fn extract_parameters(trial: &Trial) -> HashMap<String, f64> {
     let mut parameters = HashMap::new();
     for p in trial.parameters.iter() {
         let p_id = p.parameter_id.clone();
-        if let Some(p) = &p.value {
-            if let Some(Kind::NumberValue(v)) = p.kind {
-                parameters.insert(p_id, v);
-            }
+        if let Some(p) = &p.value
+            && let Some(Kind::NumberValue(v)) = p.kind
+        {
+            parameters.insert(p_id, v);
         }
     }
     parameters
@@ -14,55 +14,33 @@
 
 //! Simple example of how to use the Vizier API.
 
-use oss_vizier::vizier::vizier_service_client::VizierServiceClient;
+use std::time::Duration;
+
+use futures_util::StreamExt;
 use oss_vizier::VizierClient;
 
 #[tokio::main]
 async fn main() {
     let endpoint = "http://localhost:28080/";
 
-    let service = { VizierServiceClient::connect(endpoint) }.await.unwrap();
-
     let owner = "owner".to_string();
 
-    let mut client = VizierClient::new(owner, service);
-
-    let request = client
-        .mk_list_studies_request_builder()
-        .with_page_size(2)
-        .build();
-
-    let studies = client.service.list_studies(request).await;
-
-    if studies.is_err() {
-        println!("Error: {}", studies.err().unwrap().message());
-        return;
-    }
-
-    let studies = studies.unwrap();
-
-    let study_list = &studies.get_ref().studies;
-    for t in study_list {
-        println!("- {}", &t.display_name);
-    }
-
-    if !studies.get_ref().next_page_token.is_empty() {
-        let mut page_token = studies.get_ref().next_page_token.clone();
-
-        while !page_token.is_empty() {
-            let request = client
-                .mk_list_studies_request_builder()
-                .with_page_token(page_token)
-                .with_page_size(2)
-                .build();
-
-            let studies = client.service.list_studies(request).await.unwrap();
-            let study_list = &studies.get_ref().studies;
-            for t in study_list {
-                println!("- {}", &t.display_name);
+    let client = VizierClient::builder(endpoint, owner)
+        .with_connect_timeout(Duration::from_secs(5))
+        .with_timeout(Duration::from_secs(30))
+        .connect()
+        .await
+        .unwrap();
+
+    let mut studies = std::pin::pin!(client.list_studies_stream(2));
+
+    while let Some(study) = studies.next().await {
+        match study {
+            Ok(study) => println!("- {}", &study.display_name),
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
             }
-
-            page_token = studies.get_ref().next_page_token.clone();
         }
     }
 }
@@ -0,0 +1,242 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convergence of studies - best-so-far, regret and infeasibility over the trial
+//! history, as data series for plotting.
+//!
+//! The curves follow the order of the trials: sort them with
+//! [sort_by_completion_time] or [sort_by_start_time] beforehand.
+//!
+//! ```no_run
+//! # use oss_vizier::analysis::convergence::{best_so_far, simple_regret, sort_by_completion_time};
+//! # use oss_vizier::vizier::{StudySpec, Trial};
+//! # fn example(mut trials: Vec<Trial>, study_spec: StudySpec) {
+//! sort_by_completion_time(&mut trials);
+//! for series in best_so_far(&trials, &study_spec.metrics) {
+//!     println!("{}: {:?}", series.name, series.values());
+//! }
+//! let regret = simple_regret(&trials, &study_spec.metrics, "loss", 0.0);
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+
+use prost_types::Timestamp;
+
+use crate::Trial;
+use crate::analysis::{final_metric, is_safe, oriented};
+use crate::vizier::study_spec::MetricSpec;
+use crate::vizier::trial::State;
+
+/// A point of a [Series].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    /// Number of trials so far - 1 for the first trial.
+    pub step: usize,
+    /// Id of the trial of the step.
+    pub trial_id: String,
+    /// Value after the trial.
+    pub value: f64,
+}
+
+/// Data series - one point per trial, from the first trial with a value.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Series {
+    /// Name of the series - the metric id for per-metric series.
+    pub name: String,
+    /// The points, by increasing step.
+    pub points: Vec<Point>,
+}
+
+impl Series {
+    /// The steps of the points.
+    pub fn steps(&self) -> Vec<usize> {
+        self.points.iter().map(|p| p.step).collect()
+    }
+
+    /// The values of the points.
+    pub fn values(&self) -> Vec<f64> {
+        self.points.iter().map(|p| p.value).collect()
+    }
+
+    /// The value after the last trial, if any.
+    pub fn last(&self) -> Option<f64> {
+        self.points.last().map(|p| p.value)
+    }
+}
+
+/// Sorts trials by end time - trials that have not completed go last, by start time.
+pub fn sort_by_completion_time(trials: &mut [Trial]) {
+    trials.sort_by(|a, b| {
+        compare_times(&a.end_time, &b.end_time).then(compare_times(&a.start_time, &b.start_time))
+    });
+}
+
+/// Sorts trials by start time - trials without start time go last.
+pub fn sort_by_start_time(trials: &mut [Trial]) {
+    trials.sort_by(|a, b| compare_times(&a.start_time, &b.start_time));
+}
+
+/// Earlier first, missing last.
+fn compare_times(a: &Option<Timestamp>, b: &Option<Timestamp>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => (a.seconds, a.nanos).cmp(&(b.seconds, b.nanos)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Best value so far of each metric of `metrics`, according to its goal.
+///
+/// A trial counts if it succeeded, satisfies the safety thresholds and has a finite
+/// final value for the metric.
+pub fn best_so_far(trials: &[Trial], metrics: &[MetricSpec]) -> Vec<Series> {
+    metrics
+        .iter()
+        .map(|metric| best_so_far_metric(trials, metrics, metric))
+        .collect()
+}
+
+/// Simple regret of the metric `metric_id` against a known `optimum` - the distance
+/// between the optimum and the best value so far. `None` if the metric is not in
+/// `metrics`.
+pub fn simple_regret(
+    trials: &[Trial],
+    metrics: &[MetricSpec],
+    metric_id: &str,
+    optimum: f64,
+) -> Option<Series> {
+    let metric = metrics.iter().find(|m| m.metric_id == metric_id)?;
+
+    let mut series = best_so_far_metric(trials, metrics, metric);
+    for point in &mut series.points {
+        point.value = (oriented(metric, optimum) - oriented(metric, point.value)).max(0.0);
+    }
+    series.name = format!("{metric_id} regret");
+
+    Some(series)
+}
+
+/// Cumulative number of infeasible trials.
+pub fn cumulative_infeasible(trials: &[Trial]) -> Series {
+    let mut count = 0;
+    let points = trials
+        .iter()
+        .enumerate()
+        .map(|(i, trial)| {
+            if trial.state() == State::Infeasible {
+                count += 1;
+            }
+            Point {
+                step: i + 1,
+                trial_id: trial.id.clone(),
+                value: count as f64,
+            }
+        })
+        .collect();
+
+    Series {
+        name: "infeasible".to_string(),
+        points,
+    }
+}
+
+fn best_so_far_metric(trials: &[Trial], metrics: &[MetricSpec], metric: &MetricSpec) -> Series {
+    let mut best: Option<f64> = None;
+    let mut points = vec![];
+    for (i, trial) in trials.iter().enumerate() {
+        let value = Some(trial)
+            .filter(|t| t.state() == State::Succeeded && is_safe(t, metrics))
+            .and_then(|t| final_metric(t, &metric.metric_id))
+            .filter(|v| v.is_finite());
+
+        if let Some(value) = value
+            && best.is_none_or(|b| oriented(metric, value) > oriented(metric, b))
+        {
+            best = Some(value);
+        }
+
+        if let Some(value) = best {
+            points.push(Point {
+                step: i + 1,
+                trial_id: trial.id.clone(),
+                value,
+            });
+        }
+    }
+
+    Series {
+        name: metric.metric_id.clone(),
+        points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Timestamp;
+
+    use super::{best_so_far, cumulative_infeasible, simple_regret, sort_by_completion_time};
+    use crate::analysis::testing::{metric, trial};
+    use crate::vizier::study_spec::metric_spec::GoalType;
+    use crate::vizier::trial::State;
+
+    #[test]
+    fn it_tracks_the_best_values() {
+        let metrics = vec![
+            metric("loss", GoalType::Minimize, None),
+            metric("memory", GoalType::Minimize, Some(10.0)),
+        ];
+        let mut infeasible = trial(1, &[]);
+        infeasible.state = State::Infeasible as i32;
+        let trials = vec![
+            infeasible,
+            trial(2, &[("loss", 3.0), ("memory", 5.0)]),
+            trial(3, &[("loss", 4.0), ("memory", 5.0)]),
+            // unsafe
+            trial(4, &[("loss", 0.5), ("memory", 50.0)]),
+            trial(5, &[("loss", 1.0), ("memory", 8.0)]),
+        ];
+
+        let curves = best_so_far(&trials, &metrics);
+        assert_eq!(curves[0].name, "loss");
+        assert_eq!(curves[0].steps(), vec![2, 3, 4, 5]);
+        assert_eq!(curves[0].values(), vec![3.0, 3.0, 3.0, 1.0]);
+        assert_eq!(curves[1].values(), vec![5.0, 5.0, 5.0, 5.0]);
+
+        let regret = simple_regret(&trials, &metrics, "loss", 0.5).unwrap();
+        assert_eq!(regret.values(), vec![2.5, 2.5, 2.5, 0.5]);
+        assert!(simple_regret(&trials, &metrics, "accuracy", 1.0).is_none());
+
+        assert_eq!(
+            cumulative_infeasible(&trials).values(),
+            vec![1.0, 1.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn it_sorts_by_completion_time() {
+        let at = |seconds| Some(Timestamp { seconds, nanos: 0 });
+        let mut trials = vec![trial(1, &[]), trial(2, &[]), trial(3, &[])];
+        trials[0].end_time = None;
+        trials[1].end_time = at(20);
+        trials[2].end_time = at(10);
+
+        sort_by_completion_time(&mut trials);
+        let ids: Vec<&str> = trials.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["3", "2", "1"]);
+    }
+}
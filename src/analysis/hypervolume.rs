@@ -0,0 +1,519 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hypervolume indicator of multi-objective studies.
+//!
+//! The hypervolume is the volume of the objective space dominated by the trials and
+//! bounded by a reference point - greater is better. It is exact for up to 3
+//! objectives and estimated by Monte Carlo sampling beyond.
+//!
+//! ```no_run
+//! # use oss_vizier::analysis::hypervolume::{hypervolume_curve, study_hypervolume};
+//! # use oss_vizier::vizier::{StudySpec, Trial};
+//! # fn example(trials: Vec<Trial>, study_spec: StudySpec) -> Result<(), oss_vizier::analysis::hypervolume::Error> {
+//! // reference point in the units of the objective metrics, e.g. accuracy and latency
+//! let reference = [0.5, 100.0];
+//! let hv = study_hypervolume(&trials, &study_spec.metrics, &reference)?;
+//! let curve = hypervolume_curve(&trials, &study_spec.metrics, &reference)?;
+//! # Ok::<(), oss_vizier::analysis::hypervolume::Error>(())
+//! # }
+//! ```
+
+use crate::Trial;
+use crate::analysis::{XorShift, objective_values, objectives, oriented};
+use crate::vizier::study_spec::MetricSpec;
+
+/// Number of samples of [hypervolume] beyond 3 objectives.
+pub const DEFAULT_SAMPLES: usize = 100_000;
+
+/// Seed of the samples of [hypervolume] beyond 3 objectives.
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Error returned by the hypervolume computations.
+#[derive(thiserror::Error, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The reference point does not have one value per objective.
+    #[error("the reference point has {actual} values for {expected} objectives")]
+    ReferenceLength {
+        /// Number of objectives.
+        expected: usize,
+        /// Number of values of the reference point.
+        actual: usize,
+    },
+    /// The exact hypervolume is only computed up to 3 objectives.
+    #[error("exact hypervolume is only supported up to 3 objectives, got {0}")]
+    TooManyObjectives(usize),
+}
+
+/// Hypervolume of `points` with respect to `reference` - greater is better on every
+/// objective. Points that do not strictly dominate `reference` are ignored.
+///
+/// Exact up to 3 objectives, estimated with [DEFAULT_SAMPLES] samples beyond.
+pub fn hypervolume(points: &[Vec<f64>], reference: &[f64]) -> f64 {
+    if reference.len() <= 3 {
+        exact(&dominating(points, reference), reference)
+    } else {
+        approximate_hypervolume(points, reference, DEFAULT_SAMPLES, DEFAULT_SEED)
+    }
+}
+
+/// Exact hypervolume of `points` with respect to `reference` for 1 to 3 objectives.
+///
+/// Fails with [Error::TooManyObjectives] beyond 3 objectives.
+pub fn exact_hypervolume(points: &[Vec<f64>], reference: &[f64]) -> Result<f64, Error> {
+    if reference.len() > 3 {
+        return Err(Error::TooManyObjectives(reference.len()));
+    }
+
+    Ok(exact(&dominating(points, reference), reference))
+}
+
+/// Exact hypervolume of `points`, which strictly dominate `reference`, for up to 3
+/// objectives.
+fn exact(points: &[&[f64]], reference: &[f64]) -> f64 {
+    match reference.len() {
+        0 => 0.0,
+        1 => points
+            .iter()
+            .map(|p| p[0] - reference[0])
+            .fold(0.0, f64::max),
+        2 => hypervolume_2d(points, reference),
+        3 => hypervolume_3d(points.to_vec(), reference),
+        n => unreachable!("exact hypervolume of {n} objectives"),
+    }
+}
+
+/// Monte Carlo estimate of the hypervolume of `points` with respect to `reference`
+/// from `samples` uniform samples in the bounding box of the points.
+pub fn approximate_hypervolume(
+    points: &[Vec<f64>],
+    reference: &[f64],
+    samples: usize,
+    seed: u64,
+) -> f64 {
+    let points = dominating(points, reference);
+    if points.is_empty() || samples == 0 {
+        return 0.0;
+    }
+
+    let upper = bounding_box(&points, reference);
+    let mut rng = XorShift::new(seed);
+    let mut sample = vec![0.0; reference.len()];
+    let mut hits = 0usize;
+    for _ in 0..samples {
+        draw(&mut rng, reference, &upper, &mut sample);
+        if points.iter().any(|p| covers(p, &sample)) {
+            hits += 1;
+        }
+    }
+
+    box_volume(&upper, reference) * hits as f64 / samples as f64
+}
+
+/// Hypervolume of the completed trials with respect to `reference`, given in the units
+/// of the objective metrics - the metrics of `metrics` without safety config, in
+/// order.
+///
+/// Fails with [Error::ReferenceLength] if `reference` does not have one value per
+/// objective.
+pub fn study_hypervolume(
+    trials: &[Trial],
+    metrics: &[MetricSpec],
+    reference: &[f64],
+) -> Result<f64, Error> {
+    let reference = oriented_reference(metrics, reference)?;
+    let points: Vec<Vec<f64>> = trials
+        .iter()
+        .filter_map(|t| objective_values(t, metrics))
+        .collect();
+
+    Ok(hypervolume(&points, &reference))
+}
+
+/// Hypervolume of the first `i + 1` trials for each index `i` - how the study
+/// progressed. Order the trials by id or completion time beforehand.
+///
+/// The curve never decreases: beyond 3 objectives, the samples are drawn once in the
+/// bounding box of all the trials and each trial only adds the samples it covers.
+///
+/// Up to 3 objectives, the hypervolume is only recomputed when a trial enters the
+/// Pareto front of the trials before it, and then only over that front: a dominated
+/// trial costs a scan of the front, a trial of the front a hypervolume computation
+/// over it.
+///
+/// Fails with [Error::ReferenceLength] if `reference` does not have one value per
+/// objective.
+pub fn hypervolume_curve(
+    trials: &[Trial],
+    metrics: &[MetricSpec],
+    reference: &[f64],
+) -> Result<Vec<f64>, Error> {
+    let reference = oriented_reference(metrics, reference)?;
+    let values: Vec<Option<Vec<f64>>> = trials
+        .iter()
+        .map(|t| objective_values(t, metrics))
+        .collect();
+
+    if reference.len() > 3 {
+        return Ok(approximate_curve(
+            &values,
+            &reference,
+            DEFAULT_SAMPLES,
+            DEFAULT_SEED,
+        ));
+    }
+
+    let mut front: Vec<Vec<f64>> = vec![];
+    let mut current = 0.0;
+    Ok(values
+        .into_iter()
+        .map(|values| {
+            let point = values.filter(|p| strictly_dominates(p, &reference));
+            if let Some(point) = point.filter(|p| !front.iter().any(|q| covers(q, p))) {
+                front.retain(|q| !covers(&point, q));
+                front.push(point);
+                let front: Vec<&[f64]> = front.iter().map(Vec::as_slice).collect();
+                current = exact(&front, &reference);
+            }
+            current
+        })
+        .collect())
+}
+
+/// Monte Carlo estimates of the hypervolume of the first `i + 1` points for each
+/// index `i`, with the same `samples` for every estimate.
+fn approximate_curve(
+    values: &[Option<Vec<f64>>],
+    reference: &[f64],
+    samples: usize,
+    seed: u64,
+) -> Vec<f64> {
+    let all: Vec<Vec<f64>> = values.iter().flatten().cloned().collect();
+    let points = dominating(&all, reference);
+    if points.is_empty() || samples == 0 {
+        return vec![0.0; values.len()];
+    }
+
+    let upper = bounding_box(&points, reference);
+    let volume = box_volume(&upper, reference);
+    let mut covered = vec![false; samples];
+    let mut hits = 0usize;
+    let mut sample = vec![0.0; reference.len()];
+    values
+        .iter()
+        .map(|values| {
+            let point = values
+                .as_deref()
+                .filter(|p| strictly_dominates(p, reference));
+            if let Some(point) = point {
+                // replay the same samples and count the ones newly covered
+                let mut rng = XorShift::new(seed);
+                for covered in covered.iter_mut() {
+                    draw(&mut rng, reference, &upper, &mut sample);
+                    if !*covered && covers(point, &sample) {
+                        *covered = true;
+                        hits += 1;
+                    }
+                }
+            }
+            volume * hits as f64 / samples as f64
+        })
+        .collect()
+}
+
+fn oriented_reference(metrics: &[MetricSpec], reference: &[f64]) -> Result<Vec<f64>, Error> {
+    let objectives = objectives(metrics);
+    if objectives.len() != reference.len() {
+        return Err(Error::ReferenceLength {
+            expected: objectives.len(),
+            actual: reference.len(),
+        });
+    }
+
+    Ok(objectives
+        .into_iter()
+        .zip(reference)
+        .map(|(metric, r)| oriented(metric, *r))
+        .collect())
+}
+
+/// Upper corner of the bounding box of `points` and `reference`.
+fn bounding_box(points: &[&[f64]], reference: &[f64]) -> Vec<f64> {
+    (0..reference.len())
+        .map(|k| points.iter().map(|p| p[k]).fold(f64::MIN, f64::max))
+        .collect()
+}
+
+fn box_volume(upper: &[f64], reference: &[f64]) -> f64 {
+    upper.iter().zip(reference).map(|(u, r)| u - r).product()
+}
+
+/// Draws a uniform sample in the box between `reference` and `upper`.
+fn draw(rng: &mut XorShift, reference: &[f64], upper: &[f64], sample: &mut [f64]) {
+    for (k, s) in sample.iter_mut().enumerate() {
+        *s = reference[k] + rng.next_f64() * (upper[k] - reference[k]);
+    }
+}
+
+fn covers(point: &[f64], sample: &[f64]) -> bool {
+    point.iter().zip(sample).all(|(x, s)| x >= s)
+}
+
+fn strictly_dominates(point: &[f64], reference: &[f64]) -> bool {
+    point.len() == reference.len() && point.iter().zip(reference).all(|(x, r)| x > r)
+}
+
+fn dominating<'a>(points: &'a [Vec<f64>], reference: &[f64]) -> Vec<&'a [f64]> {
+    points
+        .iter()
+        .map(Vec::as_slice)
+        .filter(|p| strictly_dominates(p, reference))
+        .collect()
+}
+
+/// Area dominated by 2D points.
+fn hypervolume_2d(points: &[&[f64]], reference: &[f64]) -> f64 {
+    let mut staircase = Staircase::new(reference);
+    for p in points {
+        staircase.insert(p[0], p[1]);
+    }
+
+    staircase.area
+}
+
+/// Volume dominated by 3D points - sweep by decreasing third objective, adding the
+/// points to the staircase of the slice.
+fn hypervolume_3d(mut points: Vec<&[f64]>, reference: &[f64]) -> f64 {
+    points.sort_by(|a, b| b[2].total_cmp(&a[2]));
+
+    let mut staircase = Staircase::new(reference);
+    let mut volume = 0.0;
+    for (i, p) in points.iter().enumerate() {
+        staircase.insert(p[0], p[1]);
+        let z_low = points.get(i + 1).map_or(reference[2], |q| q[2]);
+        volume += staircase.area * (p[2] - z_low);
+    }
+
+    volume
+}
+
+/// The non-dominated 2D points by increasing first - and so decreasing second -
+/// objective, and the area they dominate.
+struct Staircase {
+    reference: (f64, f64),
+    points: Vec<(f64, f64)>,
+    area: f64,
+}
+
+impl Staircase {
+    fn new(reference: &[f64]) -> Self {
+        Self {
+            reference: (reference[0], reference[1]),
+            points: vec![],
+            area: 0.0,
+        }
+    }
+
+    /// Adds a point that strictly dominates the reference - the area grows by the part
+    /// of its box that no point dominates yet.
+    fn insert(&mut self, x: f64, y: f64) {
+        // the first point at or right of x - it has the highest second objective there
+        let right = self.points.partition_point(|p| p.0 < x);
+        let mut covered = self.reference.1;
+        let mut end = right;
+        if let Some(&(next_x, next_y)) = self.points.get(right) {
+            if next_y >= y {
+                return;
+            }
+            covered = next_y;
+            if next_x == x {
+                end += 1;
+            }
+        }
+
+        // walk left, through the points the new one dominates
+        let mut start = right;
+        let mut upper_x = x;
+        while start > 0 && self.points[start - 1].1 <= y {
+            let (px, py) = self.points[start - 1];
+            self.area += (upper_x - px) * (y - covered);
+            upper_x = px;
+            covered = py;
+            start -= 1;
+        }
+        let lower_x = start
+            .checked_sub(1)
+            .map_or(self.reference.0, |i| self.points[i].0);
+        self.area += (upper_x - lower_x) * (y - covered);
+
+        self.points.splice(start..end, [(x, y)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Error, approximate_hypervolume, exact_hypervolume, hypervolume, hypervolume_curve,
+        study_hypervolume,
+    };
+    use crate::analysis::testing::{metric, trial};
+    use crate::vizier::study_spec::metric_spec::GoalType;
+
+    #[test]
+    fn it_computes_exact_hypervolumes() {
+        let points = vec![
+            vec![1.0, 3.0],
+            vec![2.0, 2.0],
+            vec![3.0, 1.0],
+            vec![1.0, 1.0],
+        ];
+        assert_eq!(exact_hypervolume(&points, &[0.0, 0.0]), Ok(6.0));
+
+        let points = vec![vec![1.0, 1.0, 1.0], vec![2.0, 2.0, 0.5]];
+        // 1 + 4 * 0.5 - 1 * 0.5 (overlap)
+        assert_eq!(exact_hypervolume(&points, &[0.0, 0.0, 0.0]), Ok(2.5));
+
+        // points not dominating the reference are ignored
+        assert_eq!(exact_hypervolume(&[vec![-1.0, 5.0]], &[0.0, 0.0]), Ok(0.0));
+
+        assert_eq!(
+            exact_hypervolume(&[vec![1.0; 4]], &[0.0; 4]),
+            Err(Error::TooManyObjectives(4))
+        );
+    }
+
+    #[test]
+    fn it_matches_the_hypervolume_of_each_prefix() {
+        // volume of the unit cells covered by points of integer coordinates
+        fn cells(points: &[Vec<f64>], dimension: usize) -> f64 {
+            let mut count = 0;
+            for cell in 0..6usize.pow(dimension as u32) {
+                let corner: Vec<f64> = (0..dimension)
+                    .map(|k| (cell / 6usize.pow(k as u32) % 6) as f64 + 1.0)
+                    .collect();
+                if points
+                    .iter()
+                    .any(|p| p.iter().zip(&corner).all(|(x, c)| x >= c))
+                {
+                    count += 1;
+                }
+            }
+            count as f64
+        }
+
+        for dimension in [2, 3] {
+            let ids: Vec<String> = (0..dimension).map(|k| format!("f{k}")).collect();
+            let metrics: Vec<_> = ids
+                .iter()
+                .map(|id| metric(id, GoalType::Maximize, None))
+                .collect();
+            // spread around the plane x1 + ... + xd = 4 * (d - 1) + 1
+            let points: Vec<Vec<f64>> = (0..40)
+                .map(|i| {
+                    let mut p: Vec<usize> = (0..dimension - 1)
+                        .map(|k| (i * 37 * (k + 1) + 11 * k) % 97 % 6 + 1)
+                        .collect();
+                    let last = (4 * (dimension - 1) + 1).saturating_sub(p.iter().sum());
+                    p.push(last.clamp(1, 6) - usize::from(i % 3 == 0));
+                    p.into_iter().map(|v| v as f64).collect()
+                })
+                .collect();
+            let trials: Vec<_> = points
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let values: Vec<(&str, f64)> =
+                        ids.iter().map(String::as_str).zip(p.clone()).collect();
+                    trial(i, &values)
+                })
+                .collect();
+
+            let curve = hypervolume_curve(&trials, &metrics, &vec![0.0; dimension]).unwrap();
+            for (i, hv) in curve.iter().enumerate() {
+                assert_eq!(*hv, cells(&points[..=i], dimension), "{dimension}d, {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn it_approximates_hypervolumes() {
+        let points = vec![vec![1.0, 1.0, 1.0], vec![2.0, 2.0, 0.5]];
+        let approx = approximate_hypervolume(&points, &[0.0, 0.0, 0.0], 200_000, 42);
+        assert!((approx - 2.5).abs() < 0.05, "{approx}");
+
+        let points = vec![vec![1.0; 4], vec![0.5, 0.5, 0.5, 2.0]];
+        let approx = hypervolume(&points, &[0.0; 4]);
+        // 1 + 0.125 * 2 - 0.125 (overlap)
+        assert!((approx - 1.125).abs() < 0.05, "{approx}");
+    }
+
+    #[test]
+    fn it_tracks_progress() {
+        let metrics = vec![
+            metric("accuracy", GoalType::Maximize, None),
+            metric("latency", GoalType::Minimize, None),
+        ];
+        let trials = vec![
+            trial(1, &[("accuracy", 0.6), ("latency", 50.0)]),
+            trial(2, &[("accuracy", 0.55), ("latency", 90.0)]),
+            trial(3, &[("accuracy", 0.8), ("latency", 50.0)]),
+        ];
+
+        let curve = hypervolume_curve(&trials, &metrics, &[0.5, 100.0]).unwrap();
+
+        assert_eq!(curve.len(), 3);
+        assert!((curve[0] - 5.0).abs() < 1e-9);
+        assert!((curve[1] - 5.0).abs() < 1e-9);
+        assert!((curve[2] - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_never_decreases_beyond_3_objectives() {
+        let metrics: Vec<_> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|id| metric(id, GoalType::Maximize, None))
+            .collect();
+        let trials = vec![
+            trial(1, &[("a", 1.0), ("b", 1.0), ("c", 1.0), ("d", 1.0)]),
+            trial(2, &[("a", 0.2), ("b", 0.2), ("c", 0.2), ("d", 0.2)]),
+            trial(3, &[("a", 0.5), ("b", 0.5), ("c", 0.5), ("d", 2.0)]),
+        ];
+
+        let curve = hypervolume_curve(&trials, &metrics, &[0.0; 4]).unwrap();
+
+        // the dominated second trial covers no new sample
+        assert_eq!(curve[0], curve[1]);
+        assert!(curve[2] > curve[1]);
+        assert!((curve[2] - 1.125).abs() < 0.05, "{}", curve[2]);
+    }
+
+    #[test]
+    fn it_rejects_a_reference_of_the_wrong_length() {
+        let metrics = vec![
+            metric("accuracy", GoalType::Maximize, None),
+            metric("latency", GoalType::Minimize, None),
+        ];
+        let trials = vec![trial(1, &[("accuracy", 0.6), ("latency", 50.0)])];
+
+        let error = Error::ReferenceLength {
+            expected: 2,
+            actual: 1,
+        };
+        assert_eq!(
+            study_hypervolume(&trials, &metrics, &[0.5]),
+            Err(error.clone())
+        );
+        assert_eq!(hypervolume_curve(&trials, &metrics, &[0.5]), Err(error));
+    }
+}
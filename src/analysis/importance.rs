@@ -0,0 +1,1135 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parameter importance - which parameters mattered, fANOVA-style.
+//!
+//! A random forest is fitted on the succeeded trials, with the parameters scaled to
+//! `[0, 1]` according to their `ScaleType`. The variance of the prediction of each
+//! tree over the search space is decomposed into the variance of the main effects of
+//! the parameters and of their pairwise interactions, as in [fANOVA] - the importances
+//! are the fractions of the total variance, averaged over the trees.
+//!
+//! Categorical parameters are split on subsets of their values. The parameters of
+//! conditional specs that are not active in a trial are a separate region of their
+//! domain - their importance includes whether they are active.
+//!
+//! ```no_run
+//! # use oss_vizier::analysis::importance::{ImportanceConfig, parameter_importance};
+//! # use oss_vizier::vizier::{StudySpec, Trial};
+//! # fn example(trials: Vec<Trial>, study_spec: StudySpec) -> Result<(), Box<dyn std::error::Error>> {
+//! let importance = parameter_importance(
+//!     &study_spec,
+//!     &trials,
+//!     "accuracy",
+//!     &ImportanceConfig::default().with_num_trees(64),
+//! )?;
+//! for effect in &importance.main_effects {
+//!     println!("{}: {:.3}", effect.parameter_id, effect.importance);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [fANOVA]: https://proceedings.mlr.press/v32/hutter14.html
+
+use std::collections::BTreeSet;
+
+use prost_types::value::Kind;
+
+use crate::Trial;
+use crate::analysis::{XorShift, final_metric, scale};
+use crate::vizier::StudySpec;
+use crate::vizier::study_spec::ParameterSpec;
+use crate::vizier::study_spec::parameter_spec::{ParameterValueSpec, ScaleType};
+use crate::vizier::trial::State;
+
+/// Error returned by [parameter_importance].
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    /// Not enough succeeded trials with a finite value of the metric and parameter
+    /// values in the spec.
+    #[error("need at least 2 trials with a value for the metric, got {0}")]
+    NotEnoughTrials(usize),
+    /// The study spec has no parameter.
+    #[error("the study spec has no parameter")]
+    NoParameters,
+    /// A parameter spec has no value spec.
+    #[error("parameter {0} has no value spec")]
+    MissingValueSpec(String),
+}
+
+/// Configuration of the random forest of [parameter_importance].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportanceConfig {
+    /// Number of trees of the forest.
+    pub num_trees: usize,
+    /// Maximum depth of the trees.
+    pub max_depth: usize,
+    /// Minimum number of trials in a leaf.
+    pub min_samples_leaf: usize,
+    /// Fraction of the parameters considered at each split.
+    pub max_features: f64,
+    /// Seed of the bootstrap samples and of the parameters considered at each split.
+    pub seed: u64,
+}
+
+impl Default for ImportanceConfig {
+    fn default() -> Self {
+        Self {
+            num_trees: 32,
+            max_depth: 16,
+            min_samples_leaf: 1,
+            max_features: 0.7,
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+}
+
+impl ImportanceConfig {
+    /// Sets the number of trees.
+    pub fn with_num_trees(mut self, num_trees: usize) -> Self {
+        self.num_trees = num_trees;
+        self
+    }
+
+    /// Sets the maximum depth of the trees.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the minimum number of trials in a leaf.
+    pub fn with_min_samples_leaf(mut self, min_samples_leaf: usize) -> Self {
+        self.min_samples_leaf = min_samples_leaf;
+        self
+    }
+
+    /// Sets the fraction of the parameters considered at each split.
+    pub fn with_max_features(mut self, max_features: f64) -> Self {
+        self.max_features = max_features;
+        self
+    }
+
+    /// Sets the seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Importance of a parameter on its own.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MainEffect {
+    /// Id of the parameter.
+    pub parameter_id: String,
+    /// Fraction of the variance explained by the parameter - averaged over the trees.
+    pub importance: f64,
+    /// Standard deviation of the fraction over the trees.
+    pub std: f64,
+}
+
+/// Importance of the interaction of two parameters - beyond their main effects.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PairwiseEffect {
+    /// Ids of the parameters.
+    pub parameter_ids: (String, String),
+    /// Fraction of the variance explained by the interaction - averaged over the
+    /// trees.
+    pub importance: f64,
+    /// Standard deviation of the fraction over the trees.
+    pub std: f64,
+}
+
+/// Result of [parameter_importance].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Importance {
+    /// Id of the metric.
+    pub metric_id: String,
+    /// Number of trials the forest was fitted on.
+    pub num_trials: usize,
+    /// Main effects, by decreasing importance.
+    pub main_effects: Vec<MainEffect>,
+    /// Pairwise effects, by decreasing importance.
+    pub pairwise_effects: Vec<PairwiseEffect>,
+}
+
+#[cfg(feature = "serde")]
+impl Importance {
+    /// The importances as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Fits a random forest on the succeeded trials with a finite value of the metric
+/// `metric_id` and computes the importance of the parameters of `study_spec`,
+/// including the parameters of conditional specs.
+///
+/// Trials with a parameter value outside `study_spec` - a category that is not in the
+/// spec, a number out of its bounds, or a value of the wrong type - are skipped, as are
+/// trials without a value for a parameter that is not conditional.
+pub fn parameter_importance(
+    study_spec: &StudySpec,
+    trials: &[Trial],
+    metric_id: &str,
+    config: &ImportanceConfig,
+) -> Result<Importance, Error> {
+    let features = features(&study_spec.parameters)?;
+    if features.is_empty() {
+        return Err(Error::NoParameters);
+    }
+
+    let (rows, targets): (Vec<Vec<f64>>, Vec<f64>) = trials
+        .iter()
+        .filter(|t| t.state() == State::Succeeded)
+        .filter_map(|t| {
+            let y = final_metric(t, metric_id).filter(|v| v.is_finite())?;
+            let row = features
+                .iter()
+                .map(|f| f.encode(t))
+                .collect::<Option<_>>()?;
+            Some((row, y))
+        })
+        .unzip();
+    if rows.len() < 2 {
+        return Err(Error::NotEnoughTrials(rows.len()));
+    }
+
+    let num_features = features.len();
+    let max_features =
+        ((num_features as f64 * config.max_features).ceil() as usize).clamp(1, num_features);
+    let domains: Vec<Domain> = features.iter().map(|f| f.domain.clone()).collect();
+
+    let mut rng = XorShift::new(config.seed);
+    let mut main = vec![vec![]; num_features];
+    let mut pairwise = vec![vec![]; num_features * num_features];
+    for _ in 0..config.num_trees.max(1) {
+        let sample: Vec<usize> = (0..rows.len()).map(|_| rng.below(rows.len())).collect();
+        let tree = Tree::fit(
+            &rows,
+            &targets,
+            sample,
+            &domains,
+            config,
+            max_features,
+            &mut rng,
+        );
+
+        let Some(decomposition) = decompose(&tree, &domains) else {
+            // constant tree - no variance to explain
+            continue;
+        };
+        for i in 0..num_features {
+            main[i].push(decomposition.main[i]);
+            for j in (i + 1)..num_features {
+                pairwise[i * num_features + j].push(decomposition.pairwise[i * num_features + j]);
+            }
+        }
+    }
+
+    let mut main_effects: Vec<MainEffect> = features
+        .iter()
+        .zip(&main)
+        .map(|(feature, fractions)| {
+            let (importance, std) = mean_std(fractions);
+            MainEffect {
+                parameter_id: feature.parameter_id.clone(),
+                importance,
+                std,
+            }
+        })
+        .collect();
+    main_effects.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+
+    let mut pairwise_effects = vec![];
+    for i in 0..num_features {
+        for j in (i + 1)..num_features {
+            let (importance, std) = mean_std(&pairwise[i * num_features + j]);
+            pairwise_effects.push(PairwiseEffect {
+                parameter_ids: (
+                    features[i].parameter_id.clone(),
+                    features[j].parameter_id.clone(),
+                ),
+                importance,
+                std,
+            });
+        }
+    }
+    pairwise_effects.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+
+    Ok(Importance {
+        metric_id: metric_id.to_string(),
+        num_trials: rows.len(),
+        main_effects,
+        pairwise_effects,
+    })
+}
+
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+/// Value of the features of inactive conditional parameters.
+const INACTIVE: f64 = -1.0;
+
+/// Domain of a feature - uniform measure over it.
+#[derive(Clone, Debug, PartialEq)]
+enum Domain {
+    /// `[lower, 1]` - `lower` is [INACTIVE] for conditional parameters.
+    Numeric { lower: f64 },
+    /// Category indices - the last one stands for inactive for conditional
+    /// parameters.
+    Categorical { num_categories: usize },
+}
+
+#[derive(Clone, Debug)]
+enum Encoding {
+    /// Scaled to `[0, 1]`.
+    Scaled {
+        min: f64,
+        max: f64,
+        scale_type: ScaleType,
+    },
+    /// Index of the category.
+    Categories(Vec<String>),
+}
+
+/// A parameter encoded as a feature of the forest.
+#[derive(Clone, Debug)]
+struct Feature {
+    parameter_id: String,
+    encoding: Encoding,
+    domain: Domain,
+    /// Whether the parameter is a conditional parameter - it may have no value.
+    conditional: bool,
+}
+
+impl Feature {
+    fn new(spec: &ParameterSpec, conditional: bool) -> Result<Self, Error> {
+        let scale_type = spec.scale_type();
+        let encoding = match &spec.parameter_value_spec {
+            None => return Err(Error::MissingValueSpec(spec.parameter_id.clone())),
+            Some(ParameterValueSpec::DoubleValueSpec(s)) => Encoding::Scaled {
+                min: s.min_value,
+                max: s.max_value,
+                scale_type,
+            },
+            Some(ParameterValueSpec::IntegerValueSpec(s)) => Encoding::Scaled {
+                min: s.min_value as f64,
+                max: s.max_value as f64,
+                scale_type,
+            },
+            Some(ParameterValueSpec::DiscreteValueSpec(s)) => Encoding::Scaled {
+                min: s.values.iter().copied().fold(f64::INFINITY, f64::min),
+                max: s.values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                scale_type,
+            },
+            Some(ParameterValueSpec::CategoricalValueSpec(s)) => {
+                Encoding::Categories(s.values.clone())
+            }
+        };
+
+        let domain = match &encoding {
+            Encoding::Scaled { .. } => Domain::Numeric {
+                lower: if conditional { INACTIVE } else { 0.0 },
+            },
+            Encoding::Categories(values) => Domain::Categorical {
+                num_categories: values.len() + usize::from(conditional),
+            },
+        };
+
+        Ok(Self {
+            parameter_id: spec.parameter_id.clone(),
+            encoding,
+            domain,
+            conditional,
+        })
+    }
+
+    /// The feature of a trial - the inactive value if the trial has no value for a
+    /// conditional parameter, `None` if the trial has no value for a parameter that is
+    /// not conditional or if the value is outside the spec, e.g. an unknown category or
+    /// a number out of bounds.
+    fn encode(&self, trial: &Trial) -> Option<f64> {
+        let kind = trial
+            .parameters
+            .iter()
+            .find(|p| p.parameter_id == self.parameter_id)
+            .and_then(|p| p.value.as_ref())
+            .and_then(|v| v.kind.as_ref());
+
+        match (&self.encoding, kind) {
+            (_, None) => self.conditional.then(|| self.inactive()),
+            (
+                Encoding::Scaled {
+                    min,
+                    max,
+                    scale_type,
+                },
+                Some(Kind::NumberValue(v)),
+            ) => (min..=max)
+                .contains(&v)
+                .then(|| scale(*v, *min, *max, *scale_type)),
+            (Encoding::Categories(values), Some(Kind::StringValue(v))) => {
+                values.iter().position(|c| c == v).map(|index| index as f64)
+            }
+            _ => None,
+        }
+    }
+
+    fn inactive(&self) -> f64 {
+        match self.domain {
+            Domain::Numeric { lower } => lower,
+            Domain::Categorical { num_categories } => num_categories.saturating_sub(1) as f64,
+        }
+    }
+}
+
+/// The features of the parameters and of their conditional parameters, depth-first.
+fn features(specs: &[ParameterSpec]) -> Result<Vec<Feature>, Error> {
+    fn walk(
+        specs: &[ParameterSpec],
+        conditional: bool,
+        out: &mut Vec<Feature>,
+    ) -> Result<(), Error> {
+        for spec in specs {
+            out.push(Feature::new(spec, conditional)?);
+            let children: Vec<ParameterSpec> = spec
+                .conditional_parameter_specs
+                .iter()
+                .filter_map(|c| c.parameter_spec.clone())
+                .collect();
+            walk(&children, true, out)?;
+        }
+        Ok(())
+    }
+
+    let mut out = vec![];
+    walk(specs, false, &mut out)?;
+
+    // the same conditional parameter may appear under several parent values
+    let mut seen = BTreeSet::new();
+    out.retain(|f| seen.insert(f.parameter_id.clone()));
+
+    Ok(out)
+}
+
+#[derive(Clone, Debug)]
+enum Split {
+    /// `x < threshold` goes left.
+    Numeric { feature: usize, threshold: f64 },
+    /// `left[x]` goes left.
+    Categorical { feature: usize, left: Vec<bool> },
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Leaf(f64),
+    Split {
+        split: Split,
+        left: usize,
+        right: usize,
+    },
+}
+
+/// Regression tree - CART with squared error.
+struct Tree {
+    nodes: Vec<Node>,
+}
+
+struct Fit<'a> {
+    rows: &'a [Vec<f64>],
+    targets: &'a [f64],
+    domains: &'a [Domain],
+    config: &'a ImportanceConfig,
+    max_features: usize,
+}
+
+impl Tree {
+    fn fit(
+        rows: &[Vec<f64>],
+        targets: &[f64],
+        sample: Vec<usize>,
+        domains: &[Domain],
+        config: &ImportanceConfig,
+        max_features: usize,
+        rng: &mut XorShift,
+    ) -> Self {
+        let fit = Fit {
+            rows,
+            targets,
+            domains,
+            config,
+            max_features,
+        };
+        let mut tree = Tree { nodes: vec![] };
+        tree.grow(&fit, sample, 0, rng);
+        tree
+    }
+
+    fn grow(&mut self, fit: &Fit, sample: Vec<usize>, depth: usize, rng: &mut XorShift) -> usize {
+        let index = self.nodes.len();
+        let mean = sample.iter().map(|&i| fit.targets[i]).sum::<f64>() / sample.len() as f64;
+        self.nodes.push(Node::Leaf(mean));
+
+        if depth >= fit.config.max_depth || sample.len() < 2 * fit.config.min_samples_leaf.max(1) {
+            return index;
+        }
+
+        let Some(split) = best_split(fit, &sample, rng) else {
+            return index;
+        };
+        let (left, right): (Vec<usize>, Vec<usize>) = sample
+            .into_iter()
+            .partition(|&i| goes_left(&split, &fit.rows[i]));
+
+        let left = self.grow(fit, left, depth + 1, rng);
+        let right = self.grow(fit, right, depth + 1, rng);
+        self.nodes[index] = Node::Split { split, left, right };
+
+        index
+    }
+}
+
+fn goes_left(split: &Split, row: &[f64]) -> bool {
+    match split {
+        Split::Numeric { feature, threshold } => row[*feature] < *threshold,
+        Split::Categorical { feature, left } => left[row[*feature] as usize],
+    }
+}
+
+/// The split of `sample` with the least squared error among `max_features` random
+/// features, if any reduces it.
+fn best_split(fit: &Fit, sample: &[usize], rng: &mut XorShift) -> Option<Split> {
+    let num_features = fit.domains.len();
+    let mut candidates: Vec<usize> = (0..num_features).collect();
+    for i in 0..fit.max_features {
+        let j = i + rng.below(num_features - i);
+        candidates.swap(i, j);
+    }
+
+    let total: f64 = sample.iter().map(|&i| fit.targets[i]).sum();
+    let total_sq: f64 = sample.iter().map(|&i| fit.targets[i].powi(2)).sum();
+    let parent_error = total_sq - total * total / sample.len() as f64;
+    if parent_error <= 1e-12 * total_sq.max(1.0) {
+        return None;
+    }
+
+    let mut best: Option<(f64, Split)> = None;
+    for &feature in &candidates[..fit.max_features] {
+        let candidate = match &fit.domains[feature] {
+            Domain::Numeric { .. } => best_numeric_split(fit, sample, feature),
+            Domain::Categorical { num_categories } => {
+                best_categorical_split(fit, sample, feature, *num_categories)
+            }
+        };
+        if let Some((error, split)) = candidate
+            && error < parent_error - 1e-12
+            && best.as_ref().is_none_or(|(e, _)| error < *e)
+        {
+            best = Some((error, split));
+        }
+    }
+
+    best.map(|(_, split)| split)
+}
+
+/// Squared error of the two sides of a split from their sums, sums of squares and
+/// counts.
+fn split_error(left: (f64, f64, usize), right: (f64, f64, usize)) -> f64 {
+    let error = |(sum, sum_sq, n): (f64, f64, usize)| sum_sq - sum * sum / n as f64;
+    error(left) + error(right)
+}
+
+fn best_numeric_split(fit: &Fit, sample: &[usize], feature: usize) -> Option<(f64, Split)> {
+    let mut order: Vec<usize> = sample.to_vec();
+    order.sort_by(|&a, &b| fit.rows[a][feature].total_cmp(&fit.rows[b][feature]));
+
+    let total: f64 = order.iter().map(|&i| fit.targets[i]).sum();
+    let total_sq: f64 = order.iter().map(|&i| fit.targets[i].powi(2)).sum();
+    let min_leaf = fit.config.min_samples_leaf.max(1);
+
+    let mut best: Option<(f64, Split)> = None;
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    for k in 1..order.len() {
+        let y = fit.targets[order[k - 1]];
+        sum += y;
+        sum_sq += y * y;
+
+        let (low, high) = (fit.rows[order[k - 1]][feature], fit.rows[order[k]][feature]);
+        if low == high || k < min_leaf || order.len() - k < min_leaf {
+            continue;
+        }
+
+        let error = split_error(
+            (sum, sum_sq, k),
+            (total - sum, total_sq - sum_sq, order.len() - k),
+        );
+        if best.as_ref().is_none_or(|(e, _)| error < *e) {
+            let threshold = (low + high) / 2.0;
+            best = Some((error, Split::Numeric { feature, threshold }));
+        }
+    }
+
+    best
+}
+
+/// Categories sorted by mean target - the best split is one of the prefixes.
+fn best_categorical_split(
+    fit: &Fit,
+    sample: &[usize],
+    feature: usize,
+    num_categories: usize,
+) -> Option<(f64, Split)> {
+    let mut stats = vec![(0.0, 0.0, 0usize); num_categories];
+    for &i in sample {
+        let c = fit.rows[i][feature] as usize;
+        let y = fit.targets[i];
+        stats[c].0 += y;
+        stats[c].1 += y * y;
+        stats[c].2 += 1;
+    }
+
+    let mut present: Vec<usize> = (0..num_categories).filter(|&c| stats[c].2 > 0).collect();
+    present.sort_by(|&a, &b| {
+        let mean = |c: usize| stats[c].0 / stats[c].2 as f64;
+        mean(a).total_cmp(&mean(b))
+    });
+
+    let (total, total_sq): (f64, f64) = present
+        .iter()
+        .fold((0.0, 0.0), |(s, q), &c| (s + stats[c].0, q + stats[c].1));
+    let min_leaf = fit.config.min_samples_leaf.max(1);
+
+    let mut best: Option<(f64, usize)> = None;
+    let (mut sum, mut sum_sq, mut n) = (0.0, 0.0, 0);
+    for k in 1..present.len() {
+        let (s, q, m) = stats[present[k - 1]];
+        sum += s;
+        sum_sq += q;
+        n += m;
+        if n < min_leaf || sample.len() - n < min_leaf {
+            continue;
+        }
+
+        let error = split_error(
+            (sum, sum_sq, n),
+            (total - sum, total_sq - sum_sq, sample.len() - n),
+        );
+        if best.is_none_or(|(e, _)| error < e) {
+            best = Some((error, k));
+        }
+    }
+
+    // categories absent from the sample go right
+    best.map(|(error, k)| {
+        let mut left = vec![false; num_categories];
+        for &c in &present[..k] {
+            left[c] = true;
+        }
+        (error, Split::Categorical { feature, left })
+    })
+}
+
+/// Region of a feature covered by a leaf.
+#[derive(Clone, Debug)]
+enum Region {
+    Interval(f64, f64),
+    Categories(Vec<bool>),
+}
+
+impl Region {
+    fn full(domain: &Domain) -> Self {
+        match domain {
+            Domain::Numeric { lower } => Region::Interval(*lower, 1.0),
+            Domain::Categorical { num_categories } => {
+                Region::Categories(vec![true; *num_categories])
+            }
+        }
+    }
+
+    /// Fraction of the domain covered by the region.
+    fn fraction(&self, domain: &Domain) -> f64 {
+        match (self, domain) {
+            (Region::Interval(low, high), Domain::Numeric { lower }) => {
+                (high - low).max(0.0) / (1.0 - lower)
+            }
+            (Region::Categories(allowed), Domain::Categorical { num_categories }) => {
+                allowed.iter().filter(|a| **a).count() as f64 / *num_categories as f64
+            }
+            _ => unreachable!("region and domain mismatch"),
+        }
+    }
+}
+
+/// Cell of the partition of a feature domain induced by the splits of a tree.
+#[derive(Clone, Debug)]
+enum Cell {
+    Interval(f64, f64),
+    Category(usize),
+}
+
+impl Cell {
+    fn within(&self, region: &Region) -> bool {
+        match (self, region) {
+            (Cell::Interval(low, high), Region::Interval(a, b)) => a <= low && high <= b,
+            (Cell::Category(c), Region::Categories(allowed)) => allowed[*c],
+            _ => false,
+        }
+    }
+
+    fn fraction(&self, domain: &Domain) -> f64 {
+        match (self, domain) {
+            (Cell::Interval(low, high), Domain::Numeric { lower }) => (high - low) / (1.0 - lower),
+            (Cell::Category(_), Domain::Categorical { num_categories }) => {
+                1.0 / *num_categories as f64
+            }
+            _ => unreachable!("cell and domain mismatch"),
+        }
+    }
+}
+
+struct Leaf {
+    value: f64,
+    regions: Vec<Region>,
+    /// Fraction of the search space covered by the leaf.
+    weight: f64,
+    /// Fraction of the domain of each feature covered by the leaf.
+    fractions: Vec<f64>,
+}
+
+fn leaves(tree: &Tree, domains: &[Domain]) -> Vec<Leaf> {
+    fn walk(
+        tree: &Tree,
+        node: usize,
+        regions: Vec<Region>,
+        domains: &[Domain],
+        out: &mut Vec<Leaf>,
+    ) {
+        match &tree.nodes[node] {
+            Node::Leaf(value) => {
+                let fractions: Vec<f64> = regions
+                    .iter()
+                    .zip(domains)
+                    .map(|(r, d)| r.fraction(d))
+                    .collect();
+                let weight = fractions.iter().product();
+                if weight > 0.0 {
+                    out.push(Leaf {
+                        value: *value,
+                        regions,
+                        weight,
+                        fractions,
+                    });
+                }
+            }
+            Node::Split { split, left, right } => {
+                let (mut l, mut r) = (regions.clone(), regions);
+                match split {
+                    Split::Numeric { feature, threshold } => {
+                        if let (Region::Interval(_, high), Region::Interval(low, _)) =
+                            (&mut l[*feature], &mut r[*feature])
+                        {
+                            *high = high.min(*threshold);
+                            *low = low.max(*threshold);
+                        }
+                    }
+                    Split::Categorical { feature, left } => {
+                        if let (Region::Categories(a), Region::Categories(b)) =
+                            (&mut l[*feature], &mut r[*feature])
+                        {
+                            for (c, goes_left) in left.iter().enumerate() {
+                                a[c] &= *goes_left;
+                                b[c] &= !*goes_left;
+                            }
+                        }
+                    }
+                }
+                walk(tree, *left, l, domains, out);
+                walk(tree, *right, r, domains, out);
+            }
+        }
+    }
+
+    let mut out = vec![];
+    walk(
+        tree,
+        0,
+        domains.iter().map(Region::full).collect(),
+        domains,
+        &mut out,
+    );
+    out
+}
+
+/// Partition of the domain of `feature` by the leaves.
+fn cells(leaves: &[Leaf], feature: usize, domain: &Domain) -> Vec<Cell> {
+    match domain {
+        Domain::Numeric { lower } => {
+            let mut bounds = vec![*lower, 1.0];
+            for leaf in leaves {
+                if let Region::Interval(low, high) = leaf.regions[feature] {
+                    bounds.push(low);
+                    bounds.push(high);
+                }
+            }
+            bounds.sort_by(f64::total_cmp);
+            bounds.dedup();
+            bounds
+                .windows(2)
+                .map(|w| Cell::Interval(w[0], w[1]))
+                .collect()
+        }
+        Domain::Categorical { num_categories } => {
+            (0..*num_categories).map(Cell::Category).collect()
+        }
+    }
+}
+
+/// Fractions of the variance of a tree explained by each feature and pair of
+/// features.
+struct Decomposition {
+    main: Vec<f64>,
+    /// Indexed by `i * num_features + j` for `i < j`.
+    pairwise: Vec<f64>,
+}
+
+/// fANOVA decomposition of the prediction of a tree under the uniform measure over
+/// the domains - `None` if the prediction is constant.
+fn decompose(tree: &Tree, domains: &[Domain]) -> Option<Decomposition> {
+    let leaves = leaves(tree, domains);
+    let mean: f64 = leaves.iter().map(|l| l.weight * l.value).sum();
+    let variance: f64 = leaves
+        .iter()
+        .map(|l| l.weight * (l.value - mean).powi(2))
+        .sum();
+    if variance <= 1e-12 * mean.abs().max(1.0).powi(2) {
+        return None;
+    }
+
+    let num_features = domains.len();
+    let cells: Vec<Vec<Cell>> = (0..num_features)
+        .map(|i| cells(&leaves, i, &domains[i]))
+        .collect();
+
+    // variance of the marginal prediction of each feature
+    let mut main = vec![0.0; num_features];
+    for i in 0..num_features {
+        main[i] = cells[i]
+            .iter()
+            .map(|cell| {
+                let marginal: f64 = leaves
+                    .iter()
+                    .filter(|l| cell.within(&l.regions[i]))
+                    .map(|l| l.value * l.weight / l.fractions[i])
+                    .sum();
+                cell.fraction(&domains[i]) * (marginal - mean).powi(2)
+            })
+            .sum();
+    }
+
+    let mut pairwise = vec![0.0; num_features * num_features];
+    for i in 0..num_features {
+        for j in (i + 1)..num_features {
+            let mut joint = 0.0;
+            for a in &cells[i] {
+                for b in &cells[j] {
+                    let marginal: f64 = leaves
+                        .iter()
+                        .filter(|l| a.within(&l.regions[i]) && b.within(&l.regions[j]))
+                        .map(|l| l.value * l.weight / (l.fractions[i] * l.fractions[j]))
+                        .sum();
+                    joint += a.fraction(&domains[i])
+                        * b.fraction(&domains[j])
+                        * (marginal - mean).powi(2);
+                }
+            }
+            pairwise[i * num_features + j] = (joint - main[i] - main[j]).max(0.0) / variance;
+        }
+    }
+
+    for m in &mut main {
+        *m /= variance;
+    }
+
+    Some(Decomposition { main, pairwise })
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use super::{Error, ImportanceConfig, parameter_importance};
+    use crate::analysis::testing::trial;
+    use crate::vizier::study_spec::ParameterSpec;
+    use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::{
+        CategoricalValueCondition, ParentValueCondition,
+    };
+    use crate::vizier::study_spec::parameter_spec::{
+        CategoricalValueSpec, ConditionalParameterSpec, DoubleValueSpec, ParameterValueSpec,
+    };
+    use crate::vizier::{StudySpec, trial};
+
+    fn double(parameter_id: &str) -> ParameterSpec {
+        ParameterSpec {
+            parameter_id: parameter_id.to_string(),
+            parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(DoubleValueSpec {
+                min_value: 0.0,
+                max_value: 1.0,
+                default_value: None,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn categorical(parameter_id: &str, values: &[&str]) -> ParameterSpec {
+        ParameterSpec {
+            parameter_id: parameter_id.to_string(),
+            parameter_value_spec: Some(ParameterValueSpec::CategoricalValueSpec(
+                CategoricalValueSpec {
+                    values: values.iter().map(|v| v.to_string()).collect(),
+                    default_value: None,
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    fn with_parameters(mut t: crate::Trial, parameters: &[(&str, Kind)]) -> crate::Trial {
+        t.parameters = parameters
+            .iter()
+            .map(|(parameter_id, kind)| trial::Parameter {
+                parameter_id: parameter_id.to_string(),
+                value: Some(Value {
+                    kind: Some(kind.clone()),
+                }),
+            })
+            .collect();
+        t
+    }
+
+    /// Deterministic pseudo-random values in `[0, 1)`.
+    fn grid(i: usize, k: usize) -> f64 {
+        ((i * (2 * k + 7) + 3 * k) % 23) as f64 / 23.0
+    }
+
+    #[test]
+    fn it_finds_the_important_parameters() {
+        let spec = StudySpec {
+            parameters: vec![
+                double("learning_rate"),
+                double("dropout"),
+                categorical("optimizer", &["sgd", "adam"]),
+            ],
+            ..Default::default()
+        };
+
+        let trials: Vec<crate::Trial> = (0..60)
+            .map(|i| {
+                let lr = grid(i, 0);
+                let dropout = grid(i, 1);
+                let optimizer = if i % 3 == 0 { "adam" } else { "sgd" };
+                let y = 10.0 * lr + if optimizer == "adam" { 3.0 } else { 0.0 } + 0.01 * dropout;
+                with_parameters(
+                    trial(i, &[("accuracy", y)]),
+                    &[
+                        ("learning_rate", Kind::NumberValue(lr)),
+                        ("dropout", Kind::NumberValue(dropout)),
+                        ("optimizer", Kind::StringValue(optimizer.to_string())),
+                    ],
+                )
+            })
+            .collect();
+
+        let importance =
+            parameter_importance(&spec, &trials, "accuracy", &ImportanceConfig::default()).unwrap();
+
+        assert_eq!(importance.num_trials, 60);
+        let ids: Vec<&str> = importance
+            .main_effects
+            .iter()
+            .map(|e| e.parameter_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["learning_rate", "optimizer", "dropout"]);
+        assert!(importance.main_effects[0].importance > 0.5);
+        assert!(importance.main_effects[2].importance < 0.05);
+
+        let total: f64 = importance
+            .main_effects
+            .iter()
+            .map(|e| e.importance)
+            .sum::<f64>()
+            + importance
+                .pairwise_effects
+                .iter()
+                .map(|e| e.importance)
+                .sum::<f64>();
+        assert!(total <= 1.0 + 1e-9, "{total}");
+    }
+
+    #[test]
+    fn it_handles_conditional_parameters() {
+        let mut model = categorical("model", &["linear", "tree"]);
+        model.conditional_parameter_specs = vec![ConditionalParameterSpec {
+            parameter_spec: Some(double("depth")),
+            parent_value_condition: Some(ParentValueCondition::ParentCategoricalValues(
+                CategoricalValueCondition {
+                    values: vec!["tree".to_string()],
+                },
+            )),
+        }];
+        let spec = StudySpec {
+            parameters: vec![model],
+            ..Default::default()
+        };
+
+        let trials: Vec<crate::Trial> = (0..40)
+            .map(|i| {
+                if i % 2 == 0 {
+                    with_parameters(
+                        trial(i, &[("loss", 1.0)]),
+                        &[("model", Kind::StringValue("linear".to_string()))],
+                    )
+                } else {
+                    let depth = grid(i, 0);
+                    with_parameters(
+                        trial(i, &[("loss", depth)]),
+                        &[
+                            ("model", Kind::StringValue("tree".to_string())),
+                            ("depth", Kind::NumberValue(depth)),
+                        ],
+                    )
+                }
+            })
+            .collect();
+
+        let importance =
+            parameter_importance(&spec, &trials, "loss", &ImportanceConfig::default()).unwrap();
+        assert_eq!(importance.main_effects.len(), 2);
+        assert_eq!(importance.pairwise_effects.len(), 1);
+        assert!(
+            importance
+                .main_effects
+                .iter()
+                .all(|e| e.importance.is_finite())
+        );
+
+        assert_eq!(
+            parameter_importance(&spec, &trials[..1], "loss", &ImportanceConfig::default()),
+            Err(Error::NotEnoughTrials(1))
+        );
+    }
+
+    #[test]
+    fn it_skips_trials_with_unknown_categories() {
+        let spec = StudySpec {
+            parameters: vec![categorical("optimizer", &["sgd", "adam"])],
+            ..Default::default()
+        };
+
+        let trials: Vec<crate::Trial> = ["sgd", "adam", "rmsprop", "adagrad"]
+            .iter()
+            .enumerate()
+            .map(|(i, optimizer)| {
+                with_parameters(
+                    trial(i, &[("accuracy", i as f64)]),
+                    &[("optimizer", Kind::StringValue(optimizer.to_string()))],
+                )
+            })
+            .collect();
+
+        let importance =
+            parameter_importance(&spec, &trials, "accuracy", &ImportanceConfig::default()).unwrap();
+        assert_eq!(importance.num_trials, 2);
+
+        assert_eq!(
+            parameter_importance(
+                &spec,
+                &trials[1..],
+                "accuracy",
+                &ImportanceConfig::default()
+            ),
+            Err(Error::NotEnoughTrials(1))
+        );
+    }
+
+    #[test]
+    fn it_skips_trials_with_missing_or_out_of_range_values() {
+        let spec = StudySpec {
+            parameters: vec![double("learning_rate"), double("dropout")],
+            ..Default::default()
+        };
+
+        let trials = vec![
+            with_parameters(
+                trial(0, &[("accuracy", 0.0)]),
+                &[
+                    ("learning_rate", Kind::NumberValue(0.1)),
+                    ("dropout", Kind::NumberValue(0.5)),
+                ],
+            ),
+            with_parameters(
+                trial(1, &[("accuracy", 1.0)]),
+                &[
+                    ("learning_rate", Kind::NumberValue(0.9)),
+                    ("dropout", Kind::NumberValue(0.2)),
+                ],
+            ),
+            // no dropout
+            with_parameters(
+                trial(2, &[("accuracy", 2.0)]),
+                &[("learning_rate", Kind::NumberValue(0.5))],
+            ),
+            // out of bounds
+            with_parameters(
+                trial(3, &[("accuracy", 3.0)]),
+                &[
+                    ("learning_rate", Kind::NumberValue(5.0)),
+                    ("dropout", Kind::NumberValue(0.2)),
+                ],
+            ),
+            with_parameters(
+                trial(4, &[("accuracy", 4.0)]),
+                &[
+                    ("learning_rate", Kind::NumberValue(f64::NAN)),
+                    ("dropout", Kind::NumberValue(0.2)),
+                ],
+            ),
+        ];
+
+        let importance =
+            parameter_importance(&spec, &trials, "accuracy", &ImportanceConfig::default()).unwrap();
+        assert_eq!(importance.num_trials, 2);
+
+        assert_eq!(
+            parameter_importance(
+                &spec,
+                &trials[1..],
+                "accuracy",
+                &ImportanceConfig::default()
+            ),
+            Err(Error::NotEnoughTrials(1))
+        );
+    }
+}
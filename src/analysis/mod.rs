@@ -0,0 +1,212 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local analysis of trials - fetched from the service or exported.
+//!
+//! Metrics are read from the final measurement of the trials. Objective values are
+//! oriented so that greater is better: the values of `MINIMIZE` metrics are negated.
+
+use crate::Trial;
+use crate::vizier::study_spec::MetricSpec;
+use crate::vizier::study_spec::metric_spec::GoalType;
+use crate::vizier::study_spec::parameter_spec::ScaleType;
+use crate::vizier::trial::State;
+
+pub mod convergence;
+pub mod hypervolume;
+pub mod importance;
+pub mod pareto;
+
+/// The final value of the metric `metric_id` of a trial, if any.
+pub fn final_metric(trial: &Trial, metric_id: &str) -> Option<f64> {
+    trial
+        .final_measurement
+        .as_ref()?
+        .metrics
+        .iter()
+        .find(|m| m.metric_id == metric_id)
+        .map(|m| m.value)
+}
+
+/// `value` oriented so that greater is better according to the goal of `metric`.
+pub fn oriented(metric: &MetricSpec, value: f64) -> f64 {
+    match metric.goal() {
+        GoalType::Minimize => -value,
+        GoalType::Maximize | GoalType::Unspecified => value,
+    }
+}
+
+/// The objective metrics - the metrics without safety config.
+pub fn objectives(metrics: &[MetricSpec]) -> Vec<&MetricSpec> {
+    metrics
+        .iter()
+        .filter(|m| m.safety_config.is_none())
+        .collect()
+}
+
+/// Whether the final measurement of a trial satisfies the thresholds of all the
+/// safety metrics - a missing safety metric is unsafe.
+pub fn is_safe(trial: &Trial, metrics: &[MetricSpec]) -> bool {
+    metrics.iter().all(|metric| match &metric.safety_config {
+        None => true,
+        Some(safety) => final_metric(trial, &metric.metric_id).is_some_and(|value| {
+            oriented(metric, value) >= oriented(metric, safety.safety_threshold)
+        }),
+    })
+}
+
+/// The oriented values of the objectives of a trial, if it succeeded, is safe and has
+/// a finite value for every objective.
+pub fn objective_values(trial: &Trial, metrics: &[MetricSpec]) -> Option<Vec<f64>> {
+    if trial.state() != State::Succeeded || !is_safe(trial, metrics) {
+        return None;
+    }
+
+    objectives(metrics)
+        .into_iter()
+        .map(|metric| {
+            final_metric(trial, &metric.metric_id)
+                .filter(|v| v.is_finite())
+                .map(|v| oriented(metric, v))
+        })
+        .collect()
+}
+
+/// `value` in `[min, max]` scaled to `[0, 1]` according to `scale_type`.
+pub(crate) fn scale(value: f64, min: f64, max: f64, scale_type: ScaleType) -> f64 {
+    if max <= min {
+        return 0.5;
+    }
+    let value = value.clamp(min, max);
+
+    let scaled = match scale_type {
+        ScaleType::UnitLogScale if min > 0.0 => (value / min).ln() / (max / min).ln(),
+        ScaleType::UnitReverseLogScale if min > 0.0 => {
+            1.0 - ((max + min - value) / min).ln() / (max / min).ln()
+        }
+        _ => (value - min) / (max - min),
+    };
+
+    scaled.clamp(0.0, 1.0)
+}
+
+/// The value in `[min, max]` of `u` in `[0, 1]` - the inverse of [scale].
+pub(crate) fn unscale(u: f64, min: f64, max: f64, scale_type: ScaleType) -> f64 {
+    if max <= min {
+        return min;
+    }
+    let u = u.clamp(0.0, 1.0);
+
+    let value = match scale_type {
+        ScaleType::UnitLogScale if min > 0.0 => min * (u * (max / min).ln()).exp(),
+        ScaleType::UnitReverseLogScale if min > 0.0 => {
+            max + min - min * ((1.0 - u) * (max / min).ln()).exp()
+        }
+        _ => min + u * (max - min),
+    };
+
+    value.clamp(min, max)
+}
+
+/// xorshift64* generator - deterministic sampling without extra dependencies.
+#[derive(Clone, Debug)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        XorShift(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::Trial;
+    use crate::vizier::study_spec::MetricSpec;
+    use crate::vizier::study_spec::metric_spec::{GoalType, SafetyMetricConfig};
+    use crate::vizier::{Measurement, measurement, trial};
+
+    /// A succeeded trial with the given final metrics.
+    pub(crate) fn trial(id: usize, metrics: &[(&str, f64)]) -> Trial {
+        Trial {
+            id: id.to_string(),
+            state: trial::State::Succeeded as i32,
+            final_measurement: Some(Measurement {
+                elapsed_duration: None,
+                step_count: 1,
+                metrics: metrics
+                    .iter()
+                    .map(|(metric_id, value)| measurement::Metric {
+                        metric_id: metric_id.to_string(),
+                        value: *value,
+                    })
+                    .collect(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A metric spec, with a safety threshold if `safety` is set.
+    pub(crate) fn metric(metric_id: &str, goal: GoalType, safety: Option<f64>) -> MetricSpec {
+        MetricSpec {
+            metric_id: metric_id.to_string(),
+            goal: goal as i32,
+            safety_config: safety.map(|safety_threshold| SafetyMetricConfig {
+                safety_threshold,
+                desired_min_safe_trials_fraction: None,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scale, unscale};
+    use crate::vizier::study_spec::parameter_spec::ScaleType;
+
+    #[test]
+    fn it_scales_parameters() {
+        assert_eq!(scale(5.0, 0.0, 10.0, ScaleType::UnitLinearScale), 0.5);
+        assert!((scale(10.0, 1.0, 100.0, ScaleType::UnitLogScale) - 0.5).abs() < 1e-12);
+        assert!((scale(91.0, 1.0, 100.0, ScaleType::UnitReverseLogScale) - 0.5).abs() < 1e-12);
+        assert_eq!(scale(3.0, 3.0, 3.0, ScaleType::UnitLinearScale), 0.5);
+    }
+
+    #[test]
+    fn it_unscales_values() {
+        assert_eq!(unscale(0.5, 0.0, 10.0, ScaleType::UnitLinearScale), 5.0);
+        assert!((unscale(0.5, 1.0, 100.0, ScaleType::UnitLogScale) - 10.0).abs() < 1e-9);
+        assert!((unscale(0.5, 1.0, 100.0, ScaleType::UnitReverseLogScale) - 91.0).abs() < 1e-9);
+        assert_eq!(
+            unscale(1.0, 1.0, 100.0, ScaleType::UnitReverseLogScale),
+            100.0
+        );
+    }
+}
@@ -0,0 +1,233 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pareto fronts of multi-objective studies - a local equivalent of
+//! `ListOptimalTrials`.
+//!
+//! ```no_run
+//! # use oss_vizier::analysis::pareto::{pareto_front, rank_trials};
+//! # use oss_vizier::vizier::{StudySpec, Trial};
+//! # fn example(trials: Vec<Trial>, study_spec: StudySpec) {
+//! let ranked = rank_trials(&trials, &study_spec.metrics);
+//! let front = pareto_front(&trials, &study_spec.metrics);
+//! # }
+//! ```
+
+use crate::Trial;
+use crate::analysis::objective_values;
+use crate::vizier::study_spec::MetricSpec;
+
+/// Pareto rank of a trial.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankedTrial {
+    /// Index of the trial in the input.
+    pub index: usize,
+    /// Index of the non-dominated front of the trial - 0 for the Pareto front.
+    pub rank: usize,
+    /// Crowding distance of the trial in its front - infinite at the boundaries.
+    pub crowding_distance: f64,
+    /// Oriented objective values of the trial - greater is better.
+    pub objectives: Vec<f64>,
+}
+
+/// Whether `a` dominates `b`: at least as good on every objective and better on one -
+/// greater is better.
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+/// Non-dominated sorting: the rank of each point - 0 for the non-dominated ones, 1
+/// for the ones only dominated by points of rank 0, etc.
+pub fn non_dominated_sort(points: &[Vec<f64>]) -> Vec<usize> {
+    let n = points.len();
+
+    let mut dominated_by = vec![0usize; n];
+    let mut dominates_list: Vec<Vec<usize>> = vec![vec![]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            if dominates(&points[i], &points[j]) {
+                dominates_list[i].push(j);
+                dominated_by[j] += 1;
+            } else if dominates(&points[j], &points[i]) {
+                dominates_list[j].push(i);
+                dominated_by[i] += 1;
+            }
+        }
+    }
+
+    let mut ranks = vec![0; n];
+    let mut front: Vec<usize> = (0..n).filter(|&i| dominated_by[i] == 0).collect();
+    let mut rank = 0;
+    while !front.is_empty() {
+        let mut next = vec![];
+        for &i in &front {
+            ranks[i] = rank;
+            for &j in &dominates_list[i] {
+                dominated_by[j] -= 1;
+                if dominated_by[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        front = next;
+        rank += 1;
+    }
+
+    ranks
+}
+
+/// Crowding distances of the points of a front - the sum over the objectives of the
+/// normalized distance between the neighbours of each point.
+pub fn crowding_distances(front: &[&[f64]]) -> Vec<f64> {
+    let n = front.len();
+    let mut distances = vec![0.0; n];
+    if n == 0 {
+        return distances;
+    }
+
+    let num_objectives = front[0].len();
+    for k in 0..num_objectives {
+        let values: Vec<f64> = front.iter().map(|p| p[k]).collect();
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+
+        distances[order[0]] = f64::INFINITY;
+        distances[order[n - 1]] = f64::INFINITY;
+
+        let range = values[order[n - 1]] - values[order[0]];
+        if range <= 0.0 {
+            continue;
+        }
+        for w in order.windows(3) {
+            distances[w[1]] += (values[w[2]] - values[w[0]]) / range;
+        }
+    }
+
+    distances
+}
+
+/// Ranks the succeeded, safe trials with a value for every objective metric - the
+/// metrics of `metrics` without safety config. The other trials are left out.
+///
+/// The result is sorted by rank and decreasing crowding distance.
+pub fn rank_trials(trials: &[Trial], metrics: &[MetricSpec]) -> Vec<RankedTrial> {
+    let (indices, points): (Vec<usize>, Vec<Vec<f64>>) = trials
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| objective_values(t, metrics).map(|v| (i, v)))
+        .unzip();
+
+    let ranks = non_dominated_sort(&points);
+
+    let mut crowding = vec![0.0; points.len()];
+    let num_ranks = ranks.iter().max().map_or(0, |r| r + 1);
+    for rank in 0..num_ranks {
+        let members: Vec<usize> = (0..points.len()).filter(|&i| ranks[i] == rank).collect();
+        let front: Vec<&[f64]> = members.iter().map(|&i| points[i].as_slice()).collect();
+        for (i, d) in members.into_iter().zip(crowding_distances(&front)) {
+            crowding[i] = d;
+        }
+    }
+
+    let mut ranked: Vec<RankedTrial> = indices
+        .into_iter()
+        .zip(points)
+        .enumerate()
+        .map(|(i, (index, objectives))| RankedTrial {
+            index,
+            rank: ranks[i],
+            crowding_distance: crowding[i],
+            objectives,
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        a.rank
+            .cmp(&b.rank)
+            .then(b.crowding_distance.total_cmp(&a.crowding_distance))
+    });
+
+    ranked
+}
+
+/// The trials of the Pareto front - see [rank_trials].
+pub fn pareto_front<'a>(trials: &'a [Trial], metrics: &[MetricSpec]) -> Vec<&'a Trial> {
+    rank_trials(trials, metrics)
+        .into_iter()
+        .filter(|r| r.rank == 0)
+        .map(|r| &trials[r.index])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crowding_distances, non_dominated_sort, pareto_front, rank_trials};
+    use crate::analysis::testing::{metric, trial};
+    use crate::vizier::study_spec::metric_spec::GoalType;
+
+    #[test]
+    fn it_sorts_non_dominated_points() {
+        let points = vec![
+            vec![1.0, 5.0],
+            vec![2.0, 4.0],
+            vec![1.0, 4.0],
+            vec![0.0, 0.0],
+        ];
+
+        assert_eq!(non_dominated_sort(&points), vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn it_computes_crowding_distances() {
+        let front: Vec<&[f64]> = vec![&[0.0, 4.0], &[1.0, 2.0], &[4.0, 0.0]];
+
+        let distances = crowding_distances(&front);
+        assert!(distances[0].is_infinite());
+        assert!(distances[2].is_infinite());
+        assert!((distances[1] - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_respects_goals_and_safety() {
+        let metrics = vec![
+            metric("accuracy", GoalType::Maximize, None),
+            metric("latency", GoalType::Minimize, None),
+            metric("memory", GoalType::Minimize, Some(10.0)),
+        ];
+
+        let trials = vec![
+            trial(0, &[("accuracy", 0.9), ("latency", 10.0), ("memory", 5.0)]),
+            trial(1, &[("accuracy", 0.8), ("latency", 5.0), ("memory", 5.0)]),
+            trial(2, &[("accuracy", 0.8), ("latency", 20.0), ("memory", 5.0)]),
+            // unsafe
+            trial(3, &[("accuracy", 1.0), ("latency", 1.0), ("memory", 50.0)]),
+            // missing objective
+            trial(4, &[("accuracy", 1.0), ("memory", 1.0)]),
+        ];
+
+        let front: Vec<&str> = pareto_front(&trials, &metrics)
+            .into_iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(front.len(), 2);
+        assert!(front.contains(&"0") && front.contains(&"1"));
+
+        let ranked = rank_trials(&trials, &metrics);
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(ranked[2].index, 2);
+        assert_eq!(ranked[2].rank, 1);
+        assert_eq!(ranked[2].objectives, vec![0.8, -20.0]);
+    }
+}
@@ -0,0 +1,395 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Classic single-objective test functions.
+//!
+//! The parameters are the doubles `x1`, `x2`, ... and the objective is the minimized
+//! metric [METRIC_ID].
+
+use std::f64::consts::{E, PI};
+
+use crate::Trial;
+use crate::benchmarks::{Error, Problem, double_parameters, measurement, minimize, numbers};
+use crate::vizier::Measurement;
+use crate::vizier::study_spec::{MetricSpec, ParameterSpec};
+
+/// Id of the objective metric of the functions.
+pub const METRIC_ID: &str = "value";
+
+/// A single-objective function of double parameters - a [Problem] minimizing
+/// [METRIC_ID].
+pub trait Function {
+    /// The name of the function.
+    fn name(&self) -> String;
+
+    /// The bounds of `x1`, `x2`, ... - [Problem::evaluate] rejects points outside.
+    fn bounds(&self) -> Vec<(f64, f64)>;
+
+    /// The global minimum.
+    fn minimum(&self) -> f64;
+
+    /// The value at `x`.
+    fn value(&self, x: &[f64]) -> f64;
+}
+
+impl<F: Function> Problem for F {
+    fn name(&self) -> String {
+        Function::name(self)
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        double_parameters(&self.bounds())
+    }
+
+    fn metrics(&self) -> Vec<MetricSpec> {
+        minimize(&[METRIC_ID.to_string()])
+    }
+
+    fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error> {
+        let x = numbers(trial, &self.bounds())?;
+        Ok(measurement(vec![(METRIC_ID.to_string(), self.value(&x))]))
+    }
+
+    fn optimum(&self) -> Option<f64> {
+        Some(self.minimum())
+    }
+}
+
+/// Branin function on `[-5, 10] x [0, 15]` - three global minima of 0.397887.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Branin;
+
+impl Function for Branin {
+    fn name(&self) -> String {
+        "branin".to_string()
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-5.0, 10.0), (0.0, 15.0)]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.397_887_357_729_738
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        let b = 5.1 / (4.0 * PI * PI);
+        let c = 5.0 / PI;
+        let t = 1.0 / (8.0 * PI);
+        (x[1] - b * x[0] * x[0] + c * x[0] - 6.0).powi(2) + 10.0 * (1.0 - t) * x[0].cos() + 10.0
+    }
+}
+
+/// Hartmann 3-dimensional function on `[0, 1]^3` - global minimum of -3.86278.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hartmann3;
+
+impl Function for Hartmann3 {
+    fn name(&self) -> String {
+        "hartmann3".to_string()
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(0.0, 1.0); 3]
+    }
+
+    fn minimum(&self) -> f64 {
+        -3.862_779_787_332_77
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        const A: [[f64; 3]; 4] = [
+            [3.0, 10.0, 30.0],
+            [0.1, 10.0, 35.0],
+            [3.0, 10.0, 30.0],
+            [0.1, 10.0, 35.0],
+        ];
+        const P: [[f64; 3]; 4] = [
+            [0.3689, 0.1170, 0.2673],
+            [0.4699, 0.4387, 0.7470],
+            [0.1091, 0.8732, 0.5547],
+            [0.0381, 0.5743, 0.8828],
+        ];
+        hartmann(x, &A, &P)
+    }
+}
+
+/// Hartmann 6-dimensional function on `[0, 1]^6` - global minimum of -3.32237.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hartmann6;
+
+impl Function for Hartmann6 {
+    fn name(&self) -> String {
+        "hartmann6".to_string()
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(0.0, 1.0); 6]
+    }
+
+    fn minimum(&self) -> f64 {
+        -3.322_368_011_391_339
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        const A: [[f64; 6]; 4] = [
+            [10.0, 3.0, 17.0, 3.5, 1.7, 8.0],
+            [0.05, 10.0, 17.0, 0.1, 8.0, 14.0],
+            [3.0, 3.5, 1.7, 10.0, 17.0, 8.0],
+            [17.0, 8.0, 0.05, 10.0, 0.1, 14.0],
+        ];
+        const P: [[f64; 6]; 4] = [
+            [0.1312, 0.1696, 0.5569, 0.0124, 0.8283, 0.5886],
+            [0.2329, 0.4135, 0.8307, 0.3736, 0.1004, 0.9991],
+            [0.2348, 0.1451, 0.3522, 0.2883, 0.3047, 0.6650],
+            [0.4047, 0.8828, 0.8732, 0.5743, 0.1091, 0.0381],
+        ];
+        hartmann(x, &A, &P)
+    }
+}
+
+fn hartmann<const D: usize>(x: &[f64], a: &[[f64; D]; 4], p: &[[f64; D]; 4]) -> f64 {
+    const ALPHA: [f64; 4] = [1.0, 1.2, 3.0, 3.2];
+
+    -(0..4)
+        .map(|i| {
+            let inner: f64 = (0..D).map(|j| a[i][j] * (x[j] - p[i][j]).powi(2)).sum();
+            ALPHA[i] * (-inner).exp()
+        })
+        .sum::<f64>()
+}
+
+/// Rosenbrock function on `[-5, 10]^d` - global minimum of 0 at `(1, ..., 1)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Rosenbrock {
+    dimension: usize,
+}
+
+impl Rosenbrock {
+    /// Creates a new [Rosenbrock] function of `dimension` parameters - at least 2.
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(2),
+        }
+    }
+}
+
+impl Function for Rosenbrock {
+    fn name(&self) -> String {
+        format!("rosenbrock-{}d", self.dimension)
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-5.0, 10.0); self.dimension]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.0
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        x.windows(2)
+            .map(|w| 100.0 * (w[1] - w[0] * w[0]).powi(2) + (w[0] - 1.0).powi(2))
+            .sum::<f64>()
+    }
+}
+
+/// Ackley function on `[-32.768, 32.768]^d` - global minimum of 0 at the origin.
+#[derive(Clone, Copy, Debug)]
+pub struct Ackley {
+    dimension: usize,
+}
+
+impl Ackley {
+    /// Creates a new [Ackley] function of `dimension` parameters.
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(1),
+        }
+    }
+}
+
+impl Function for Ackley {
+    fn name(&self) -> String {
+        format!("ackley-{}d", self.dimension)
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-32.768, 32.768); self.dimension]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.0
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        let d = x.len() as f64;
+        let squares = x.iter().map(|v| v * v).sum::<f64>() / d;
+        let cosines = x.iter().map(|v| (2.0 * PI * v).cos()).sum::<f64>() / d;
+        -20.0 * (-0.2 * squares.sqrt()).exp() - cosines.exp() + 20.0 + E
+    }
+}
+
+/// Rastrigin function on `[-5.12, 5.12]^d` - global minimum of 0 at the origin.
+#[derive(Clone, Copy, Debug)]
+pub struct Rastrigin {
+    dimension: usize,
+}
+
+impl Rastrigin {
+    /// Creates a new [Rastrigin] function of `dimension` parameters.
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(1),
+        }
+    }
+}
+
+impl Function for Rastrigin {
+    fn name(&self) -> String {
+        format!("rastrigin-{}d", self.dimension)
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-5.12, 5.12); self.dimension]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.0
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        10.0 * x.len() as f64
+            + x.iter()
+                .map(|v| v * v - 10.0 * (2.0 * PI * v).cos())
+                .sum::<f64>()
+    }
+}
+
+/// Levy function on `[-10, 10]^d` - global minimum of 0 at `(1, ..., 1)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Levy {
+    dimension: usize,
+}
+
+impl Levy {
+    /// Creates a new [Levy] function of `dimension` parameters.
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension: dimension.max(1),
+        }
+    }
+}
+
+impl Function for Levy {
+    fn name(&self) -> String {
+        format!("levy-{}d", self.dimension)
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-10.0, 10.0); self.dimension]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.0
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        let w: Vec<f64> = x.iter().map(|v| 1.0 + (v - 1.0) / 4.0).collect();
+        let last = w[w.len() - 1];
+
+        (PI * w[0]).sin().powi(2)
+            + w[..w.len() - 1]
+                .iter()
+                .map(|wi| (wi - 1.0).powi(2) * (1.0 + 10.0 * (PI * wi + 1.0).sin().powi(2)))
+                .sum::<f64>()
+            + (last - 1.0).powi(2) * (1.0 + (2.0 * PI * last).sin().powi(2))
+    }
+}
+
+/// Himmelblau function on `[-5, 5]^2` - four global minima of 0.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Himmelblau;
+
+impl Function for Himmelblau {
+    fn name(&self) -> String {
+        "himmelblau".to_string()
+    }
+
+    fn bounds(&self) -> Vec<(f64, f64)> {
+        vec![(-5.0, 5.0); 2]
+    }
+
+    fn minimum(&self) -> f64 {
+        0.0
+    }
+
+    fn value(&self, x: &[f64]) -> f64 {
+        (x[0].powi(2) + x[1] - 11.0).powi(2) + (x[0] + x[1].powi(2) - 7.0).powi(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::{Ackley, Branin, Hartmann3, Hartmann6, Himmelblau, Levy, Rastrigin, Rosenbrock};
+    use crate::benchmarks::testing::point;
+    use crate::benchmarks::{Error, Problem};
+
+    fn value(problem: &dyn Problem, x: &[f64]) -> f64 {
+        problem.evaluate(&point(x)).unwrap().metrics[0].value
+    }
+
+    #[test]
+    fn it_reaches_the_optimum_at_the_minimizers() {
+        let cases: Vec<(Box<dyn Problem>, Vec<f64>)> = vec![
+            (Box::new(Branin), vec![-PI, 12.275]),
+            (Box::new(Branin), vec![PI, 2.275]),
+            (Box::new(Hartmann3), vec![0.114614, 0.555649, 0.852547]),
+            (
+                Box::new(Hartmann6),
+                vec![0.20169, 0.150011, 0.476874, 0.275332, 0.311652, 0.6573],
+            ),
+            (Box::new(Rosenbrock::new(3)), vec![1.0; 3]),
+            (Box::new(Ackley::new(4)), vec![0.0; 4]),
+            (Box::new(Rastrigin::new(4)), vec![0.0; 4]),
+            (Box::new(Levy::new(4)), vec![1.0; 4]),
+            (Box::new(Himmelblau), vec![3.0, 2.0]),
+            (Box::new(Himmelblau), vec![-2.805118, 3.131312]),
+        ];
+
+        for (problem, x) in cases {
+            let optimum = problem.optimum().unwrap();
+            assert!(
+                (value(problem.as_ref(), &x) - optimum).abs() < 1e-4,
+                "{}",
+                problem.name()
+            );
+            assert!(value(problem.as_ref(), &vec![0.3; x.len()]) > optimum);
+        }
+    }
+
+    #[test]
+    fn it_rejects_points_out_of_bounds() {
+        assert_eq!(
+            Branin.evaluate(&point(&[-6.0, 1.0])).unwrap_err(),
+            Error::InvalidValue("x1".to_string())
+        );
+        assert_eq!(
+            Hartmann3.evaluate(&point(&[0.5, 0.5, 1.5])).unwrap_err(),
+            Error::InvalidValue("x3".to_string())
+        );
+    }
+}
@@ -0,0 +1,558 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmark harness - compares algorithms on [Problem]s over repeated seeds.
+//!
+//! A [Harness] runs every (algorithm, problem, seed) of its grid with a budget of
+//! trials on a [Backend] - the service with [ServiceBackend] or the local policies with
+//! [LocalBackend] - and collects the best-so-far curves and [regret] of each run. The
+//! [Results] aggregate the regret per step over the seeds, to pick the `algorithm` of
+//! a [crate::study::spec::StudySpecBuilder] with evidence.
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::benchmarks::harness::{Harness, ServiceBackend};
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # #[cfg(feature = "csv")]
+//! # async fn example(client: VizierClient<InterceptedChannel>) -> Result<(), Box<dyn std::error::Error>> {
+//! let harness = Harness::new(50)
+//!     .with_algorithms(vec!["RANDOM_SEARCH".to_string(), "GAUSSIAN_PROCESS_BANDIT".to_string()])
+//!     .with_seeds((0..10).collect());
+//!
+//! let results = harness
+//!     .run(&mut ServiceBackend::new(client, "benchmark-2022-06-01".to_string()))
+//!     .await?;
+//! results.summary_table().write_csv(File::create("summary.csv")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::pin::pin;
+
+use futures_util::TryStreamExt;
+use prost::bytes::Bytes;
+use tonic::codegen::{Body, StdError};
+
+use crate::analysis::convergence::{Series, best_so_far};
+use crate::benchmarks::{Problem, regret, suite};
+use crate::optimizer::Optimizer;
+use crate::optimizer::local::{Algorithm, LocalOptimizer};
+use crate::optimizer::remote::RemoteOptimizer;
+use crate::study::ToStudyName;
+use crate::table::{Column, Table, Values};
+use crate::{Error, StudyName, Trial, VizierClient};
+
+/// Creates the optimizer of a run of a [Harness].
+pub trait Backend {
+    /// The optimizer of a run.
+    type Optimizer: Optimizer;
+
+    /// A new optimizer running `algorithm` on a new study of `problem` - `seed`
+    /// distinguishes the repetitions.
+    fn optimizer(
+        &mut self,
+        algorithm: &str,
+        problem: &dyn Problem,
+        seed: u64,
+    ) -> impl Future<Output = Result<Self::Optimizer, Error>>;
+}
+
+/// [Backend] running the local policies - see [Algorithm::from_name].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalBackend;
+
+impl Backend for LocalBackend {
+    type Optimizer = LocalOptimizer<Algorithm>;
+
+    async fn optimizer(
+        &mut self,
+        algorithm: &str,
+        problem: &dyn Problem,
+        seed: u64,
+    ) -> Result<Self::Optimizer, Error> {
+        let policy = Algorithm::from_name(algorithm, seed)?;
+        let study_name = StudyName::new(
+            "local".to_string(),
+            format!("{}-{algorithm}-{seed}", problem.name()),
+        );
+
+        Ok(
+            LocalOptimizer::new(problem.study_spec(algorithm.to_string()), policy)
+                .with_study_name(study_name),
+        )
+    }
+}
+
+/// [Backend] running the algorithms of the service - one study per run.
+///
+/// The studies are named `{prefix}-{problem}-{algorithm}-{seed}`. The service returns
+/// the existing study for a known name, so a run fails with [Error::StudyNotEmpty]
+/// if a study of that name already has trials - use a prefix unique to each
+/// benchmark. The seed only distinguishes the repetitions - the service picks its own.
+pub struct ServiceBackend<T> {
+    client: VizierClient<T>,
+    prefix: String,
+}
+
+impl<T> ServiceBackend<T> {
+    /// Creates a new [ServiceBackend] creating its studies with `client`.
+    pub fn new(client: VizierClient<T>, prefix: String) -> Self {
+        Self { client, prefix }
+    }
+}
+
+impl<T> Backend for ServiceBackend<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    type Optimizer = RemoteOptimizer<T>;
+
+    async fn optimizer(
+        &mut self,
+        algorithm: &str,
+        problem: &dyn Problem,
+        seed: u64,
+    ) -> Result<Self::Optimizer, Error> {
+        let display_name = format!("{}-{}-{algorithm}-{seed}", self.prefix, problem.name());
+
+        // the trials of an earlier benchmark would be mixed into the run
+        let existing = self
+            .client
+            .find_study_by_display_name(&display_name)
+            .await?;
+        if let Some(study) = existing {
+            let mut trials = pin!(self.client.list_trials_stream(study.to_study_name(), 1));
+            if trials.try_next().await?.is_some() {
+                return Err(Error::StudyNotEmpty(study.name));
+            }
+        }
+
+        let request = self
+            .client
+            .mk_study_request_builder()
+            .with_display_name(display_name.clone())
+            .with_study_spec(problem.study_spec(algorithm.to_string()))
+            .build()?;
+        let study = self.client.create_study(request).await?;
+
+        Ok(RemoteOptimizer::new(
+            self.client.clone(),
+            study.to_study_name(),
+            display_name,
+        ))
+    }
+}
+
+/// Runs a grid of (algorithm, problem, seed) - see the [module](self) documentation.
+pub struct Harness {
+    algorithms: Vec<String>,
+    problems: Vec<Box<dyn Problem>>,
+    seeds: Vec<u64>,
+    num_trials: usize,
+    batch_size: usize,
+}
+
+impl Harness {
+    /// Creates a new [Harness] with a budget of `num_trials` trials per run, running
+    /// `RANDOM_SEARCH` on the [suite] with 5 seeds.
+    pub fn new(num_trials: usize) -> Self {
+        Self {
+            algorithms: vec!["RANDOM_SEARCH".to_string()],
+            problems: suite(),
+            seeds: (0..5).collect(),
+            num_trials,
+            batch_size: 1,
+        }
+    }
+
+    /// Sets the algorithms to compare - the `algorithm` of the study specs.
+    pub fn with_algorithms(mut self, algorithms: Vec<String>) -> Self {
+        self.algorithms = algorithms;
+        self
+    }
+
+    /// Sets the problems.
+    pub fn with_problems(mut self, problems: Vec<Box<dyn Problem>>) -> Self {
+        self.problems = problems;
+        self
+    }
+
+    /// Sets the seeds - one run per seed for each algorithm and problem.
+    pub fn with_seeds(mut self, seeds: Vec<u64>) -> Self {
+        self.seeds = seeds;
+        self
+    }
+
+    /// Sets the number of trials asked at once - 1 by default.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Runs the grid on `backend`, one run after the other.
+    pub async fn run<B: Backend>(&self, backend: &mut B) -> Result<Results, Error> {
+        let mut runs = vec![];
+
+        for problem in &self.problems {
+            for algorithm in &self.algorithms {
+                for &seed in &self.seeds {
+                    let mut optimizer =
+                        backend.optimizer(algorithm, problem.as_ref(), seed).await?;
+                    let trials = self.evaluate(&mut optimizer, problem.as_ref()).await?;
+
+                    runs.push(Run {
+                        algorithm: algorithm.clone(),
+                        problem: problem.name(),
+                        seed,
+                        best_so_far: best_so_far(&trials, &problem.metrics()),
+                        regret: regret(problem.as_ref(), &trials).unwrap_or_default(),
+                        num_trials: trials.len(),
+                    });
+                }
+            }
+        }
+
+        let summaries = summarize(&runs);
+        Ok(Results { runs, summaries })
+    }
+
+    /// Evaluates the trials of a run - in completion order.
+    async fn evaluate<O: Optimizer>(
+        &self,
+        optimizer: &mut O,
+        problem: &dyn Problem,
+    ) -> Result<Vec<Trial>, Error> {
+        let mut completed = Vec::with_capacity(self.num_trials);
+
+        while completed.len() < self.num_trials {
+            let count = self.batch_size.min(self.num_trials - completed.len());
+            let trials = optimizer.ask(count).await?;
+            if trials.is_empty() {
+                break;
+            }
+
+            for trial in trials.iter().take(count) {
+                let measurement = problem.evaluate(trial)?;
+                completed.push(optimizer.tell(trial, measurement).await?);
+            }
+        }
+
+        Ok(completed)
+    }
+}
+
+/// A run of an algorithm on a problem.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Run {
+    /// The algorithm.
+    pub algorithm: String,
+    /// Name of the problem.
+    pub problem: String,
+    /// The seed.
+    pub seed: u64,
+    /// Number of completed trials - below the budget if the algorithm ran out of
+    /// suggestions.
+    pub num_trials: usize,
+    /// Best value so far of each metric of the problem.
+    pub best_so_far: Vec<Series>,
+    /// Regret after each trial - see [regret].
+    pub regret: Vec<Option<f64>>,
+}
+
+/// Statistics of the regret of the runs after a number of trials.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepStatistics {
+    /// Number of trials so far - 1 for the first trial.
+    pub step: usize,
+    /// Number of runs with a regret at this step.
+    pub runs: usize,
+    /// Mean regret.
+    pub mean: f64,
+    /// Lowest regret.
+    pub min: f64,
+    /// First quartile of the regret.
+    pub q25: f64,
+    /// Median regret.
+    pub median: f64,
+    /// Third quartile of the regret.
+    pub q75: f64,
+    /// Highest regret.
+    pub max: f64,
+}
+
+/// Regret of an algorithm on a problem aggregated over the seeds.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Summary {
+    /// The algorithm.
+    pub algorithm: String,
+    /// Name of the problem.
+    pub problem: String,
+    /// Statistics per step - steps without any regret are omitted.
+    pub steps: Vec<StepStatistics>,
+}
+
+impl Summary {
+    /// The statistics after the last step, if any.
+    pub fn last(&self) -> Option<&StepStatistics> {
+        self.steps.last()
+    }
+}
+
+/// Result of [Harness::run].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Results {
+    /// The runs, by problem, algorithm and seed.
+    pub runs: Vec<Run>,
+    /// One summary per problem and algorithm - see [summarize].
+    pub summaries: Vec<Summary>,
+}
+
+impl Results {
+    /// The summary of `algorithm` on `problem`, if any.
+    pub fn summary(&self, algorithm: &str, problem: &str) -> Option<&Summary> {
+        self.summaries
+            .iter()
+            .find(|s| s.algorithm == algorithm && s.problem == problem)
+    }
+
+    /// One row per problem, algorithm and step with the statistics of the regret.
+    pub fn summary_table(&self) -> Table {
+        let rows: Vec<(&Summary, &StepStatistics)> = self
+            .summaries
+            .iter()
+            .flat_map(|s| s.steps.iter().map(move |step| (s, step)))
+            .collect();
+
+        let str = |name: &str, f: fn(&Summary) -> &str| Column {
+            name: name.to_string(),
+            values: Values::Str(rows.iter().map(|(s, _)| Some(f(s).to_string())).collect()),
+        };
+        let float = |name: &str, f: fn(&StepStatistics) -> f64| Column {
+            name: name.to_string(),
+            values: Values::Float(rows.iter().map(|(_, step)| Some(f(step))).collect()),
+        };
+
+        Table::from_columns(vec![
+            str("problem", |s| &s.problem),
+            str("algorithm", |s| &s.algorithm),
+            Column {
+                name: "step".to_string(),
+                values: Values::Int(
+                    rows.iter()
+                        .map(|(_, step)| Some(step.step as i64))
+                        .collect(),
+                ),
+            },
+            Column {
+                name: "runs".to_string(),
+                values: Values::Int(
+                    rows.iter()
+                        .map(|(_, step)| Some(step.runs as i64))
+                        .collect(),
+                ),
+            },
+            float("mean", |step| step.mean),
+            float("min", |step| step.min),
+            float("q25", |step| step.q25),
+            float("median", |step| step.median),
+            float("q75", |step| step.q75),
+            float("max", |step| step.max),
+        ])
+    }
+
+    /// One row per run and trial with the regret - the long format.
+    pub fn runs_table(&self) -> Table {
+        let rows: Vec<(&Run, usize, Option<f64>)> = self
+            .runs
+            .iter()
+            .flat_map(|run| {
+                run.regret
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, regret)| (run, i + 1, *regret))
+            })
+            .collect();
+
+        Table::from_columns(vec![
+            Column {
+                name: "problem".to_string(),
+                values: Values::Str(
+                    rows.iter()
+                        .map(|(run, _, _)| Some(run.problem.clone()))
+                        .collect(),
+                ),
+            },
+            Column {
+                name: "algorithm".to_string(),
+                values: Values::Str(
+                    rows.iter()
+                        .map(|(run, _, _)| Some(run.algorithm.clone()))
+                        .collect(),
+                ),
+            },
+            Column {
+                name: "seed".to_string(),
+                values: Values::Int(
+                    rows.iter()
+                        .map(|(run, _, _)| Some(run.seed as i64))
+                        .collect(),
+                ),
+            },
+            Column {
+                name: "step".to_string(),
+                values: Values::Int(rows.iter().map(|(_, step, _)| Some(*step as i64)).collect()),
+            },
+            Column {
+                name: "regret".to_string(),
+                values: Values::Float(rows.iter().map(|(_, _, regret)| *regret).collect()),
+            },
+        ])
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Results {
+    /// The runs and summaries as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Aggregates the regret of the runs of each algorithm on each problem over the seeds,
+/// in the order of the runs.
+pub fn summarize(runs: &[Run]) -> Vec<Summary> {
+    let mut groups: Vec<(&str, &str, Vec<&Run>)> = vec![];
+    for run in runs {
+        match groups
+            .iter_mut()
+            .find(|(algorithm, problem, _)| *algorithm == run.algorithm && *problem == run.problem)
+        {
+            Some((_, _, group)) => group.push(run),
+            None => groups.push((&run.algorithm, &run.problem, vec![run])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(algorithm, problem, group)| {
+            let num_steps = group.iter().map(|run| run.regret.len()).max().unwrap_or(0);
+            let steps = (0..num_steps)
+                .filter_map(|i| {
+                    let values: Vec<f64> = group
+                        .iter()
+                        .filter_map(|run| run.regret.get(i).copied().flatten())
+                        .collect();
+                    statistics(i + 1, values)
+                })
+                .collect();
+
+            Summary {
+                algorithm: algorithm.to_string(),
+                problem: problem.to_string(),
+                steps,
+            }
+        })
+        .collect()
+}
+
+fn statistics(step: usize, mut values: Vec<f64>) -> Option<StepStatistics> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+
+    Some(StepStatistics {
+        step,
+        runs: values.len(),
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        min: values[0],
+        q25: quantile(&values, 0.25),
+        median: quantile(&values, 0.5),
+        q75: quantile(&values, 0.75),
+        max: values[values.len() - 1],
+    })
+}
+
+/// The `q` quantile of non-empty `sorted` values - linearly interpolated.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let position = q * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Harness, LocalBackend, quantile};
+    use crate::Error;
+    use crate::benchmarks::functions::Branin;
+    use crate::benchmarks::multi_objective::Zdt;
+    use crate::optimizer;
+
+    #[test]
+    fn it_interpolates_quantiles() {
+        let values = [1.0, 2.0, 4.0, 8.0];
+        assert_eq!(quantile(&values, 0.0), 1.0);
+        assert_eq!(quantile(&values, 0.5), 3.0);
+        assert_eq!(quantile(&values, 0.75), 5.0);
+        assert_eq!(quantile(&values, 1.0), 8.0);
+        assert_eq!(quantile(&[2.0], 0.25), 2.0);
+    }
+
+    #[tokio::test]
+    async fn it_compares_local_algorithms() {
+        let harness = Harness::new(20)
+            .with_algorithms(vec!["RANDOM_SEARCH".to_string(), "GRID_SEARCH".to_string()])
+            .with_problems(vec![Box::new(Branin), Box::new(Zdt::zdt1(3))])
+            .with_seeds(vec![1, 2, 3])
+            .with_batch_size(4);
+
+        let results = harness.run(&mut LocalBackend).await.unwrap();
+        assert_eq!(results.runs.len(), 12);
+        assert_eq!(results.summaries.len(), 4);
+
+        let random = results.summary("RANDOM_SEARCH", "branin").unwrap();
+        assert_eq!(random.steps.len(), 20);
+        let last = random.last().unwrap();
+        assert_eq!(last.runs, 3);
+        assert!(last.min <= last.median && last.median <= last.max);
+        assert!(random.steps[0].median >= last.median);
+
+        // the grid does not depend on the seed
+        let grid = results.summary("GRID_SEARCH", "branin").unwrap();
+        let last = grid.last().unwrap();
+        assert_eq!(last.min, last.max);
+
+        let table = results.summary_table();
+        assert_eq!(table.num_rows(), 80);
+        assert_eq!(results.runs_table().num_rows(), 240);
+
+        let unsupported = Harness::new(1)
+            .with_algorithms(vec!["GAUSSIAN_PROCESS_BANDIT".to_string()])
+            .run(&mut LocalBackend)
+            .await;
+        assert!(matches!(
+            unsupported,
+            Err(Error::Optimizer(optimizer::Error::UnsupportedAlgorithm(_)))
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synthetic problems over mixed parameter types.
+
+use crate::Trial;
+use crate::benchmarks::functions::METRIC_ID;
+use crate::benchmarks::{
+    Error, Problem, double_parameters, measurement, minimize, number, numbers, string,
+};
+use crate::vizier::Measurement;
+use crate::vizier::study_spec::parameter_spec::{
+    CategoricalValueSpec, DiscreteValueSpec, IntegerValueSpec, ParameterValueSpec, ScaleType,
+};
+use crate::vizier::study_spec::{MetricSpec, ParameterSpec};
+
+/// Values of the discrete parameters.
+const DISCRETE_VALUES: [f64; 5] = [0.1, 0.2, 0.5, 1.0, 2.0];
+
+/// Values of the categorical parameters.
+const CATEGORIES: [&str; 4] = ["a", "b", "c", "d"];
+
+/// Maximum value of the integer parameters.
+const MAX_INTEGER: i64 = 10;
+
+/// Separable quadratic problem over double, integer, discrete and categorical
+/// parameters - a sum of one term per parameter, minimized to 0 by a single target
+/// value of each parameter.
+///
+/// The parameters are the doubles `x1`, ... in `[0, 1]`, the integers `n1`, ... in
+/// `[0, 10]`, the discretes `q1`, ... in `{0.1, 0.2, 0.5, 1, 2}` and the categoricals
+/// `c1`, ... in `{a, b, c, d}`. A wrong category costs 0.5.
+#[derive(Clone, Copy, Debug)]
+pub struct Mixed {
+    doubles: usize,
+    integers: usize,
+    discretes: usize,
+    categoricals: usize,
+}
+
+impl Mixed {
+    /// Creates a new [Mixed] problem with the given number of parameters of each type.
+    pub fn new(doubles: usize, integers: usize, discretes: usize, categoricals: usize) -> Self {
+        Self {
+            doubles,
+            integers,
+            discretes,
+            categoricals,
+        }
+    }
+
+    /// The target value of the `i`-th double parameter - evenly spread in `[0, 1]`.
+    fn double_target(&self, i: usize) -> f64 {
+        (i + 1) as f64 / (self.doubles + 1) as f64
+    }
+
+    fn integer_target(i: usize) -> i64 {
+        (3 + 2 * i as i64) % (MAX_INTEGER + 1)
+    }
+
+    fn discrete_target(i: usize) -> f64 {
+        DISCRETE_VALUES[(i + 2) % DISCRETE_VALUES.len()]
+    }
+
+    fn categorical_target(i: usize) -> &'static str {
+        CATEGORIES[i % CATEGORIES.len()]
+    }
+}
+
+impl Problem for Mixed {
+    fn name(&self) -> String {
+        format!(
+            "mixed-{}x-{}n-{}q-{}c",
+            self.doubles, self.integers, self.discretes, self.categoricals
+        )
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        let spec = |parameter_id: String, parameter_value_spec| ParameterSpec {
+            parameter_id,
+            scale_type: ScaleType::Unspecified as i32,
+            conditional_parameter_specs: vec![],
+            parameter_value_spec: Some(parameter_value_spec),
+        };
+
+        let mut parameters = double_parameters(&vec![(0.0, 1.0); self.doubles]);
+        parameters.extend((1..=self.integers).map(|i| {
+            spec(
+                format!("n{i}"),
+                ParameterValueSpec::IntegerValueSpec(IntegerValueSpec {
+                    min_value: 0,
+                    max_value: MAX_INTEGER,
+                    default_value: None,
+                }),
+            )
+        }));
+        parameters.extend((1..=self.discretes).map(|i| {
+            spec(
+                format!("q{i}"),
+                ParameterValueSpec::DiscreteValueSpec(DiscreteValueSpec {
+                    values: DISCRETE_VALUES.to_vec(),
+                    default_value: None,
+                }),
+            )
+        }));
+        parameters.extend((1..=self.categoricals).map(|i| {
+            spec(
+                format!("c{i}"),
+                ParameterValueSpec::CategoricalValueSpec(CategoricalValueSpec {
+                    values: CATEGORIES.iter().map(|c| c.to_string()).collect(),
+                    default_value: None,
+                }),
+            )
+        }));
+
+        parameters
+    }
+
+    fn metrics(&self) -> Vec<MetricSpec> {
+        minimize(&[METRIC_ID.to_string()])
+    }
+
+    fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error> {
+        let mut value = 0.0;
+
+        let x = numbers(trial, &vec![(0.0, 1.0); self.doubles])?;
+        for (i, x) in x.into_iter().enumerate() {
+            value += (x - self.double_target(i)).powi(2);
+        }
+
+        for i in 0..self.integers {
+            let parameter_id = format!("n{}", i + 1);
+            let n = number(trial, &parameter_id)?;
+            if n.fract() != 0.0 || !(0.0..=MAX_INTEGER as f64).contains(&n) {
+                return Err(Error::InvalidValue(parameter_id));
+            }
+            value += ((n - Self::integer_target(i) as f64) / MAX_INTEGER as f64).powi(2);
+        }
+
+        for i in 0..self.discretes {
+            let parameter_id = format!("q{}", i + 1);
+            let q = number(trial, &parameter_id)?;
+            if !DISCRETE_VALUES.contains(&q) {
+                return Err(Error::InvalidValue(parameter_id));
+            }
+            value += (q - Self::discrete_target(i)).powi(2) / 4.0;
+        }
+
+        for i in 0..self.categoricals {
+            let parameter_id = format!("c{}", i + 1);
+            let c = string(trial, &parameter_id)?;
+            if !CATEGORIES.contains(&c) {
+                return Err(Error::InvalidValue(parameter_id));
+            }
+            if c != Self::categorical_target(i) {
+                value += 0.5;
+            }
+        }
+
+        Ok(measurement(vec![(METRIC_ID.to_string(), value)]))
+    }
+
+    fn optimum(&self) -> Option<f64> {
+        Some(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use super::Mixed;
+    use crate::benchmarks::{Error, Problem};
+    use crate::vizier::{Trial, trial};
+
+    fn trial(parameters: &[(&str, Kind)]) -> Trial {
+        Trial {
+            parameters: parameters
+                .iter()
+                .map(|(parameter_id, kind)| trial::Parameter {
+                    parameter_id: parameter_id.to_string(),
+                    value: Some(Value {
+                        kind: Some(kind.clone()),
+                    }),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_evaluates_mixed_parameters() {
+        let problem = Mixed::new(1, 1, 1, 1);
+        assert_eq!(problem.parameters().len(), 4);
+
+        let value = |x: f64, n: f64, q: f64, c: &str| {
+            problem.evaluate(&trial(&[
+                ("x1", Kind::NumberValue(x)),
+                ("n1", Kind::NumberValue(n)),
+                ("q1", Kind::NumberValue(q)),
+                ("c1", Kind::StringValue(c.to_string())),
+            ]))
+        };
+
+        assert_eq!(value(0.5, 3.0, 0.5, "a").unwrap().metrics[0].value, 0.0);
+        assert_eq!(value(0.5, 3.0, 0.5, "b").unwrap().metrics[0].value, 0.5);
+        assert_eq!(
+            value(0.5, 3.0, 0.5, "e"),
+            Err(Error::InvalidValue("c1".to_string()))
+        );
+        assert_eq!(
+            value(0.5, 3.5, 0.5, "a"),
+            Err(Error::InvalidValue("n1".to_string()))
+        );
+
+        // values outside the search space
+        assert_eq!(
+            value(5.0, 3.0, 0.5, "a"),
+            Err(Error::InvalidValue("x1".to_string()))
+        );
+        assert_eq!(
+            value(0.5, 42.0, 0.5, "a"),
+            Err(Error::InvalidValue("n1".to_string()))
+        );
+        assert_eq!(
+            value(0.5, -1.0, 0.5, "a"),
+            Err(Error::InvalidValue("n1".to_string()))
+        );
+        assert_eq!(
+            value(0.5, 3.0, 0.3, "a"),
+            Err(Error::InvalidValue("q1".to_string()))
+        );
+    }
+}
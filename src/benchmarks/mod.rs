@@ -0,0 +1,367 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Black-box benchmark problems - to compare algorithms on functions with a known
+//! optimum.
+//!
+//! A [Problem] exposes its [StudySpec], evaluates the parameters of a trial and knows
+//! its optimum, so the [regret] of a study can be computed:
+//! - [functions]: classic single-objective test functions, all minimized,
+//! - [multi_objective]: ZDT and DTLZ problems, whose optimum is the hypervolume of
+//!   their Pareto front with respect to their [Problem::reference_point],
+//! - [mixed]: synthetic problems over double, integer, discrete and categorical
+//!   parameters.
+//!
+//! The [harness] compares algorithms on these problems over repeated seeds.
+//!
+//! ```no_run
+//! # use oss_vizier::benchmarks::functions::Branin;
+//! # use oss_vizier::benchmarks::{Problem, regret};
+//! # use oss_vizier::optimizer::local::{LocalOptimizer, RandomSearch};
+//! # use oss_vizier::optimizer::run;
+//! # async fn example() -> Result<(), oss_vizier::Error> {
+//! let problem = Branin;
+//! let study_spec = problem.study_spec("RANDOM_SEARCH".to_string());
+//!
+//! let trials = run(&mut LocalOptimizer::new(study_spec, RandomSearch::new(1)), 50, |t| {
+//!     problem.evaluate(t).unwrap()
+//! })
+//! .await?;
+//! let regret = regret(&problem, &trials);
+//! # Ok(())
+//! # }
+//! ```
+
+use prost_types::value::Kind;
+
+use crate::Trial;
+use crate::analysis::hypervolume::hypervolume_curve;
+use crate::analysis::{objective_values, oriented};
+use crate::vizier::study_spec::metric_spec::GoalType;
+use crate::vizier::study_spec::parameter_spec::{DoubleValueSpec, ParameterValueSpec, ScaleType};
+use crate::vizier::study_spec::{MetricSpec, ObservationNoise, ParameterSpec};
+use crate::vizier::{Measurement, StudySpec, measurement};
+
+pub mod functions;
+pub mod harness;
+pub mod mixed;
+pub mod multi_objective;
+
+/// Error returned when evaluating a trial.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    /// The trial has no value for a parameter of the problem.
+    #[error("missing parameter {0}")]
+    MissingParameter(String),
+    /// The value of a parameter has the wrong type or is not in the search space.
+    #[error("invalid value for parameter {0}")]
+    InvalidValue(String),
+}
+
+/// A benchmark problem.
+pub trait Problem {
+    /// The name of the problem - e.g. `ackley-10d`.
+    fn name(&self) -> String;
+
+    /// The search space.
+    fn parameters(&self) -> Vec<ParameterSpec>;
+
+    /// The metrics - objectives first.
+    fn metrics(&self) -> Vec<MetricSpec>;
+
+    /// The final measurement of a trial.
+    fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error>;
+
+    /// The optimal value of the objective or, for multi-objective problems, the
+    /// hypervolume of the Pareto front with respect to [Problem::reference_point] -
+    /// `None` if unknown.
+    fn optimum(&self) -> Option<f64>;
+
+    /// The reference point of the hypervolume of multi-objective problems, in the units
+    /// of the objectives.
+    fn reference_point(&self) -> Option<Vec<f64>> {
+        None
+    }
+
+    /// The [StudySpec] of a study of the problem with `algorithm`.
+    fn study_spec(&self, algorithm: String) -> StudySpec {
+        crate::study::spec::StudySpecBuilder::new(algorithm, ObservationNoise::Low)
+            .with_metric_specs(self.metrics())
+            .with_parameters(self.parameters())
+            .build()
+    }
+}
+
+/// The standard suite: the classic functions, 2-objective ZDT, 3-objective DTLZ and
+/// the mixed problem.
+pub fn suite() -> Vec<Box<dyn Problem>> {
+    use functions::*;
+    use multi_objective::*;
+
+    vec![
+        Box::new(Branin),
+        Box::new(Hartmann3),
+        Box::new(Hartmann6),
+        Box::new(Rosenbrock::new(4)),
+        Box::new(Ackley::new(5)),
+        Box::new(Rastrigin::new(5)),
+        Box::new(Levy::new(5)),
+        Box::new(Himmelblau),
+        Box::new(Zdt::zdt1(10)),
+        Box::new(Zdt::zdt2(10)),
+        Box::new(Zdt::zdt3(10)),
+        Box::new(Dtlz::dtlz1(7, 3)),
+        Box::new(Dtlz::dtlz2(12, 3)),
+        Box::new(mixed::Mixed::new(2, 2, 1, 2)),
+    ]
+}
+
+/// Regret of the first `i + 1` trials for each index `i` - order the trials by id or
+/// completion time beforehand.
+///
+/// For single-objective problems, the distance between the optimum and the best value
+/// so far - `None` until a trial succeeds. For multi-objective problems, the
+/// difference between the optimal hypervolume and the hypervolume so far - clamped to
+/// 0 as the hypervolume is estimated beyond 3 objectives. `None` entirely if the
+/// optimum of the problem is unknown.
+pub fn regret(problem: &dyn Problem, trials: &[Trial]) -> Option<Vec<Option<f64>>> {
+    let optimum = problem.optimum()?;
+    let metrics = problem.metrics();
+
+    if let Some(reference) = problem.reference_point() {
+        let curve = hypervolume_curve(trials, &metrics, &reference).ok()?;
+        return Some(
+            curve
+                .into_iter()
+                .map(|hv| Some((optimum - hv).max(0.0)))
+                .collect(),
+        );
+    }
+
+    let metric = metrics.first()?;
+    let optimum = oriented(metric, optimum);
+
+    let mut best: Option<f64> = None;
+    Some(
+        trials
+            .iter()
+            .map(|trial| {
+                if let Some(values) = objective_values(trial, &metrics) {
+                    best = Some(best.map_or(values[0], |b| b.max(values[0])));
+                }
+                best.map(|b| (optimum - b).max(0.0))
+            })
+            .collect(),
+    )
+}
+
+/// Double parameters `x1`, `x2`, ... with the given bounds.
+fn double_parameters(bounds: &[(f64, f64)]) -> Vec<ParameterSpec> {
+    bounds
+        .iter()
+        .enumerate()
+        .map(|(i, (min_value, max_value))| ParameterSpec {
+            parameter_id: format!("x{}", i + 1),
+            scale_type: ScaleType::UnitLinearScale as i32,
+            conditional_parameter_specs: vec![],
+            parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(DoubleValueSpec {
+                min_value: *min_value,
+                max_value: *max_value,
+                default_value: None,
+            })),
+        })
+        .collect()
+}
+
+/// Minimized metrics with the given ids.
+fn minimize(metric_ids: &[String]) -> Vec<MetricSpec> {
+    metric_ids
+        .iter()
+        .map(|metric_id| MetricSpec {
+            metric_id: metric_id.clone(),
+            goal: GoalType::Minimize as i32,
+            safety_config: None,
+        })
+        .collect()
+}
+
+/// The final measurement with the given metric values.
+fn measurement(metrics: Vec<(String, f64)>) -> Measurement {
+    Measurement {
+        elapsed_duration: None,
+        step_count: 1,
+        metrics: metrics
+            .into_iter()
+            .map(|(metric_id, value)| measurement::Metric { metric_id, value })
+            .collect(),
+    }
+}
+
+fn value<'a>(trial: &'a Trial, parameter_id: &str) -> Result<&'a Kind, Error> {
+    trial
+        .parameters
+        .iter()
+        .find(|p| p.parameter_id == parameter_id)
+        .and_then(|p| p.value.as_ref())
+        .and_then(|v| v.kind.as_ref())
+        .ok_or_else(|| Error::MissingParameter(parameter_id.to_string()))
+}
+
+fn number(trial: &Trial, parameter_id: &str) -> Result<f64, Error> {
+    match value(trial, parameter_id)? {
+        Kind::NumberValue(v) if v.is_finite() => Ok(*v),
+        _ => Err(Error::InvalidValue(parameter_id.to_string())),
+    }
+}
+
+fn string<'a>(trial: &'a Trial, parameter_id: &str) -> Result<&'a str, Error> {
+    match value(trial, parameter_id)? {
+        Kind::StringValue(v) => Ok(v),
+        _ => Err(Error::InvalidValue(parameter_id.to_string())),
+    }
+}
+
+/// The values of the parameters `x1`, `x2`, ... - one per bounds, within the bounds.
+fn numbers(trial: &Trial, bounds: &[(f64, f64)]) -> Result<Vec<f64>, Error> {
+    bounds
+        .iter()
+        .enumerate()
+        .map(|(i, (min, max))| {
+            let parameter_id = format!("x{}", i + 1);
+            let x = number(trial, &parameter_id)?;
+            if x < *min || x > *max {
+                return Err(Error::InvalidValue(parameter_id));
+            }
+            Ok(x)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use crate::Trial;
+    use crate::vizier::trial;
+
+    /// A trial with the double parameters `x1`, `x2`, ...
+    pub(crate) fn point(x: &[f64]) -> Trial {
+        Trial {
+            parameters: x
+                .iter()
+                .enumerate()
+                .map(|(i, v)| trial::Parameter {
+                    parameter_id: format!("x{}", i + 1),
+                    value: Some(Value {
+                        kind: Some(Kind::NumberValue(*v)),
+                    }),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::functions::Branin;
+    use super::multi_objective::Zdt;
+    use super::testing::point;
+    use super::{Error, Problem, regret, suite};
+    use crate::vizier::study_spec::{MetricSpec, ParameterSpec};
+    use crate::vizier::{Measurement, Trial, trial};
+
+    #[test]
+    fn it_computes_the_regret() {
+        let problem = Branin;
+
+        let mut trials = vec![
+            point(&[0.0, 0.0]),
+            point(&[std::f64::consts::PI, 2.275]),
+            point(&[0.0, 0.0]),
+        ];
+        trials[0].state = trial::State::Infeasible as i32;
+        for t in &mut trials[1..] {
+            t.final_measurement = Some(problem.evaluate(t).unwrap());
+            t.state = trial::State::Succeeded as i32;
+        }
+
+        let regret = regret(&problem, &trials).unwrap();
+        assert_eq!(regret[0], None);
+        assert!(regret[1].unwrap() < 1e-5);
+        assert_eq!(regret[1], regret[2]);
+    }
+
+    #[test]
+    fn it_clamps_the_regret_of_overestimated_hypervolumes() {
+        /// ZDT1 with an optimum below the hypervolume of its trials - as an estimate of
+        /// the hypervolume can be.
+        struct Underestimated(Zdt);
+
+        impl Problem for Underestimated {
+            fn name(&self) -> String {
+                self.0.name()
+            }
+
+            fn parameters(&self) -> Vec<ParameterSpec> {
+                self.0.parameters()
+            }
+
+            fn metrics(&self) -> Vec<MetricSpec> {
+                self.0.metrics()
+            }
+
+            fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error> {
+                self.0.evaluate(trial)
+            }
+
+            fn optimum(&self) -> Option<f64> {
+                Some(0.0)
+            }
+
+            fn reference_point(&self) -> Option<Vec<f64>> {
+                self.0.reference_point()
+            }
+        }
+
+        let problem = Underestimated(Zdt::zdt1(2));
+        let mut trial = point(&[0.5, 0.0]);
+        trial.final_measurement = Some(problem.evaluate(&trial).unwrap());
+        trial.state = trial::State::Succeeded as i32;
+
+        assert_eq!(regret(&problem, &[trial]).unwrap(), vec![Some(0.0)]);
+    }
+
+    #[test]
+    fn it_has_valid_study_specs() {
+        for problem in suite() {
+            let study_spec = problem.study_spec("RANDOM_SEARCH".to_string());
+            assert!(!study_spec.parameters.is_empty(), "{}", problem.name());
+            assert!(!study_spec.metrics.is_empty(), "{}", problem.name());
+            assert!(problem.optimum().is_some(), "{}", problem.name());
+            assert_eq!(
+                problem.reference_point().map(|r| r.len()),
+                (study_spec.metrics.len() > 1).then_some(study_spec.metrics.len()),
+                "{}",
+                problem.name()
+            );
+        }
+
+        assert_eq!(
+            Branin.evaluate(&point(&[1.0])),
+            Err(Error::MissingParameter("x2".to_string()))
+        );
+    }
+}
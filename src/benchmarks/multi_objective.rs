@@ -0,0 +1,316 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multi-objective test problems - ZDT and DTLZ.
+//!
+//! The parameters are the doubles `x1`, `x2`, ... in `[0, 1]` and the objectives are
+//! the minimized metrics `f1`, `f2`, ... The reference points are the usual ones:
+//! `(11, 11)` for ZDT, `400` for DTLZ1 and `1.1` for DTLZ2 on every objective.
+
+use std::f64::consts::PI;
+
+use crate::Trial;
+use crate::analysis::hypervolume::exact_hypervolume;
+use crate::benchmarks::{Error, Problem, double_parameters, measurement, minimize, numbers};
+use crate::vizier::Measurement;
+use crate::vizier::study_spec::{MetricSpec, ParameterSpec};
+
+/// Number of points of the Pareto fronts sampled to compute the optimal hypervolume
+/// of ZDT problems.
+const FRONT_SAMPLES: usize = 10_001;
+
+fn objective_ids(num_objectives: usize) -> Vec<String> {
+    (1..=num_objectives).map(|i| format!("f{i}")).collect()
+}
+
+fn objectives(values: Vec<f64>) -> Measurement {
+    measurement(
+        objective_ids(values.len())
+            .into_iter()
+            .zip(values)
+            .collect(),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ZdtVariant {
+    Zdt1,
+    Zdt2,
+    Zdt3,
+}
+
+/// ZDT problems - 2 objectives, `dimension` parameters.
+///
+/// ZDT1 has a convex Pareto front, ZDT2 a concave one and ZDT3 a disconnected one.
+#[derive(Clone, Copy, Debug)]
+pub struct Zdt {
+    variant: ZdtVariant,
+    dimension: usize,
+}
+
+impl Zdt {
+    /// ZDT1 with `dimension` parameters - at least 2.
+    pub fn zdt1(dimension: usize) -> Self {
+        Self::new(ZdtVariant::Zdt1, dimension)
+    }
+
+    /// ZDT2 with `dimension` parameters - at least 2.
+    pub fn zdt2(dimension: usize) -> Self {
+        Self::new(ZdtVariant::Zdt2, dimension)
+    }
+
+    /// ZDT3 with `dimension` parameters - at least 2.
+    pub fn zdt3(dimension: usize) -> Self {
+        Self::new(ZdtVariant::Zdt3, dimension)
+    }
+
+    fn new(variant: ZdtVariant, dimension: usize) -> Self {
+        Self {
+            variant,
+            dimension: dimension.max(2),
+        }
+    }
+
+    /// `f2` given `f1` and `g` - the Pareto front for `g = 1`.
+    fn f2(&self, f1: f64, g: f64) -> f64 {
+        let r = f1 / g;
+        g * match self.variant {
+            ZdtVariant::Zdt1 => 1.0 - r.sqrt(),
+            ZdtVariant::Zdt2 => 1.0 - r * r,
+            ZdtVariant::Zdt3 => 1.0 - r.sqrt() - r * (10.0 * PI * f1).sin(),
+        }
+    }
+}
+
+impl Problem for Zdt {
+    fn name(&self) -> String {
+        let variant = match self.variant {
+            ZdtVariant::Zdt1 => "zdt1",
+            ZdtVariant::Zdt2 => "zdt2",
+            ZdtVariant::Zdt3 => "zdt3",
+        };
+        format!("{variant}-{}d", self.dimension)
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        double_parameters(&vec![(0.0, 1.0); self.dimension])
+    }
+
+    fn metrics(&self) -> Vec<MetricSpec> {
+        minimize(&objective_ids(2))
+    }
+
+    fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error> {
+        let x = numbers(trial, &vec![(0.0, 1.0); self.dimension])?;
+        let f1 = x[0];
+        let g = 1.0 + 9.0 * x[1..].iter().sum::<f64>() / (self.dimension - 1) as f64;
+        Ok(objectives(vec![f1, self.f2(f1, g)]))
+    }
+
+    fn optimum(&self) -> Option<f64> {
+        let reference = self.reference_point()?;
+        let front: Vec<Vec<f64>> = (0..FRONT_SAMPLES)
+            .map(|i| i as f64 / (FRONT_SAMPLES - 1) as f64)
+            .map(|f1| vec![-f1, -self.f2(f1, 1.0)])
+            .collect();
+
+        exact_hypervolume(&front, &reference.iter().map(|r| -r).collect::<Vec<_>>()).ok()
+    }
+
+    fn reference_point(&self) -> Option<Vec<f64>> {
+        Some(vec![11.0, 11.0])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DtlzVariant {
+    Dtlz1,
+    Dtlz2,
+}
+
+/// DTLZ problems - `num_objectives` objectives, `dimension` parameters.
+///
+/// DTLZ1 has a linear Pareto front `f1 + ... + fm = 0.5` and many local fronts, DTLZ2 a
+/// spherical one `f1^2 + ... + fm^2 = 1`.
+#[derive(Clone, Copy, Debug)]
+pub struct Dtlz {
+    variant: DtlzVariant,
+    dimension: usize,
+    num_objectives: usize,
+}
+
+impl Dtlz {
+    /// DTLZ1 with `dimension` parameters - at least `num_objectives`.
+    pub fn dtlz1(dimension: usize, num_objectives: usize) -> Self {
+        Self::new(DtlzVariant::Dtlz1, dimension, num_objectives)
+    }
+
+    /// DTLZ2 with `dimension` parameters - at least `num_objectives`.
+    pub fn dtlz2(dimension: usize, num_objectives: usize) -> Self {
+        Self::new(DtlzVariant::Dtlz2, dimension, num_objectives)
+    }
+
+    fn new(variant: DtlzVariant, dimension: usize, num_objectives: usize) -> Self {
+        let num_objectives = num_objectives.max(2);
+        Self {
+            variant,
+            dimension: dimension.max(num_objectives),
+            num_objectives,
+        }
+    }
+}
+
+impl Problem for Dtlz {
+    fn name(&self) -> String {
+        let variant = match self.variant {
+            DtlzVariant::Dtlz1 => "dtlz1",
+            DtlzVariant::Dtlz2 => "dtlz2",
+        };
+        format!("{variant}-{}d-{}m", self.dimension, self.num_objectives)
+    }
+
+    fn parameters(&self) -> Vec<ParameterSpec> {
+        double_parameters(&vec![(0.0, 1.0); self.dimension])
+    }
+
+    fn metrics(&self) -> Vec<MetricSpec> {
+        minimize(&objective_ids(self.num_objectives))
+    }
+
+    fn evaluate(&self, trial: &Trial) -> Result<Measurement, Error> {
+        let x = numbers(trial, &vec![(0.0, 1.0); self.dimension])?;
+        let m = self.num_objectives;
+        let (position, distance) = x.split_at(m - 1);
+
+        let values = match self.variant {
+            DtlzVariant::Dtlz1 => {
+                let g = 100.0
+                    * (distance.len() as f64
+                        + distance
+                            .iter()
+                            .map(|v| (v - 0.5).powi(2) - (20.0 * PI * (v - 0.5)).cos())
+                            .sum::<f64>());
+                (0..m)
+                    .map(|i| {
+                        let mut f = 0.5 * (1.0 + g);
+                        f *= position[..m - 1 - i].iter().product::<f64>();
+                        if i > 0 {
+                            f *= 1.0 - position[m - 1 - i];
+                        }
+                        f
+                    })
+                    .collect()
+            }
+            DtlzVariant::Dtlz2 => {
+                let g = distance.iter().map(|v| (v - 0.5).powi(2)).sum::<f64>();
+                (0..m)
+                    .map(|i| {
+                        let mut f = 1.0 + g;
+                        f *= position[..m - 1 - i]
+                            .iter()
+                            .map(|v| (v * PI / 2.0).cos())
+                            .product::<f64>();
+                        if i > 0 {
+                            f *= (position[m - 1 - i] * PI / 2.0).sin();
+                        }
+                        f
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(objectives(values))
+    }
+
+    /// The volume of the box of the reference point minus the volume under the front.
+    fn optimum(&self) -> Option<f64> {
+        let m = self.num_objectives as i32;
+        let reference = self.reference_point()?[0];
+
+        let under_front = match self.variant {
+            // simplex with edges of 0.5
+            DtlzVariant::Dtlz1 => 0.5f64.powi(m) / (1..=m).map(f64::from).product::<f64>(),
+            // positive orthant of the unit ball
+            DtlzVariant::Dtlz2 => unit_ball_volume(m) / 2f64.powi(m),
+        };
+
+        Some(reference.powi(m) - under_front)
+    }
+
+    fn reference_point(&self) -> Option<Vec<f64>> {
+        let reference = match self.variant {
+            DtlzVariant::Dtlz1 => 400.0,
+            DtlzVariant::Dtlz2 => 1.1,
+        };
+        Some(vec![reference; self.num_objectives])
+    }
+}
+
+/// Volume of the unit ball in `dimension` dimensions.
+fn unit_ball_volume(dimension: i32) -> f64 {
+    match dimension {
+        0 => 1.0,
+        1 => 2.0,
+        n => unit_ball_volume(n - 2) * 2.0 * PI / f64::from(n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dtlz, Zdt};
+    use crate::benchmarks::Problem;
+    use crate::benchmarks::testing::point;
+
+    fn values(problem: &dyn Problem, x: &[f64]) -> Vec<f64> {
+        problem
+            .evaluate(&point(x))
+            .unwrap()
+            .metrics
+            .iter()
+            .map(|m| m.value)
+            .collect()
+    }
+
+    #[test]
+    fn it_evaluates_pareto_optimal_points() {
+        let mut x = vec![0.0; 10];
+        x[0] = 0.25;
+        assert_eq!(values(&Zdt::zdt1(10), &x), vec![0.25, 0.5]);
+        assert_eq!(values(&Zdt::zdt2(10), &x), vec![0.25, 0.9375]);
+
+        let mut x = vec![0.5; 7];
+        x[0] = 0.3;
+        x[1] = 0.8;
+        let f = values(&Dtlz::dtlz1(7, 3), &x);
+        assert!((f.iter().sum::<f64>() - 0.5).abs() < 1e-9);
+
+        let f = values(&Dtlz::dtlz2(7, 3), &x);
+        assert!((f.iter().map(|v| v * v).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_computes_the_optimal_hypervolume() {
+        // 11 x 11 minus the area under 1 - sqrt(f1) and 1 - f1^2
+        assert!((Zdt::zdt1(5).optimum().unwrap() - (121.0 - 1.0 / 3.0)).abs() < 1e-3);
+        assert!((Zdt::zdt2(5).optimum().unwrap() - (121.0 - 2.0 / 3.0)).abs() < 1e-3);
+
+        // the disconnected front of ZDT3 goes below f2 = 0
+        assert!((Zdt::zdt3(5).optimum().unwrap() - 128.778_116).abs() < 1e-2);
+
+        let dtlz2 = Dtlz::dtlz2(4, 2).optimum().unwrap();
+        assert!((dtlz2 - (1.21 - std::f64::consts::PI / 4.0)).abs() < 1e-12);
+        let dtlz1 = Dtlz::dtlz1(4, 2).optimum().unwrap();
+        assert!((dtlz1 - (160_000.0 - 0.125)).abs() < 1e-9);
+    }
+}
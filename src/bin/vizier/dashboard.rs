@@ -0,0 +1,665 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactive terminal dashboard of a live study.
+//!
+//! Polls the study and its trials and shows the trial states, the best value of each
+//! metric, the recent measurements and the activity of the workers. The selected
+//! trial can be stopped with `s`.
+//!
+//! The requests run in background tasks: a slow or unreachable service does not
+//! freeze the dashboard.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use oss_vizier::analysis::{final_metric, is_safe, oriented};
+use oss_vizier::model::study::StudyName;
+use oss_vizier::model::trial::ToTrialName;
+use oss_vizier::pagination::collect_all;
+use oss_vizier::vizier::study_spec::MetricSpec;
+use oss_vizier::vizier::trial::State;
+use oss_vizier::vizier::{Measurement, Study, Trial};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::Client;
+
+/// States shown in the header, in lifecycle order.
+const STATES: [State; 5] = [
+    State::Requested,
+    State::Active,
+    State::Stopping,
+    State::Succeeded,
+    State::Infeasible,
+];
+
+/// Time after which a refresh is abandoned - the next one starts at the next interval.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Runs the dashboard of `study_name` until the user quits.
+pub(crate) async fn run(
+    client: Client,
+    study_name: StudyName,
+    interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let mut terminal = ratatui::try_init()?;
+    let result = Dashboard::new(client, study_name)
+        .run(&mut terminal, interval)
+        .await;
+    ratatui::restore();
+    result
+}
+
+/// Outcome of a request run in the background.
+enum Update {
+    /// The study - if it was fetched - and its trials.
+    Refreshed(Result<(Option<Box<Study>>, Vec<Trial>), String>),
+    /// The status of a stop request.
+    Stopped(String),
+}
+
+struct Dashboard {
+    client: Client,
+    study_name: StudyName,
+    study: Option<Study>,
+    trials: Vec<Trial>,
+    table_state: TableState,
+    /// Id of the selected trial - the rows shift as new trials are listed.
+    selected_id: Option<String>,
+    status: String,
+    refreshed_at: Option<Instant>,
+    /// Whether a refresh is running.
+    refreshing: bool,
+    updates: Sender<Update>,
+    received: Receiver<Update>,
+}
+
+impl Dashboard {
+    fn new(client: Client, study_name: StudyName) -> Self {
+        let (updates, received) = channel();
+        Self {
+            client,
+            study_name,
+            study: None,
+            trials: vec![],
+            table_state: TableState::default().with_selected(Some(0)),
+            selected_id: None,
+            status: String::new(),
+            refreshed_at: None,
+            refreshing: false,
+            updates,
+            received,
+        }
+    }
+
+    async fn run(
+        mut self,
+        terminal: &mut DefaultTerminal,
+        interval: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            while let Ok(update) = self.received.try_recv() {
+                self.apply(update);
+            }
+            if !self.refreshing && self.refreshed_at.is_none_or(|t| t.elapsed() >= interval) {
+                self.refresh();
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select(1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+                KeyCode::Char('r') => self.refreshed_at = None,
+                KeyCode::Char('s') => self.stop_selected(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Starts fetching the study - the first time - and its trials in the background.
+    fn refresh(&mut self) {
+        self.refreshed_at = Some(Instant::now());
+        self.refreshing = true;
+
+        let mut client = self.client.clone();
+        let study_name = self.study_name.clone();
+        let fetch_study = self.study.is_none();
+        let updates = self.updates.clone();
+        tokio::spawn(async move {
+            let refresh = async {
+                let study = if fetch_study {
+                    let request = client.mk_get_study_request(study_name.clone());
+                    let study = client
+                        .get_study(request)
+                        .await
+                        .map_err(|e| format!("failed to get the study: {e}"))?;
+                    Some(Box::new(study))
+                } else {
+                    None
+                };
+
+                let trials = collect_all(client.list_trials_stream(study_name, 0))
+                    .await
+                    .map_err(|e| format!("failed to list the trials: {e}"))?;
+
+                Ok((study, trials))
+            };
+
+            let result = tokio::time::timeout(REFRESH_TIMEOUT, refresh)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(format!(
+                        "refresh timed out after {}s",
+                        REFRESH_TIMEOUT.as_secs()
+                    ))
+                });
+            // the dashboard may have quit
+            let _ = updates.send(Update::Refreshed(result));
+        });
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Refreshed(result) => {
+                self.refreshing = false;
+                match result {
+                    Ok((study, trials)) => {
+                        if let Some(study) = study {
+                            self.study = Some(*study);
+                        }
+                        self.set_trials(trials);
+                    }
+                    Err(status) => self.status = status,
+                }
+            }
+            Update::Stopped(status) => {
+                self.status = status;
+                self.refreshed_at = None;
+            }
+        }
+    }
+
+    /// Shows `trials`, latest first, keeping the selected trial.
+    fn set_trials(&mut self, mut trials: Vec<Trial>) {
+        trials.sort_by_key(|t| std::cmp::Reverse(t.id.parse::<u64>().unwrap_or(0)));
+        self.trials = trials;
+        self.restore_selection();
+    }
+
+    fn selected(&self) -> Option<&Trial> {
+        let id = self.selected_id.as_ref()?;
+        self.trials.iter().find(|t| &t.id == id)
+    }
+
+    /// Moves the selection by `delta`, within the trials.
+    fn select(&mut self, delta: isize) {
+        let last = self.trials.len().saturating_sub(1);
+        let current = self.table_state.selected().unwrap_or(0);
+        let row = current.saturating_add_signed(delta).min(last);
+        self.table_state.select(Some(row));
+        self.selected_id = self.trials.get(row).map(|t| t.id.clone());
+    }
+
+    /// Selects the row of the selected trial after a refresh - or the same row if the
+    /// trial is gone.
+    fn restore_selection(&mut self) {
+        let row = self
+            .selected_id
+            .as_ref()
+            .and_then(|id| self.trials.iter().position(|t| &t.id == id));
+        match row {
+            Some(row) => self.table_state.select(Some(row)),
+            None => self.select(0),
+        }
+    }
+
+    /// Starts stopping the selected trial in the background.
+    fn stop_selected(&mut self) {
+        let Some(trial) = self.selected() else {
+            return;
+        };
+        if !matches!(trial.state(), State::Requested | State::Active) {
+            self.status = format!(
+                "trial {} is {} - only requested and active trials can be stopped",
+                trial.id,
+                trial.state().as_str_name()
+            );
+            return;
+        }
+
+        let trial_id = trial.id.clone();
+        let request = self.client.mk_stop_trial_request(trial.to_trial_name());
+        self.status = format!("stopping trial {trial_id}...");
+
+        let mut client = self.client.clone();
+        let updates = self.updates.clone();
+        tokio::spawn(async move {
+            let status = match client.stop_trial(request).await {
+                Ok(_) => format!("stopping trial {trial_id}"),
+                Err(e) => format!("failed to stop trial {trial_id}: {e}"),
+            };
+            let _ = updates.send(Update::Stopped(status));
+        });
+    }
+
+    fn metrics(&self) -> Vec<MetricSpec> {
+        self.study
+            .as_ref()
+            .and_then(|s| s.study_spec.as_ref())
+            .map(|s| s.metrics.clone())
+            .unwrap_or_default()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, main, measurements, footer] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [trials, side] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(main);
+        let [best, workers] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(side);
+
+        let metrics = self.metrics();
+        self.draw_header(frame, header);
+        self.draw_trials(frame, trials, &metrics);
+        self.draw_best(frame, best, &metrics);
+        self.draw_workers(frame, workers);
+        self.draw_measurements(frame, measurements);
+
+        let help = "↑/↓ select  s stop trial  r refresh  q quit";
+        let footer_text = if self.status.is_empty() {
+            help.to_string()
+        } else {
+            format!("{help}  |  {}", self.status)
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let title = match &self.study {
+            Some(study) => format!("{} ({})", study.display_name, study.name),
+            None => self.study_name.to_string(),
+        };
+
+        let mut counts: Vec<String> = STATES
+            .iter()
+            .map(|state| {
+                let count = self.trials.iter().filter(|t| t.state() == *state).count();
+                format!("{} {count}", state.as_str_name())
+            })
+            .collect();
+        if self.refreshing {
+            counts.push("refreshing...".to_string());
+        } else if let Some(t) = self.refreshed_at {
+            counts.push(format!("refreshed {}s ago", t.elapsed().as_secs()));
+        }
+
+        frame.render_widget(
+            Paragraph::new(counts.join("  ")).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+
+    fn draw_trials(&mut self, frame: &mut Frame, area: Rect, metrics: &[MetricSpec]) {
+        let mut header = vec!["ID".to_string(), "STATE".to_string(), "CLIENT".to_string()];
+        header.push("STEP".to_string());
+        header.extend(metrics.iter().map(|m| m.metric_id.clone()));
+
+        let rows: Vec<Row> = self
+            .trials
+            .iter()
+            .map(|trial| {
+                let last = trial.measurements.last();
+                let mut cells = vec![
+                    trial.id.clone(),
+                    trial.state().as_str_name().to_string(),
+                    trial.client_id.clone(),
+                    last.map(|m| m.step_count.to_string()).unwrap_or_default(),
+                ];
+                // final value, or the latest intermediate one
+                cells.extend(metrics.iter().map(|metric| {
+                    final_metric(trial, &metric.metric_id)
+                        .or_else(|| last.and_then(|m| metric_value(m, &metric.metric_id)))
+                        .map(format_value)
+                        .unwrap_or_default()
+                }));
+                Row::new(cells).style(Style::default().fg(state_color(trial.state())))
+            })
+            .collect();
+
+        let mut widths = vec![
+            Constraint::Length(6),
+            Constraint::Length(11),
+            Constraint::Length(14),
+            Constraint::Length(7),
+        ];
+        widths.extend(metrics.iter().map(|_| Constraint::Min(10)));
+
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .block(Block::bordered().title("Trials"));
+        frame.render_stateful_widget(table, area, &mut self.table_state);
+    }
+
+    fn draw_best(&self, frame: &mut Frame, area: Rect, metrics: &[MetricSpec]) {
+        let items: Vec<ListItem> = metrics
+            .iter()
+            .map(|metric| {
+                let best = self
+                    .trials
+                    .iter()
+                    .filter(|t| t.state() == State::Succeeded && is_safe(t, metrics))
+                    .filter_map(|t| {
+                        final_metric(t, &metric.metric_id)
+                            .filter(|v| v.is_finite())
+                            .map(|v| (t, v))
+                    })
+                    .max_by(|a, b| oriented(metric, a.1).total_cmp(&oriented(metric, b.1)));
+
+                let text = match best {
+                    Some((trial, value)) => format!(
+                        "{} ({}): {} - trial {}",
+                        metric.metric_id,
+                        metric.goal().as_str_name(),
+                        format_value(value),
+                        trial.id
+                    ),
+                    None => format!("{}: -", metric.metric_id),
+                };
+                ListItem::new(text)
+            })
+            .collect();
+
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title("Best")),
+            area,
+        );
+    }
+
+    fn draw_workers(&self, frame: &mut Frame, area: Rect) {
+        let workers = workers(&self.trials);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let items: Vec<ListItem> = workers
+            .iter()
+            .map(|(client_id, activity)| {
+                let client_id = if client_id.is_empty() { "-" } else { client_id };
+                ListItem::new(format!(
+                    "{client_id}: {} active, {} done, seen {}s ago",
+                    activity.active,
+                    activity.completed,
+                    (now - activity.last_seen).max(0)
+                ))
+            })
+            .collect();
+
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title("Workers")),
+            area,
+        );
+    }
+
+    fn draw_measurements(&self, frame: &mut Frame, area: Rect) {
+        // latest first: start time of the trial plus elapsed duration of the measurement
+        let mut recent: Vec<(i64, &Trial, &Measurement)> = self
+            .trials
+            .iter()
+            .flat_map(|trial| {
+                let start = trial.start_time.as_ref().map_or(0, |t| t.seconds);
+                trial.measurements.iter().map(move |m| {
+                    let elapsed = m.elapsed_duration.as_ref().map_or(0, |d| d.seconds);
+                    (start + elapsed, trial, m)
+                })
+            })
+            .collect();
+        recent.sort_by_key(|(at, trial, m)| {
+            std::cmp::Reverse((*at, trial.id.parse::<u64>().unwrap_or(0), m.step_count))
+        });
+
+        let lines: Vec<Line> = recent
+            .iter()
+            .take(area.height.saturating_sub(2) as usize)
+            .map(|(_, trial, m)| {
+                let metrics: Vec<String> = m
+                    .metrics
+                    .iter()
+                    .map(|x| format!("{}={}", x.metric_id, format_value(x.value)))
+                    .collect();
+                Line::from(format!(
+                    "trial {} step {}: {}",
+                    trial.id,
+                    m.step_count,
+                    metrics.join(" ")
+                ))
+            })
+            .collect();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Recent measurements")),
+            area,
+        );
+    }
+}
+
+/// Activity of a worker - the trials with its client id.
+#[derive(Debug, Default, PartialEq)]
+struct Activity {
+    active: usize,
+    completed: usize,
+    /// Latest start or end time of its trials, in seconds since the epoch.
+    last_seen: i64,
+}
+
+/// The activity of the workers, by client id.
+fn workers(trials: &[Trial]) -> BTreeMap<&str, Activity> {
+    let mut workers: BTreeMap<&str, Activity> = BTreeMap::new();
+    for trial in trials {
+        let activity = workers.entry(trial.client_id.as_str()).or_default();
+        match trial.state() {
+            State::Active | State::Stopping => activity.active += 1,
+            State::Succeeded | State::Infeasible => activity.completed += 1,
+            State::Unspecified | State::Requested => {}
+        }
+        let seen = trial
+            .end_time
+            .as_ref()
+            .or(trial.start_time.as_ref())
+            .map_or(0, |t| t.seconds);
+        activity.last_seen = activity.last_seen.max(seen);
+    }
+
+    workers
+}
+
+fn metric_value(measurement: &Measurement, metric_id: &str) -> Option<f64> {
+    measurement
+        .metrics
+        .iter()
+        .find(|m| m.metric_id == metric_id)
+        .map(|m| m.value)
+}
+
+fn format_value(value: f64) -> String {
+    format!("{value:.4}")
+}
+
+fn state_color(state: State) -> Color {
+    match state {
+        State::Requested => Color::Yellow,
+        State::Active => Color::Green,
+        State::Stopping => Color::Magenta,
+        State::Succeeded => Color::Reset,
+        State::Infeasible => Color::Red,
+        State::Unspecified => Color::DarkGray,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oss_vizier::builder::ClientBuilder;
+    use oss_vizier::model::study::StudyName;
+    use oss_vizier::vizier::Trial;
+    use oss_vizier::vizier::trial::State;
+    use prost_types::Timestamp;
+
+    use super::{Activity, Dashboard, workers};
+
+    fn trial(id: u64, state: State, client_id: &str) -> Trial {
+        let mut trial = Trial {
+            id: id.to_string(),
+            client_id: client_id.to_string(),
+            ..Default::default()
+        };
+        trial.set_state(state);
+        trial
+    }
+
+    fn dashboard(ids: &[u64]) -> Dashboard {
+        let client = ClientBuilder::new("http://localhost:28080", "owner")
+            .connect_lazy()
+            .unwrap();
+        let mut dashboard = Dashboard::new(
+            client,
+            StudyName::new("owner".to_string(), "study".to_string()),
+        );
+        dashboard.set_trials(
+            ids.iter()
+                .map(|&id| trial(id, State::Active, "worker"))
+                .collect(),
+        );
+        dashboard
+    }
+
+    #[tokio::test]
+    async fn it_moves_the_selection_within_the_trials() {
+        let mut dashboard = dashboard(&[1, 2, 3]);
+        assert_eq!(dashboard.table_state.selected(), Some(0));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("3"));
+
+        dashboard.select(1);
+        assert_eq!(dashboard.table_state.selected(), Some(1));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("2"));
+
+        dashboard.select(5);
+        assert_eq!(dashboard.table_state.selected(), Some(2));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("1"));
+
+        dashboard.select(-5);
+        assert_eq!(dashboard.table_state.selected(), Some(0));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_selected_trial_across_refreshes() {
+        let mut dashboard = dashboard(&[1, 2, 3]);
+        dashboard.select(1);
+
+        // new trials shift the rows
+        dashboard.set_trials(
+            [1, 2, 3, 4, 5]
+                .map(|id| trial(id, State::Active, "worker"))
+                .to_vec(),
+        );
+        assert_eq!(dashboard.table_state.selected(), Some(3));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("2"));
+
+        // the selected trial is gone - the row is kept
+        dashboard.set_trials(
+            [1, 3, 4, 5]
+                .map(|id| trial(id, State::Active, "worker"))
+                .to_vec(),
+        );
+        assert_eq!(dashboard.table_state.selected(), Some(3));
+        assert_eq!(dashboard.selected_id.as_deref(), Some("1"));
+
+        // fewer rows than the selected one
+        dashboard.set_trials(vec![]);
+        assert_eq!(dashboard.table_state.selected(), Some(0));
+        assert_eq!(dashboard.selected_id, None);
+    }
+
+    #[test]
+    fn it_aggregates_the_activity_of_the_workers() {
+        let time = |seconds| Some(Timestamp { seconds, nanos: 0 });
+        let trials = vec![
+            Trial {
+                start_time: time(10),
+                end_time: time(20),
+                ..trial(1, State::Succeeded, "a")
+            },
+            Trial {
+                start_time: time(30),
+                ..trial(2, State::Active, "a")
+            },
+            Trial {
+                start_time: time(15),
+                end_time: time(25),
+                ..trial(3, State::Infeasible, "b")
+            },
+            trial(4, State::Stopping, "b"),
+            trial(5, State::Requested, "c"),
+        ];
+
+        let workers = workers(&trials);
+        assert_eq!(
+            workers.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "a",
+                    Activity {
+                        active: 1,
+                        completed: 1,
+                        last_seen: 30,
+                    }
+                ),
+                (
+                    "b",
+                    Activity {
+                        active: 1,
+                        completed: 1,
+                        last_seen: 25,
+                    }
+                ),
+                ("c", Activity::default()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Command-line tool to manage OSS Vizier studies and trials.
+//!
+//! ```text
+//! vizier --endpoint http://localhost:28080 --owner me studies list
+//! vizier studies create --from-file study.yaml
+//! vizier trials suggest 1 --count 2 --client-id worker-0
+//! vizier trials complete 1 3 --metric accuracy=0.93
+//! vizier --output json optimal 1
+//! vizier dashboard 1 # with the `tui` feature
+//! ```
+
+use std::error::Error;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use oss_vizier::VizierClient;
+use oss_vizier::builder::InterceptedChannel;
+use oss_vizier::config::StudyConfig;
+use oss_vizier::model::study::StudyName;
+use oss_vizier::model::trial::TrialName;
+use oss_vizier::model::trial::complete::FinalMeasurementOrReason;
+use oss_vizier::pagination::collect_all;
+use oss_vizier::table::Table;
+use oss_vizier::vizier::{Measurement, Study, Trial, measurement, study};
+use serde::Serialize;
+
+#[cfg(feature = "tui")]
+mod dashboard;
+
+type Client = VizierClient<InterceptedChannel>;
+
+#[derive(Parser)]
+#[command(
+    name = "vizier",
+    version,
+    about = "Manage OSS Vizier studies and trials"
+)]
+struct Cli {
+    /// Endpoint of the Vizier service.
+    #[arg(
+        long,
+        global = true,
+        env = "ENDPOINT",
+        default_value = "http://localhost:28080"
+    )]
+    endpoint: String,
+
+    /// Owner of the studies.
+    #[arg(long, global = true, env = "VIZIER_OWNER", default_value = "owner")]
+    owner: String,
+
+    /// Bearer token sent with every request.
+    #[arg(long, global = true, env = "VIZIER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Output format.
+    #[arg(long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage studies.
+    #[command(subcommand)]
+    Studies(StudiesCommand),
+    /// Manage trials.
+    #[command(subcommand)]
+    Trials(TrialsCommand),
+    /// List the optimal trials of a study.
+    Optimal(StudyArg),
+    /// Export a study and its trials as a JSON snapshot.
+    Export {
+        #[command(flatten)]
+        study: StudyArg,
+        /// File to write the snapshot to - stdout by default.
+        #[arg(long, short = 'f')]
+        file: Option<PathBuf>,
+    },
+    /// Watch a study in an interactive terminal dashboard.
+    #[cfg(feature = "tui")]
+    Dashboard {
+        #[command(flatten)]
+        study: StudyArg,
+        /// Seconds between refreshes.
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum StudiesCommand {
+    /// List the studies of the owner.
+    List,
+    /// Get a study.
+    Get(StudyArg),
+    /// Create a study from a YAML, TOML or JSON study configuration.
+    Create {
+        /// Study configuration file.
+        #[arg(long)]
+        from_file: PathBuf,
+    },
+    /// Delete a study.
+    Delete(StudyArg),
+    /// Set the state of a study.
+    SetState {
+        #[command(flatten)]
+        study: StudyArg,
+        /// New state of the study.
+        #[arg(value_enum)]
+        state: StudyState,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StudyState {
+    Active,
+    Inactive,
+    Completed,
+}
+
+impl From<StudyState> for study::State {
+    fn from(state: StudyState) -> Self {
+        match state {
+            StudyState::Active => study::State::Active,
+            StudyState::Inactive => study::State::Inactive,
+            StudyState::Completed => study::State::Completed,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TrialsCommand {
+    /// List the trials of a study.
+    List(StudyArg),
+    /// Get a trial.
+    Get(TrialArg),
+    /// Suggest trials.
+    Suggest {
+        #[command(flatten)]
+        study: StudyArg,
+        /// Number of trials to suggest.
+        #[arg(long, default_value_t = 1)]
+        count: i32,
+        /// Id of the client the trials are suggested to.
+        #[arg(long, default_value = "vizier-cli")]
+        client_id: String,
+    },
+    /// Complete a trial with its final metrics or as infeasible.
+    Complete {
+        #[command(flatten)]
+        trial: TrialArg,
+        /// Final metric, as `metric_id=value` - can be repeated.
+        #[arg(
+            long = "metric",
+            value_name = "METRIC_ID=VALUE",
+            value_parser = parse_metric,
+            required_unless_present = "infeasible"
+        )]
+        metrics: Vec<measurement::Metric>,
+        /// Mark the trial as infeasible for this reason.
+        #[arg(long, conflicts_with = "metrics")]
+        infeasible: Option<String>,
+    },
+    /// Stop a trial.
+    Stop(TrialArg),
+    /// Delete a trial.
+    Delete(TrialArg),
+}
+
+#[derive(Args)]
+struct StudyArg {
+    /// Study id or full study name (owners/{owner}/studies/{study}).
+    study: String,
+}
+
+impl StudyArg {
+    fn study_name(&self, client: &Client) -> Result<StudyName, Box<dyn Error>> {
+        if self.study.contains('/') {
+            Ok(StudyName::from_str(&self.study)?)
+        } else {
+            Ok(client.study_name(self.study.clone()))
+        }
+    }
+}
+
+#[derive(Args)]
+struct TrialArg {
+    #[command(flatten)]
+    study: StudyArg,
+    /// Trial id.
+    trial: String,
+}
+
+impl TrialArg {
+    fn trial_name(&self, client: &Client) -> Result<TrialName, Box<dyn Error>> {
+        let study_name = self.study.study_name(client)?;
+        Ok(client.trial_name_from_study(&study_name, self.trial.clone()))
+    }
+}
+
+fn parse_metric(s: &str) -> Result<measurement::Metric, String> {
+    let (metric_id, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected metric_id=value, got {s}"))?;
+    let value = value
+        .parse()
+        .map_err(|e| format!("invalid value of metric {metric_id}: {e}"))?;
+
+    Ok(measurement::Metric {
+        metric_id: metric_id.to_string(),
+        value,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let mut builder = VizierClient::builder(cli.endpoint, cli.owner.clone());
+    if let Some(token) = cli.token {
+        builder = builder.with_bearer_token(token);
+    }
+    let mut client = builder.connect().await?;
+    let output = cli.output;
+
+    match cli.command {
+        Command::Studies(command) => match command {
+            StudiesCommand::List => {
+                let studies = collect_all(client.list_studies_stream(0)).await?;
+                print_studies(output, &studies)?;
+            }
+            StudiesCommand::Get(study) => {
+                let request = client.mk_get_study_request(study.study_name(&client)?);
+                let study = client.get_study(request).await?;
+                print_studies(output, &[study])?;
+            }
+            StudiesCommand::Create { from_file } => {
+                let config = StudyConfig::from_path(from_file)?;
+                let study = client
+                    .create_study(config.create_study_request(cli.owner)?)
+                    .await?;
+                print_studies(output, &[study])?;
+            }
+            StudiesCommand::Delete(study) => {
+                let study_name = study.study_name(&client)?;
+                client
+                    .delete_study(client.mk_delete_study_request(study_name.clone()))
+                    .await?;
+                eprintln!("deleted {study_name}");
+            }
+            StudiesCommand::SetState { study, state } => {
+                let request =
+                    client.mk_set_study_state_request(study.study_name(&client)?, state.into());
+                let study = client.set_study_state(request).await?;
+                print_studies(output, &[study])?;
+            }
+        },
+        Command::Trials(command) => match command {
+            TrialsCommand::List(study) => {
+                let trials =
+                    collect_all(client.list_trials_stream(study.study_name(&client)?, 0)).await?;
+                print_trials(output, &trials)?;
+            }
+            TrialsCommand::Get(trial) => {
+                let request = client.mk_get_trial_request(trial.trial_name(&client)?);
+                let trial = client.get_trial(request).await?;
+                print_trials(output, &[trial])?;
+            }
+            TrialsCommand::Suggest {
+                study,
+                count,
+                client_id,
+            } => {
+                let request =
+                    client.mk_suggest_trials_request(study.study_name(&client)?, count, client_id);
+                let response = client.suggest_trials(request).await?;
+                print_trials(output, &response.trials)?;
+            }
+            TrialsCommand::Complete {
+                trial,
+                metrics,
+                infeasible,
+            } => {
+                let final_measurement = match infeasible {
+                    Some(reason) => FinalMeasurementOrReason::Reason(reason),
+                    None => FinalMeasurementOrReason::FinalMeasurement(Measurement {
+                        elapsed_duration: None,
+                        step_count: 0,
+                        metrics,
+                    }),
+                };
+                let request =
+                    client.mk_complete_trial_request(trial.trial_name(&client)?, final_measurement);
+                let trial = client.complete_trial(request).await?;
+                print_trials(output, &[trial])?;
+            }
+            TrialsCommand::Stop(trial) => {
+                let request = client.mk_stop_trial_request(trial.trial_name(&client)?);
+                let trial = client.stop_trial(request).await?;
+                print_trials(output, &[trial])?;
+            }
+            TrialsCommand::Delete(trial) => {
+                let trial_name = trial.trial_name(&client)?;
+                client
+                    .delete_trial(client.mk_delete_trial_request(trial_name.clone()))
+                    .await?;
+                eprintln!("deleted {trial_name}");
+            }
+        },
+        Command::Optimal(study) => {
+            let trials =
+                collect_all(client.list_optimal_trials_stream(study.study_name(&client)?, 0))
+                    .await?;
+            print_trials(output, &trials)?;
+        }
+        Command::Export { study, file } => {
+            let snapshot = client.export_study(study.study_name(&client)?).await?;
+            match file {
+                Some(path) => snapshot.save(path)?,
+                None => snapshot.to_writer(std::io::stdout().lock())?,
+            }
+        }
+        #[cfg(feature = "tui")]
+        Command::Dashboard { study, interval } => {
+            let study_name = study.study_name(&client)?;
+            dashboard::run(client, study_name, std::time::Duration::from_secs(interval)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer_pretty(BufWriter::new(std::io::stdout().lock()), value)?;
+    println!();
+    Ok(())
+}
+
+fn print_studies(output: Output, studies: &[Study]) -> Result<(), Box<dyn Error>> {
+    if output == Output::Json {
+        return print_json(&studies);
+    }
+
+    let rows: Vec<Vec<String>> = studies
+        .iter()
+        .map(|s| {
+            vec![
+                s.name.clone(),
+                s.display_name.clone(),
+                s.state().as_str_name().to_string(),
+                s.study_spec
+                    .as_ref()
+                    .map(|spec| spec.algorithm.clone())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+    print_table(&["NAME", "DISPLAY NAME", "STATE", "ALGORITHM"], &rows);
+
+    Ok(())
+}
+
+fn print_trials(output: Output, trials: &[Trial]) -> Result<(), Box<dyn Error>> {
+    if output == Output::Json {
+        return print_json(&trials);
+    }
+
+    // same columns as the CSV export, without the timestamps
+    let table = Table::trials(trials);
+    let columns: Vec<_> = table
+        .columns()
+        .iter()
+        .filter(|c| !c.name.ends_with("_time"))
+        .collect();
+    let headers: Vec<String> = columns.iter().map(|c| c.name.to_uppercase()).collect();
+    let rows: Vec<Vec<String>> = (0..table.num_rows())
+        .map(|row| columns.iter().map(|c| c.values.format(row)).collect())
+        .collect();
+    print_table(&headers, &rows);
+
+    Ok(())
+}
+
+/// Prints rows as left-aligned columns.
+fn print_table(headers: &[impl AsRef<str>], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.as_ref().len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+
+    line(headers.iter().map(|h| h.as_ref()).collect());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oss_vizier::builder::ClientBuilder;
+
+    use super::{StudyArg, parse_metric};
+
+    #[test]
+    fn it_parses_metrics() {
+        let metric = parse_metric("accuracy=0.75").unwrap();
+        assert_eq!(metric.metric_id, "accuracy");
+        assert_eq!(metric.value, 0.75);
+
+        assert_eq!(
+            parse_metric("accuracy").unwrap_err(),
+            "expected metric_id=value, got accuracy"
+        );
+        assert!(
+            parse_metric("accuracy=high")
+                .unwrap_err()
+                .starts_with("invalid value of metric accuracy")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_resolves_study_names() {
+        let client = ClientBuilder::new("http://localhost:28080", "owner")
+            .connect_lazy()
+            .unwrap();
+
+        let study = StudyArg {
+            study: "42".to_string(),
+        };
+        assert_eq!(
+            study.study_name(&client).unwrap().to_string(),
+            "owners/owner/studies/42"
+        );
+
+        let study = StudyArg {
+            study: "owners/other/studies/7".to_string(),
+        };
+        assert_eq!(
+            study.study_name(&client).unwrap().to_string(),
+            "owners/other/studies/7"
+        );
+
+        let study = StudyArg {
+            study: "owners/other/7".to_string(),
+        };
+        assert!(study.study_name(&client).is_err());
+    }
+}
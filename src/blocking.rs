@@ -0,0 +1,463 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blocking client - for synchronous code that does not run a tokio runtime.
+//!
+//! [VizierClient] mirrors the async [crate::VizierClient] and runs its requests on an
+//! internal runtime with one worker thread - which keeps driving the connection
+//! between requests. It must not be used from within an async runtime.
+//!
+//! ```no_run
+//! # use oss_vizier::blocking::VizierClient;
+//! # use oss_vizier::model::study::StudyName;
+//! # fn example(study_name: StudyName) -> Result<(), oss_vizier::Error> {
+//! let mut client = VizierClient::builder("http://localhost:28080", "owner")
+//!     .connect_blocking()?;
+//!
+//! let request = client.mk_suggest_trials_request(study_name.clone(), 1, "worker".to_string());
+//! let trials = client.suggest_trials(request)?.trials;
+//!
+//! // or wait for the suggestion operation later
+//! let request = client.mk_suggest_trials_request(study_name, 1, "worker".to_string());
+//! let operation = client.suggest_trials_operation(request)?;
+//! let trials = operation.wait()?.trials;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use tokio::runtime::Runtime;
+
+use crate::builder::{ClientBuilder, InterceptedChannel};
+use crate::google::longrunning::{Operation, operation};
+use crate::model::InvalidName;
+use crate::model::operation::OperationName;
+use crate::model::study;
+use crate::model::trial::{self, complete::FinalMeasurementOrReason, optimal};
+use crate::poll::PollPolicy;
+use crate::retry::{RetryCounts, RetryPolicy};
+use crate::vizier::{
+    AddTrialMeasurementRequest, CheckTrialEarlyStoppingStateRequest,
+    CheckTrialEarlyStoppingStateResponse, CompleteTrialRequest, CreateStudyRequest,
+    CreateTrialRequest, DeleteStudyRequest, DeleteTrialRequest, GetStudyRequest, GetTrialRequest,
+    ListOptimalTrialsRequest, ListOptimalTrialsResponse, ListStudiesRequest, ListStudiesResponse,
+    ListTrialsRequest, ListTrialsResponse, Measurement, SetStudyStateRequest, StopTrialRequest,
+    Study, StudySpec, SuggestTrialsRequest, SuggestTrialsResponse, Trial,
+};
+use crate::{Error, StudyName, TrialName, util};
+
+/// Blocks on the async method `$method` of the inner client.
+macro_rules! blocking {
+    ($(#[$doc:meta])* $method:ident($($arg:ident: $ty:ty),*) -> $ret:ty) => {
+        $(#[$doc])*
+        pub fn $method(&mut self, $($arg: $ty),*) -> Result<$ret, Error> {
+            self.runtime.block_on(self.inner.$method($($arg),*))
+        }
+    };
+}
+
+/// Forwards to the method `$method` of the inner client.
+macro_rules! forward {
+    ($(#[$doc:meta])* $method:ident($($arg:ident: $ty:ty),*) -> $ret:ty) => {
+        $(#[$doc])*
+        pub fn $method(&self, $($arg: $ty),*) -> $ret {
+            self.inner.$method($($arg),*)
+        }
+    };
+}
+
+/// Blocking Vizier client.
+#[derive(Clone)]
+pub struct VizierClient {
+    inner: crate::VizierClient<InterceptedChannel>,
+    runtime: Arc<Runtime>,
+}
+
+impl ClientBuilder {
+    /// Connects to the service and builds a blocking [VizierClient].
+    pub fn connect_blocking(self) -> Result<VizierClient, Error> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .map_err(Error::Runtime)?;
+        let inner = runtime.block_on(self.connect())?;
+
+        Ok(VizierClient {
+            inner,
+            runtime: Arc::new(runtime),
+        })
+    }
+}
+
+impl VizierClient {
+    /// Creates a [ClientBuilder] to configure the connection to the service at
+    /// `endpoint` for the studies of `owner` - see [ClientBuilder::connect_blocking].
+    pub fn builder(endpoint: impl Into<String>, owner: impl Into<String>) -> ClientBuilder {
+        ClientBuilder::new(endpoint, owner)
+    }
+
+    /// Sets the [RetryPolicy] of the RPC methods of the client - see [crate::retry].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry_policy(retry_policy);
+        self
+    }
+
+    /// Sets the [PollPolicy] used to wait for long-running operations.
+    pub fn with_poll_policy(mut self, poll_policy: PollPolicy) -> Self {
+        self.inner = self.inner.with_poll_policy(poll_policy);
+        self
+    }
+
+    forward!(
+        /// The counters of RPCs sent by the RPC methods of this client and its clones.
+        retry_counts() -> RetryCounts
+    );
+
+    forward!(
+        /// Creates a new [CreateStudyRequest] builder.
+        mk_study_request_builder() -> study::create::RequestBuilder
+    );
+
+    forward!(
+        /// Creates a new [GetStudyRequest].
+        mk_get_study_request(study_name: StudyName) -> GetStudyRequest
+    );
+
+    forward!(
+        /// Creates a new [DeleteStudyRequest].
+        mk_delete_study_request(study_name: StudyName) -> DeleteStudyRequest
+    );
+
+    forward!(
+        /// Creates a new [SetStudyStateRequest].
+        mk_set_study_state_request(
+            study_name: StudyName,
+            state: crate::vizier::study::State
+        ) -> SetStudyStateRequest
+    );
+
+    forward!(
+        /// Creates a new [ListStudiesRequest] builder.
+        mk_list_studies_request_builder() -> study::list::RequestBuilder
+    );
+
+    forward!(
+        /// Creates a new [GetTrialRequest].
+        mk_get_trial_request(trial_name: TrialName) -> GetTrialRequest
+    );
+
+    forward!(
+        /// Creates a new [SuggestTrialsRequest].
+        mk_suggest_trials_request(
+            study_name: StudyName,
+            suggestion_count: i32,
+            client_id: String
+        ) -> SuggestTrialsRequest
+    );
+
+    forward!(
+        /// Creates a new [CreateTrialRequest].
+        mk_create_trial_request(study_name: StudyName, trial: Trial) -> CreateTrialRequest
+    );
+
+    forward!(
+        /// Creates a new [DeleteTrialRequest].
+        mk_delete_trial_request(trial_name: TrialName) -> DeleteTrialRequest
+    );
+
+    forward!(
+        /// Creates a new [ListTrialsRequest] builder.
+        mk_list_trials_request_builder(study_name: StudyName) -> trial::list::RequestBuilder
+    );
+
+    forward!(
+        /// Creates a new [AddTrialMeasurementRequest].
+        mk_add_trial_measurement_request(
+            trial_name: TrialName,
+            measurement: Measurement
+        ) -> AddTrialMeasurementRequest
+    );
+
+    forward!(
+        /// Creates a new [CompleteTrialRequest].
+        mk_complete_trial_request(
+            trial_name: TrialName,
+            final_measurement: FinalMeasurementOrReason
+        ) -> CompleteTrialRequest
+    );
+
+    forward!(
+        /// Creates a new [CheckTrialEarlyStoppingStateRequest].
+        mk_check_trial_early_stopping_state_request(
+            trial_name: TrialName
+        ) -> CheckTrialEarlyStoppingStateRequest
+    );
+
+    forward!(
+        /// Creates a new [StopTrialRequest].
+        mk_stop_trial_request(trial_name: TrialName) -> StopTrialRequest
+    );
+
+    forward!(
+        /// Creates a new [ListOptimalTrialsRequest].
+        mk_list_optimal_trials_request(study_name: StudyName) -> ListOptimalTrialsRequest
+    );
+
+    forward!(
+        /// Creates a new [ListOptimalTrialsRequest] builder.
+        mk_list_optimal_trials_request_builder(study_name: StudyName) -> optimal::RequestBuilder
+    );
+
+    forward!(
+        /// Creates a [TrialName] from the study and trial numbers.
+        trial_name(study: String, trial: String) -> TrialName
+    );
+
+    /// Creates a [TrialName] from a [StudyName] and trial number.
+    pub fn trial_name_from_study(
+        &self,
+        study_name: &StudyName,
+        trial: impl Into<String>,
+    ) -> TrialName {
+        self.inner.trial_name_from_study(study_name, trial)
+    }
+
+    /// Creates a [StudyName] from the study number.
+    pub fn study_name(&self, study: impl Into<String>) -> StudyName {
+        self.inner.study_name(study)
+    }
+
+    blocking!(
+        /// Waits for an operation according to the client [PollPolicy].
+        wait_for_operation(retries: usize, operation: Operation) -> Option<operation::Result>
+    );
+
+    blocking!(
+        /// Gets the result of an operation, if it is done.
+        get_operation(operation_name: String) -> Option<operation::Result>
+    );
+
+    blocking!(
+        /// Suggests trials to a study.
+        ///
+        /// Waits for the suggestion operation according to the client [PollPolicy].
+        suggest_trials(request: SuggestTrialsRequest) -> SuggestTrialsResponse
+    );
+
+    /// Starts suggesting trials to a study and returns a handle on the suggestion
+    /// operation.
+    pub fn suggest_trials_operation(
+        &mut self,
+        request: SuggestTrialsRequest,
+    ) -> Result<OperationHandle<SuggestTrialsResponse>, Error> {
+        let inner = self
+            .runtime
+            .block_on(self.inner.suggest_trials_operation(request))?;
+
+        Ok(OperationHandle {
+            inner,
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    blocking!(
+        /// Creates a study - or returns the existing one with the same display name.
+        create_study(request: CreateStudyRequest) -> Study
+    );
+
+    /// Finds the study of the owner with the given display name, scanning all the pages
+    /// of `ListStudies`.
+    pub fn find_study_by_display_name(&self, display_name: &str) -> Result<Option<Study>, Error> {
+        self.runtime
+            .block_on(self.inner.find_study_by_display_name(display_name))
+    }
+
+    blocking!(
+        /// Gets the study of the owner with the given display name or creates it - see
+        /// [crate::VizierClient::get_or_create_study].
+        get_or_create_study(display_name: String, study_spec: StudySpec) -> Study
+    );
+
+    blocking!(
+        /// Gets a study.
+        get_study(request: GetStudyRequest) -> Study
+    );
+
+    blocking!(
+        /// Lists a page of studies.
+        list_studies(request: ListStudiesRequest) -> ListStudiesResponse
+    );
+
+    blocking!(
+        /// Deletes a study.
+        delete_study(request: DeleteStudyRequest) -> ()
+    );
+
+    blocking!(
+        /// Sets the state of a study - e.g. to pause or resume it.
+        set_study_state(request: SetStudyStateRequest) -> Study
+    );
+
+    blocking!(
+        /// Creates a trial. Only retried if the [RetryPolicy] allows non-idempotent
+        /// retries.
+        create_trial(request: CreateTrialRequest) -> Trial
+    );
+
+    blocking!(
+        /// Gets a trial.
+        get_trial(request: GetTrialRequest) -> Trial
+    );
+
+    blocking!(
+        /// Lists a page of trials.
+        list_trials(request: ListTrialsRequest) -> ListTrialsResponse
+    );
+
+    blocking!(
+        /// Adds a measurement to a trial.
+        add_trial_measurement(request: AddTrialMeasurementRequest) -> Trial
+    );
+
+    blocking!(
+        /// Completes a trial.
+        complete_trial(request: CompleteTrialRequest) -> Trial
+    );
+
+    blocking!(
+        /// Deletes a trial.
+        delete_trial(request: DeleteTrialRequest) -> ()
+    );
+
+    blocking!(
+        /// Checks whether a trial should stop early.
+        check_trial_early_stopping_state(
+            request: CheckTrialEarlyStoppingStateRequest
+        ) -> CheckTrialEarlyStoppingStateResponse
+    );
+
+    blocking!(
+        /// Stops a trial.
+        stop_trial(request: StopTrialRequest) -> Trial
+    );
+
+    blocking!(
+        /// Lists a page of the optimal trials of a study.
+        list_optimal_trials(request: ListOptimalTrialsRequest) -> ListOptimalTrialsResponse
+    );
+
+    /// Iterates over all the studies of the owner, fetching pages of `page_size`
+    /// studies - 0 for the service default.
+    pub fn list_studies_iter(
+        &self,
+        page_size: i32,
+    ) -> impl Iterator<Item = Result<Study, Error>> + '_ {
+        let mut stream = Box::pin(self.inner.list_studies_stream(page_size));
+        std::iter::from_fn(move || self.runtime.block_on(stream.next()))
+    }
+
+    /// Iterates over all the trials of a study, fetching pages of `page_size` trials
+    /// - 0 for the service default.
+    pub fn list_trials_iter(
+        &self,
+        study_name: StudyName,
+        page_size: i32,
+    ) -> impl Iterator<Item = Result<Trial, Error>> + '_ {
+        let mut stream = Box::pin(self.inner.list_trials_stream(study_name, page_size));
+        std::iter::from_fn(move || self.runtime.block_on(stream.next()))
+    }
+
+    /// Iterates over all the optimal trials of a study, fetching pages of `page_size`
+    /// trials - 0 for the service default.
+    pub fn list_optimal_trials_iter(
+        &self,
+        study_name: StudyName,
+        page_size: i32,
+    ) -> impl Iterator<Item = Result<Trial, Error>> + '_ {
+        let mut stream = Box::pin(self.inner.list_optimal_trials_stream(study_name, page_size));
+        std::iter::from_fn(move || self.runtime.block_on(stream.next()))
+    }
+
+    /// Exports a study and all its trials.
+    #[cfg(feature = "serde")]
+    pub fn export_study(
+        &mut self,
+        study_name: StudyName,
+    ) -> Result<crate::snapshot::StudySnapshot, Error> {
+        self.runtime.block_on(self.inner.export_study(study_name))
+    }
+
+    /// Imports a study exported by [VizierClient::export_study] - see
+    /// [crate::VizierClient::import_study].
+    #[cfg(feature = "serde")]
+    pub fn import_study(
+        &mut self,
+        snapshot: &crate::snapshot::StudySnapshot,
+        display_name: Option<String>,
+    ) -> Result<crate::snapshot::ImportReport, Error> {
+        self.runtime
+            .block_on(self.inner.import_study(snapshot, display_name))
+    }
+}
+
+/// Blocking handle on a long-running operation whose response is a `M` - see
+/// [crate::model::operation::OperationHandle].
+pub struct OperationHandle<M> {
+    inner: crate::model::operation::OperationHandle<InterceptedChannel, M>,
+    runtime: Arc<Runtime>,
+}
+
+impl<M> OperationHandle<M>
+where
+    M: prost::Message + prost::Name + Default,
+{
+    /// The name of the operation.
+    pub fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// The name of the operation, parsed as an [OperationName].
+    pub fn operation_name(&self) -> Result<OperationName, InvalidName> {
+        self.inner.operation_name()
+    }
+
+    /// The last known state of the operation.
+    pub fn operation(&self) -> &Operation {
+        self.inner.operation()
+    }
+
+    /// Whether the operation was done the last time it was polled.
+    pub fn is_done(&self) -> bool {
+        self.inner.is_done()
+    }
+
+    /// The response of the operation if it was done the last time it was polled.
+    pub fn result(&self) -> Option<Result<M, util::Error>> {
+        self.inner.result()
+    }
+
+    /// Polls the operation once and returns its response if it is done.
+    pub fn poll(&mut self) -> Result<Option<M>, Error> {
+        self.runtime.block_on(self.inner.poll())
+    }
+
+    /// Waits for the operation to be done, polling it according to the client
+    /// [PollPolicy], and returns its response.
+    pub fn wait(self) -> Result<M, Error> {
+        self.runtime.block_on(self.inner.wait())
+    }
+}
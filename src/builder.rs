@@ -0,0 +1,358 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [VizierClient] builder.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use oss_vizier::VizierClient;
+//! # async fn example() -> Result<(), oss_vizier::Error> {
+//! let client = VizierClient::builder("https://vizier.example.com", "owner")
+//!     .with_connect_timeout(Duration::from_secs(5))
+//!     .with_timeout(Duration::from_secs(30))
+//!     .with_bearer_token("secret")
+//!     .with_header("x-team", "ml")?
+//!     .connect()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue};
+use tonic::service::Interceptor;
+use tonic::service::interceptor::InterceptedService;
+#[cfg(feature = "tls")]
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+use tonic::transport::{Channel, Endpoint};
+
+use crate::poll::PollPolicy;
+use crate::retry::RetryPolicy;
+use crate::vizier::vizier_service_client::VizierServiceClient;
+use crate::{Error, VizierClient};
+
+/// A [Channel] with a [MetadataInterceptor].
+pub type InterceptedChannel = InterceptedService<Channel, MetadataInterceptor>;
+
+/// Source of bearer tokens, called for every request.
+pub type TokenSource = Arc<dyn Fn() -> Result<String, tonic::Status> + Send + Sync>;
+
+/// [Interceptor] adding an `authorization` bearer token and custom headers to the
+/// metadata of every request.
+#[derive(Clone, Default)]
+pub struct MetadataInterceptor {
+    headers: Vec<(AsciiMetadataKey, AsciiMetadataValue)>,
+    token_source: Option<TokenSource>,
+}
+
+impl MetadataInterceptor {
+    /// Creates a new [MetadataInterceptor] adding nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header to every request.
+    pub fn with_header(mut self, key: AsciiMetadataKey, value: AsciiMetadataValue) -> Self {
+        self.headers.push((key, value));
+        self
+    }
+
+    /// Adds an `authorization: Bearer <token>` header with a token from
+    /// `token_source` to every request.
+    pub fn with_token_source(mut self, token_source: TokenSource) -> Self {
+        self.token_source = Some(token_source);
+        self
+    }
+}
+
+impl Interceptor for MetadataInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        let metadata = request.metadata_mut();
+
+        for (key, value) in &self.headers {
+            metadata.insert(key.clone(), value.clone());
+        }
+
+        if let Some(token_source) = &self.token_source {
+            let token = token_source()?;
+            let value = AsciiMetadataValue::from_str(&format!("Bearer {token}"))
+                .map_err(|_| tonic::Status::unauthenticated("invalid bearer token"))?;
+            metadata.insert("authorization", value);
+        }
+
+        Ok(request)
+    }
+}
+
+/// [VizierClient] builder.
+pub struct ClientBuilder {
+    endpoint: String,
+    owner: String,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    keep_alive_while_idle: bool,
+    user_agent: Option<String>,
+    #[cfg(feature = "tls")]
+    tls_config: Option<ClientTlsConfig>,
+    interceptor: MetadataInterceptor,
+    poll_policy: Option<PollPolicy>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl ClientBuilder {
+    /// Creates a new [ClientBuilder] for the service at `endpoint` and the studies of
+    /// `owner`.
+    pub fn new(endpoint: impl Into<String>, owner: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            owner: owner.into(),
+            connect_timeout: None,
+            timeout: None,
+            keep_alive_interval: None,
+            keep_alive_timeout: None,
+            keep_alive_while_idle: false,
+            user_agent: None,
+            #[cfg(feature = "tls")]
+            tls_config: None,
+            interceptor: MetadataInterceptor::new(),
+            poll_policy: None,
+            retry_policy: None,
+        }
+    }
+
+    /// Sets the timeout to establish the connection.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sets the timeout of each request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval of HTTP/2 keepalive pings.
+    pub fn with_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets the timeout of HTTP/2 keepalive pings.
+    pub fn with_keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether HTTP/2 keepalive pings are sent while the connection is idle.
+    pub fn with_keep_alive_while_idle(mut self, enabled: bool) -> Self {
+        self.keep_alive_while_idle = enabled;
+        self
+    }
+
+    /// Sets the `user-agent` header.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sets the TLS configuration.
+    #[cfg(feature = "tls")]
+    pub fn with_tls_config(mut self, tls_config: ClientTlsConfig) -> Self {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// Uses TLS, trusting the CA certificate `ca_pem` in addition to the system roots.
+    #[cfg(feature = "tls")]
+    pub fn with_ca_certificate(mut self, ca_pem: impl AsRef<[u8]>) -> Self {
+        let tls_config = self.tls_config.take().unwrap_or_else(default_tls_config);
+        self.tls_config = Some(tls_config.ca_certificate(Certificate::from_pem(ca_pem)));
+        self
+    }
+
+    /// Uses TLS, authenticating with the client certificate `cert_pem` and its key
+    /// `key_pem`.
+    #[cfg(feature = "tls")]
+    pub fn with_client_identity(
+        mut self,
+        cert_pem: impl AsRef<[u8]>,
+        key_pem: impl AsRef<[u8]>,
+    ) -> Self {
+        let tls_config = self.tls_config.take().unwrap_or_else(default_tls_config);
+        self.tls_config = Some(tls_config.identity(Identity::from_pem(cert_pem, key_pem)));
+        self
+    }
+
+    /// Adds the header `key: value` to every request.
+    ///
+    /// The value is left out of the error if it is rejected, as it may be a secret.
+    pub fn with_header(mut self, key: &str, value: &str) -> Result<Self, Error> {
+        let key = AsciiMetadataKey::from_str(key)
+            .map_err(|_| Error::InvalidMetadata(format!("key {key:?}")))?;
+        let value = AsciiMetadataValue::from_str(value).map_err(|_| {
+            Error::InvalidMetadata(format!("value of header {key} is not printable ASCII"))
+        })?;
+
+        self.interceptor = self.interceptor.with_header(key, value);
+        Ok(self)
+    }
+
+    /// Adds an `authorization: Bearer <token>` header to every request.
+    pub fn with_bearer_token(self, token: impl Into<String>) -> Self {
+        let token = token.into();
+        self.with_token_source(Arc::new(move || Ok(token.clone())))
+    }
+
+    /// Adds an `authorization: Bearer <token>` header to every request, with a token
+    /// from `token_source` - for tokens that need to be refreshed.
+    pub fn with_token_source(mut self, token_source: TokenSource) -> Self {
+        self.interceptor = self.interceptor.with_token_source(token_source);
+        self
+    }
+
+    /// Sets the [PollPolicy] of the client.
+    pub fn with_poll_policy(mut self, poll_policy: PollPolicy) -> Self {
+        self.poll_policy = Some(poll_policy);
+        self
+    }
+
+    /// Sets the [RetryPolicy] of the client.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Builds the [Endpoint].
+    pub fn endpoint(&self) -> Result<Endpoint, Error> {
+        let mut endpoint = Endpoint::from_shared(self.endpoint.clone())?
+            .keep_alive_while_idle(self.keep_alive_while_idle);
+
+        if let Some(connect_timeout) = self.connect_timeout {
+            endpoint = endpoint.connect_timeout(connect_timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(interval) = self.keep_alive_interval {
+            endpoint = endpoint.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.keep_alive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            endpoint = endpoint.user_agent(user_agent.clone())?;
+        }
+        #[cfg(feature = "tls")]
+        if let Some(tls_config) = &self.tls_config {
+            endpoint = endpoint.tls_config(tls_config.clone())?;
+        }
+
+        Ok(endpoint)
+    }
+
+    /// Connects to the service and builds the [VizierClient].
+    pub async fn connect(self) -> Result<VizierClient<InterceptedChannel>, Error> {
+        let channel = self.endpoint()?.connect().await?;
+        Ok(self.build(channel))
+    }
+
+    /// Builds the [VizierClient] without connecting - the connection is established on
+    /// the first request.
+    pub fn connect_lazy(self) -> Result<VizierClient<InterceptedChannel>, Error> {
+        let channel = self.endpoint()?.connect_lazy();
+        Ok(self.build(channel))
+    }
+
+    fn build(self, channel: Channel) -> VizierClient<InterceptedChannel> {
+        let service = VizierServiceClient::with_interceptor(channel, self.interceptor);
+
+        let mut client = VizierClient::new(self.owner, service);
+        if let Some(poll_policy) = self.poll_policy {
+            client = client.with_poll_policy(poll_policy);
+        }
+        if let Some(retry_policy) = self.retry_policy {
+            client = client.with_retry_policy(retry_policy);
+        }
+
+        client
+    }
+}
+
+#[cfg(feature = "tls")]
+fn default_tls_config() -> ClientTlsConfig {
+    ClientTlsConfig::new().with_native_roots()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tonic::service::Interceptor;
+
+    use super::{ClientBuilder, MetadataInterceptor};
+    use crate::Error;
+
+    #[test]
+    fn it_adds_metadata() {
+        let mut interceptor = MetadataInterceptor::new()
+            .with_header("x-team".parse().unwrap(), "ml".parse().unwrap())
+            .with_token_source(Arc::new(|| Ok("secret".to_string())));
+
+        let request = interceptor.call(tonic::Request::new(())).unwrap();
+
+        assert_eq!(request.metadata().get("x-team").unwrap(), "ml");
+        assert_eq!(
+            request.metadata().get("authorization").unwrap(),
+            "Bearer secret"
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_headers() {
+        let builder = || ClientBuilder::new("http://localhost:28080", "owner");
+
+        assert!(matches!(
+            builder().with_header("invalid key", "value"),
+            Err(Error::InvalidMetadata(m)) if m == "key \"invalid key\""
+        ));
+        assert!(matches!(
+            builder().with_header("authorization", "secret\n"),
+            Err(Error::InvalidMetadata(m))
+                if m == "value of header authorization is not printable ASCII"
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_builds_a_lazy_client() {
+        let client = ClientBuilder::new("http://localhost:28080", "owner")
+            .with_user_agent("oss-vizier-test")
+            .with_bearer_token("secret")
+            .connect_lazy()
+            .unwrap();
+
+        assert_eq!(
+            String::from(client.study_name("s")),
+            "owners/owner/studies/s"
+        );
+    }
+}
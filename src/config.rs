@@ -0,0 +1,1228 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative study configuration files - YAML, TOML or JSON.
+//!
+//! ```yaml
+//! display_name: my_sweep
+//! algorithm: RANDOM_SEARCH
+//! observation_noise: low
+//! metrics:
+//!   - id: loss
+//!     goal: minimize
+//!   - id: latency
+//!     goal: minimize
+//!     safety:
+//!       threshold: 100.0
+//!       desired_min_safe_trials_fraction: 0.8
+//! parameters:
+//!   - id: learning_rate
+//!     type: double
+//!     min: 0.0001
+//!     max: 0.1
+//!     scale: log
+//!   - id: optimizer
+//!     type: categorical
+//!     values: [adam, sgd]
+//!     children:
+//!       - when: [sgd]
+//!         parameters:
+//!           - id: momentum
+//!             type: discrete
+//!             values: [0.0, 0.9, 0.99]
+//! automated_stopping: default
+//! metadata:
+//!   - key: owner
+//!     value: ml-team
+//! ```
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::config::StudyConfig;
+//! # async fn example(mut client: VizierClient<InterceptedChannel>) -> Result<(), Box<dyn std::error::Error>> {
+//! let config = StudyConfig::from_path("study.yaml")?;
+//! let request = config.create_study_request("owner")?;
+//! let study = client.create_study(request).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::study::create::RequestBuilder;
+use crate::study::spec::StudySpecBuilder;
+use crate::vizier::study_spec::metric_spec::{GoalType, SafetyMetricConfig};
+use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::{
+    CategoricalValueCondition, DiscreteValueCondition, IntValueCondition, ParentValueCondition,
+};
+use crate::vizier::study_spec::parameter_spec::{
+    CategoricalValueSpec, ConditionalParameterSpec, DiscreteValueSpec, DoubleValueSpec,
+    IntegerValueSpec, ParameterValueSpec, ScaleType,
+};
+use crate::vizier::study_spec::{
+    AutomatedStoppingSpec, DefaultEarlyStoppingSpec, MetricSpec, ObservationNoise, ParameterSpec,
+};
+use crate::vizier::{CreateStudyRequest, KeyValue, StudySpec, key_value};
+
+/// Where an error is in a configuration file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    /// The file, if the configuration was loaded from one.
+    pub file: Option<PathBuf>,
+    /// The line, starting at 1, if known.
+    pub line: Option<usize>,
+    /// The column, starting at 1, if known.
+    pub column: Option<usize>,
+    /// The path of the invalid field, e.g. `parameters[1].max`, if known.
+    pub path: Option<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}", file.display())?,
+            None => write!(f, "<config>")?,
+        }
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        if let Some(path) = &self.path {
+            write!(f, ": {path}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when loading a [StudyConfig].
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The file could not be read.
+    #[error("{}: {source}", file.display())]
+    Io {
+        /// The file.
+        file: PathBuf,
+        /// The I/O error.
+        source: std::io::Error,
+    },
+    /// The format could not be inferred from the file extension.
+    #[error("{}: unknown format - expected .yaml, .yml, .toml or .json", .0.display())]
+    UnknownFormat(PathBuf),
+    /// The file is not a valid configuration.
+    #[error("{location}: {message}")]
+    Parse {
+        /// Where the error is.
+        location: Location,
+        /// What is wrong.
+        message: String,
+    },
+    /// The configuration does not describe a valid study.
+    #[error("{location}: {message}")]
+    Invalid {
+        /// Where the error is.
+        location: Location,
+        /// What is wrong.
+        message: String,
+    },
+}
+
+/// Format of a configuration file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// YAML.
+    Yaml,
+    /// TOML.
+    Toml,
+    /// JSON.
+    Json,
+}
+
+impl Format {
+    /// Infers the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Declarative description of a study.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StudyConfig {
+    /// Display name of the study.
+    pub display_name: String,
+    /// Algorithm - `ALGORITHM_UNSPECIFIED` by default.
+    #[serde(default = "default_algorithm")]
+    pub algorithm: String,
+    /// Observation noise of the metrics.
+    #[serde(default)]
+    pub observation_noise: NoiseConfig,
+    /// Metrics to optimize.
+    pub metrics: Vec<MetricConfig>,
+    /// Parameters of the search space.
+    pub parameters: Vec<ParameterConfig>,
+    /// Automated stopping of the trials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub automated_stopping: Option<StoppingConfig>,
+    /// Metadata of the study.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub metadata: Vec<MetadataConfig>,
+}
+
+fn default_algorithm() -> String {
+    "ALGORITHM_UNSPECIFIED".to_string()
+}
+
+/// Observation noise - see [ObservationNoise].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseConfig {
+    /// Unspecified.
+    #[default]
+    Unspecified,
+    /// Low noise.
+    Low,
+    /// High noise.
+    High,
+}
+
+/// A metric to optimize.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricConfig {
+    /// Id of the metric.
+    pub id: String,
+    /// Whether to maximize or minimize the metric.
+    pub goal: GoalConfig,
+    /// Makes it a safety metric.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety: Option<SafetyConfig>,
+}
+
+/// Goal of a metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalConfig {
+    /// Maximize the metric.
+    Maximize,
+    /// Minimize the metric.
+    Minimize,
+}
+
+/// Safety configuration of a metric - see [SafetyMetricConfig].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SafetyConfig {
+    /// Threshold the metric must satisfy for a trial to be safe.
+    pub threshold: f64,
+    /// Desired minimum fraction of safe trials.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired_min_safe_trials_fraction: Option<f64>,
+}
+
+/// A parameter of the search space.
+///
+/// Keys that are not fields of the parameter or of its domain are rejected.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawParameterConfig")]
+pub struct ParameterConfig {
+    /// Id of the parameter.
+    pub id: String,
+    /// Domain of the parameter.
+    #[serde(flatten)]
+    pub domain: DomainConfig,
+    /// Scale of the parameter - for double and integer parameters.
+    #[serde(default)]
+    pub scale: ScaleConfig,
+    /// Parameters that only apply for some values of this one - not for double
+    /// parameters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ConditionalConfig>,
+}
+
+/// [ParameterConfig] as written - `deny_unknown_fields` does not work with the
+/// flattened domain, so the leftover keys are collected and checked instead.
+#[derive(Deserialize)]
+struct RawParameterConfig {
+    id: String,
+    #[serde(flatten)]
+    domain: DomainConfig,
+    #[serde(default)]
+    scale: ScaleConfig,
+    #[serde(default)]
+    children: Vec<ConditionalConfig>,
+    /// All the other keys - including the ones of the domain.
+    #[serde(flatten)]
+    others: BTreeMap<String, serde::de::IgnoredAny>,
+}
+
+impl TryFrom<RawParameterConfig> for ParameterConfig {
+    type Error = String;
+
+    fn try_from(raw: RawParameterConfig) -> Result<Self, Self::Error> {
+        let domain_fields: &[&str] = match raw.domain {
+            DomainConfig::Double { .. } | DomainConfig::Integer { .. } => {
+                &["type", "min", "max", "default"]
+            }
+            DomainConfig::Categorical { .. } | DomainConfig::Discrete { .. } => {
+                &["type", "values", "default"]
+            }
+        };
+
+        if let Some(key) = raw
+            .others
+            .keys()
+            .find(|k| !domain_fields.contains(&k.as_str()))
+        {
+            return Err(format!(
+                "unknown field `{key}` of parameter `{}`, expected one of `id`, `scale`, \
+                 `children`, {}",
+                raw.id,
+                domain_fields
+                    .iter()
+                    .map(|f| format!("`{f}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        Ok(ParameterConfig {
+            id: raw.id,
+            domain: raw.domain,
+            scale: raw.scale,
+            children: raw.children,
+        })
+    }
+}
+
+/// Domain of a parameter, tagged by its `type`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainConfig {
+    /// Double in `[min, max]`.
+    Double {
+        /// Lower bound.
+        min: f64,
+        /// Upper bound.
+        max: f64,
+        /// Default value.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<f64>,
+    },
+    /// Integer in `[min, max]`.
+    Integer {
+        /// Lower bound.
+        min: i64,
+        /// Upper bound.
+        max: i64,
+        /// Default value.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<i64>,
+    },
+    /// One of the `values`.
+    Categorical {
+        /// Feasible values.
+        values: Vec<String>,
+        /// Default value.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<String>,
+    },
+    /// One of the numerical `values`.
+    Discrete {
+        /// Feasible values.
+        values: Vec<f64>,
+        /// Default value.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<f64>,
+    },
+}
+
+/// Scale of a parameter - see [ScaleType].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleConfig {
+    /// Unspecified.
+    #[default]
+    Unspecified,
+    /// Linear scale.
+    Linear,
+    /// Log scale.
+    Log,
+    /// Reverse log scale.
+    ReverseLog,
+}
+
+/// Parameters that only apply when their parent takes one of the `when` values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConditionalConfig {
+    /// Values of the parent parameter.
+    pub when: Vec<ConditionValue>,
+    /// The conditional parameters.
+    pub parameters: Vec<ParameterConfig>,
+}
+
+/// A value of a parent parameter in a [ConditionalConfig].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConditionValue {
+    /// Value of an integer parameter.
+    Integer(i64),
+    /// Value of a discrete parameter.
+    Double(f64),
+    /// Value of a categorical parameter.
+    String(String),
+}
+
+/// Automated stopping of the trials.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoppingConfig {
+    /// The default early stopping of the service.
+    Default,
+}
+
+/// A metadata entry - see [KeyValue].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataConfig {
+    /// Key.
+    pub key: String,
+    /// Namespace - empty by default.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ns: String,
+    /// Value.
+    pub value: String,
+}
+
+impl StudyConfig {
+    /// Loads and validates a configuration file, inferring its format from its
+    /// extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        let format =
+            Format::from_path(path).ok_or_else(|| Error::UnknownFormat(path.to_path_buf()))?;
+        let content = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            file: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::load(&content, format, Some(path))
+    }
+
+    /// Parses and validates a configuration.
+    pub fn parse(content: &str, format: Format) -> Result<Self, Error> {
+        Self::load(content, format, None)
+    }
+
+    fn load(content: &str, format: Format, file: Option<&Path>) -> Result<Self, Error> {
+        let location = |line: Option<usize>, column: Option<usize>| Location {
+            file: file.map(Path::to_path_buf),
+            line,
+            column,
+            path: None,
+        };
+
+        let config: StudyConfig = match format {
+            Format::Yaml => serde_norway::from_str(content).map_err(|e| {
+                let (line, column) = e
+                    .location()
+                    .map(|l| (Some(l.line()), Some(l.column())))
+                    .unwrap_or_default();
+                Error::Parse {
+                    location: location(line, column),
+                    message: e.to_string(),
+                }
+            })?,
+            Format::Toml => toml::from_str(content).map_err(|e| {
+                let (line, column) = e
+                    .span()
+                    .map(|span| line_column(content, span.start))
+                    .map(|(line, column)| (Some(line), Some(column)))
+                    .unwrap_or_default();
+                Error::Parse {
+                    location: location(line, column),
+                    message: e.message().to_string(),
+                }
+            })?,
+            Format::Json => serde_json::from_str(content).map_err(|e| Error::Parse {
+                location: location(Some(e.line()), Some(e.column())),
+                message: e.to_string(),
+            })?,
+        };
+
+        config.validate().map_err(|e| match e {
+            Error::Invalid {
+                mut location,
+                message,
+            } => {
+                location.file = file.map(Path::to_path_buf);
+                if let Some((line, column)) = location
+                    .path
+                    .as_deref()
+                    .and_then(|path| locate(content, format, path))
+                {
+                    location.line = Some(line);
+                    location.column = Some(column);
+                }
+                Error::Invalid { location, message }
+            }
+            e => e,
+        })?;
+
+        Ok(config)
+    }
+
+    /// Checks that the configuration describes a valid study.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.display_name.is_empty() {
+            return Err(invalid("display_name", "must not be empty"));
+        }
+
+        if self.metrics.is_empty() {
+            return Err(invalid("metrics", "at least one metric is required"));
+        }
+        let mut metric_ids = HashSet::new();
+        for (i, metric) in self.metrics.iter().enumerate() {
+            let path = format!("metrics[{i}]");
+            if !metric_ids.insert(metric.id.as_str()) {
+                return Err(invalid(&path, format!("duplicate metric id {}", metric.id)));
+            }
+            if let Some(fraction) = metric
+                .safety
+                .as_ref()
+                .and_then(|s| s.desired_min_safe_trials_fraction)
+                && !(0.0..=1.0).contains(&fraction)
+            {
+                return Err(invalid(
+                    &format!("{path}.safety.desired_min_safe_trials_fraction"),
+                    "must be in [0, 1]",
+                ));
+            }
+        }
+
+        if self.parameters.is_empty() {
+            return Err(invalid("parameters", "at least one parameter is required"));
+        }
+        let mut parameter_ids = HashSet::new();
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            parameter.validate(&format!("parameters[{i}]"), &mut parameter_ids)?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds the [StudySpec] described by the configuration - expected to be valid:
+    /// the children of double parameters are left out.
+    pub fn study_spec(&self) -> StudySpec {
+        let observation_noise = match self.observation_noise {
+            NoiseConfig::Unspecified => ObservationNoise::Unspecified,
+            NoiseConfig::Low => ObservationNoise::Low,
+            NoiseConfig::High => ObservationNoise::High,
+        };
+
+        let mut builder = StudySpecBuilder::new(self.algorithm.clone(), observation_noise)
+            .with_metric_specs(self.metrics.iter().map(MetricConfig::metric_spec).collect())
+            .with_parameters(
+                self.parameters
+                    .iter()
+                    .map(ParameterConfig::parameter_spec)
+                    .collect(),
+            )
+            .with_metadata(
+                self.metadata
+                    .iter()
+                    .map(|m| KeyValue {
+                        key: m.key.clone(),
+                        ns: m.ns.clone(),
+                        a_value: Some(key_value::AValue::Value(m.value.clone())),
+                    })
+                    .collect(),
+            );
+
+        if let Some(StoppingConfig::Default) = self.automated_stopping {
+            builder = builder.with_automated_stopping_spec(
+                AutomatedStoppingSpec::DefaultStoppingSpec(DefaultEarlyStoppingSpec {}),
+            );
+        }
+
+        builder.build()
+    }
+
+    /// Builds the [CreateStudyRequest] of the study for `owner`.
+    pub fn create_study_request(
+        &self,
+        owner: impl Into<String>,
+    ) -> Result<CreateStudyRequest, Error> {
+        RequestBuilder::new(owner.into())
+            .with_display_name(self.display_name.clone())
+            .with_study_spec(self.study_spec())
+            .build()
+            .map_err(|e| invalid("display_name", e.to_string()))
+    }
+}
+
+impl MetricConfig {
+    fn metric_spec(&self) -> MetricSpec {
+        let goal = match self.goal {
+            GoalConfig::Maximize => GoalType::Maximize,
+            GoalConfig::Minimize => GoalType::Minimize,
+        };
+
+        MetricSpec {
+            metric_id: self.id.clone(),
+            goal: goal as i32,
+            safety_config: self.safety.as_ref().map(|s| SafetyMetricConfig {
+                safety_threshold: s.threshold,
+                desired_min_safe_trials_fraction: s.desired_min_safe_trials_fraction,
+            }),
+        }
+    }
+}
+
+impl ParameterConfig {
+    fn validate<'a>(&'a self, path: &str, ids: &mut HashSet<&'a str>) -> Result<(), Error> {
+        if self.id.is_empty() {
+            return Err(invalid(&format!("{path}.id"), "must not be empty"));
+        }
+        if !ids.insert(self.id.as_str()) {
+            return Err(invalid(path, format!("duplicate parameter id {}", self.id)));
+        }
+
+        let log_scale = matches!(self.scale, ScaleConfig::Log | ScaleConfig::ReverseLog);
+
+        match &self.domain {
+            DomainConfig::Double { min, max, default } => {
+                if !min.is_finite() {
+                    return Err(invalid(&format!("{path}.min"), "must be finite"));
+                }
+                if !max.is_finite() {
+                    return Err(invalid(&format!("{path}.max"), "must be finite"));
+                }
+                if min > max {
+                    return Err(invalid(path, "min must be lower than or equal to max"));
+                }
+                if default.is_some_and(|d| !(min..=max).contains(&&d)) {
+                    return Err(invalid(&format!("{path}.default"), "must be in [min, max]"));
+                }
+                if log_scale && *min <= 0.0 {
+                    return Err(invalid(
+                        &format!("{path}.min"),
+                        "must be > 0 with a log scale",
+                    ));
+                }
+            }
+            DomainConfig::Integer { min, max, default } => {
+                if min > max {
+                    return Err(invalid(path, "min must be lower than or equal to max"));
+                }
+                if default.is_some_and(|d| d < *min || d > *max) {
+                    return Err(invalid(&format!("{path}.default"), "must be in [min, max]"));
+                }
+                if log_scale && *min <= 0 {
+                    return Err(invalid(
+                        &format!("{path}.min"),
+                        "must be > 0 with a log scale",
+                    ));
+                }
+            }
+            DomainConfig::Categorical { values, default } => {
+                if values.is_empty() {
+                    return Err(invalid(&format!("{path}.values"), "must not be empty"));
+                }
+                if default.as_ref().is_some_and(|d| !values.contains(d)) {
+                    return Err(invalid(
+                        &format!("{path}.default"),
+                        "must be one of the values",
+                    ));
+                }
+                if self.scale != ScaleConfig::Unspecified {
+                    return Err(invalid(
+                        &format!("{path}.scale"),
+                        "not supported for categorical parameters",
+                    ));
+                }
+            }
+            DomainConfig::Discrete { values, default } => {
+                if values.is_empty() {
+                    return Err(invalid(&format!("{path}.values"), "must not be empty"));
+                }
+                if values.iter().any(|v| !v.is_finite()) {
+                    return Err(invalid(&format!("{path}.values"), "must be finite"));
+                }
+                if default.is_some_and(|d| !values.contains(&d)) {
+                    return Err(invalid(
+                        &format!("{path}.default"),
+                        "must be one of the values",
+                    ));
+                }
+                if log_scale && values.iter().any(|v| *v <= 0.0) {
+                    return Err(invalid(
+                        &format!("{path}.values"),
+                        "must be > 0 with a log scale",
+                    ));
+                }
+            }
+        }
+
+        if matches!(self.domain, DomainConfig::Double { .. }) && !self.children.is_empty() {
+            return Err(invalid(
+                &format!("{path}.children"),
+                "not supported for double parameters - use a discrete parameter",
+            ));
+        }
+        for (i, child) in self.children.iter().enumerate() {
+            let child_path = format!("{path}.children[{i}]");
+
+            if child.when.is_empty() {
+                return Err(invalid(&format!("{child_path}.when"), "must not be empty"));
+            }
+            for (j, value) in child.when.iter().enumerate() {
+                if !self.is_feasible(value) {
+                    return Err(invalid(
+                        &format!("{child_path}.when[{j}]"),
+                        format!("not a value of parameter {}", self.id),
+                    ));
+                }
+            }
+            for (j, parameter) in child.parameters.iter().enumerate() {
+                parameter.validate(&format!("{child_path}.parameters[{j}]"), ids)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `value` is a feasible value of this parameter - for conditions.
+    fn is_feasible(&self, value: &ConditionValue) -> bool {
+        match (&self.domain, value) {
+            (DomainConfig::Integer { min, max, .. }, ConditionValue::Integer(v)) => {
+                min <= v && v <= max
+            }
+            (DomainConfig::Categorical { values, .. }, ConditionValue::String(v)) => {
+                values.contains(v)
+            }
+            (DomainConfig::Discrete { values, .. }, ConditionValue::Integer(v)) => {
+                values.contains(&(*v as f64))
+            }
+            (DomainConfig::Discrete { values, .. }, ConditionValue::Double(v)) => {
+                values.contains(v)
+            }
+            _ => false,
+        }
+    }
+
+    fn parameter_spec(&self) -> ParameterSpec {
+        let parameter_value_spec = match &self.domain {
+            DomainConfig::Double { min, max, default } => {
+                ParameterValueSpec::DoubleValueSpec(DoubleValueSpec {
+                    min_value: *min,
+                    max_value: *max,
+                    default_value: *default,
+                })
+            }
+            DomainConfig::Integer { min, max, default } => {
+                ParameterValueSpec::IntegerValueSpec(IntegerValueSpec {
+                    min_value: *min,
+                    max_value: *max,
+                    default_value: *default,
+                })
+            }
+            DomainConfig::Categorical { values, default } => {
+                ParameterValueSpec::CategoricalValueSpec(CategoricalValueSpec {
+                    values: values.clone(),
+                    default_value: default.clone(),
+                })
+            }
+            DomainConfig::Discrete { values, default } => {
+                ParameterValueSpec::DiscreteValueSpec(DiscreteValueSpec {
+                    values: values.clone(),
+                    default_value: *default,
+                })
+            }
+        };
+
+        let scale_type = match self.scale {
+            ScaleConfig::Unspecified => ScaleType::Unspecified,
+            ScaleConfig::Linear => ScaleType::UnitLinearScale,
+            ScaleConfig::Log => ScaleType::UnitLogScale,
+            ScaleConfig::ReverseLog => ScaleType::UnitReverseLogScale,
+        };
+
+        let conditional_parameter_specs = self
+            .children
+            .iter()
+            .filter_map(|child| Some((child, self.parent_value_condition(&child.when)?)))
+            .flat_map(|(child, condition)| {
+                child
+                    .parameters
+                    .iter()
+                    .map(move |parameter| ConditionalParameterSpec {
+                        parameter_spec: Some(parameter.parameter_spec()),
+                        parent_value_condition: Some(condition.clone()),
+                    })
+            })
+            .collect();
+
+        ParameterSpec {
+            parameter_id: self.id.clone(),
+            parameter_value_spec: Some(parameter_value_spec),
+            scale_type: scale_type as i32,
+            conditional_parameter_specs,
+        }
+    }
+
+    /// The condition on the values `when` of this parameter - `None` for a double
+    /// parameter, which cannot be a parent.
+    fn parent_value_condition(&self, when: &[ConditionValue]) -> Option<ParentValueCondition> {
+        let condition = match self.domain {
+            DomainConfig::Double { .. } => return None,
+            DomainConfig::Categorical { .. } => {
+                ParentValueCondition::ParentCategoricalValues(CategoricalValueCondition {
+                    values: when
+                        .iter()
+                        .filter_map(|v| match v {
+                            ConditionValue::String(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                })
+            }
+            DomainConfig::Integer { .. } => {
+                ParentValueCondition::ParentIntValues(IntValueCondition {
+                    values: when
+                        .iter()
+                        .filter_map(|v| match v {
+                            ConditionValue::Integer(i) => Some(*i),
+                            _ => None,
+                        })
+                        .collect(),
+                })
+            }
+            DomainConfig::Discrete { .. } => {
+                ParentValueCondition::ParentDiscreteValues(DiscreteValueCondition {
+                    values: when
+                        .iter()
+                        .filter_map(|v| match v {
+                            ConditionValue::Integer(i) => Some(*i as f64),
+                            ConditionValue::Double(d) => Some(*d),
+                            ConditionValue::String(_) => None,
+                        })
+                        .collect(),
+                })
+            }
+        };
+
+        Some(condition)
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> Error {
+    Error::Invalid {
+        location: Location {
+            path: Some(path.to_string()),
+            ..Default::default()
+        },
+        message: message.into(),
+    }
+}
+
+/// Line and column of the field at `path` - e.g. `parameters[1].max` - in `content`, or
+/// of its closest parent if the field is missing.
+fn locate(content: &str, format: Format, path: &str) -> Option<(usize, usize)> {
+    let locations = match format {
+        // JSON is parsed as YAML - flow mappings and sequences.
+        Format::Yaml | Format::Json => yaml_locations(content)?,
+        Format::Toml => toml_locations(content)?,
+    };
+
+    let mut path = path;
+    loop {
+        if let Some(location) = locations.get(path) {
+            return Some(*location);
+        }
+        path = &path[..path.rfind(['.', '['])?];
+    }
+}
+
+/// Line and column of every field of a YAML document, by path.
+fn yaml_locations(content: &str) -> Option<HashMap<String, (usize, usize)>> {
+    use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+    use yaml_rust2::scanner::Marker;
+
+    enum Frame {
+        Mapping { path: String, key: Option<String> },
+        Sequence { path: String, index: usize },
+    }
+
+    #[derive(Default)]
+    struct Receiver {
+        stack: Vec<Frame>,
+        locations: HashMap<String, (usize, usize)>,
+    }
+
+    impl MarkedEventReceiver for Receiver {
+        fn on_event(&mut self, event: Event, mark: Marker) {
+            let location = (mark.line(), mark.col() + 1);
+
+            let path = match &event {
+                Event::MappingEnd | Event::SequenceEnd => {
+                    self.stack.pop();
+                    return;
+                }
+                Event::Scalar(..)
+                | Event::Alias(..)
+                | Event::MappingStart(..)
+                | Event::SequenceStart(..) => match self.stack.last_mut() {
+                    None => String::new(),
+                    Some(Frame::Mapping { path, key }) => match key.take() {
+                        Some(key) => join(path, &key),
+                        None => {
+                            // a key - the field is located at its key
+                            let name = match &event {
+                                Event::Scalar(name, ..) => name.clone(),
+                                _ => String::new(),
+                            };
+                            self.locations.insert(join(path, &name), location);
+                            *key = Some(name);
+                            return;
+                        }
+                    },
+                    Some(Frame::Sequence { path, index }) => {
+                        let path = format!("{path}[{index}]");
+                        *index += 1;
+                        self.locations.insert(path.clone(), location);
+                        path
+                    }
+                },
+                _ => return,
+            };
+
+            match event {
+                Event::MappingStart(..) => self.stack.push(Frame::Mapping { path, key: None }),
+                Event::SequenceStart(..) => self.stack.push(Frame::Sequence { path, index: 0 }),
+                _ => {}
+            }
+        }
+    }
+
+    let mut receiver = Receiver::default();
+    Parser::new_from_str(content)
+        .load(&mut receiver, false)
+        .ok()?;
+
+    Some(receiver.locations)
+}
+
+/// Line and column of every field of a TOML document, by path.
+fn toml_locations(content: &str) -> Option<HashMap<String, (usize, usize)>> {
+    use toml::de::{DeTable, DeValue};
+
+    fn walk(
+        value: &DeValue,
+        path: &str,
+        content: &str,
+        locations: &mut HashMap<String, (usize, usize)>,
+    ) {
+        match value {
+            DeValue::Table(table) => {
+                for (key, value) in table {
+                    let path = join(path, key.get_ref());
+                    locations.insert(path.clone(), line_column(content, key.span().start));
+                    walk(value.get_ref(), &path, content, locations);
+                }
+            }
+            DeValue::Array(array) => {
+                for (i, value) in array.iter().enumerate() {
+                    let path = format!("{path}[{i}]");
+                    locations.insert(path.clone(), line_column(content, value.span().start));
+                    walk(value.get_ref(), &path, content, locations);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let table = DeTable::parse(content).ok()?.into_inner();
+
+    let mut locations = HashMap::new();
+    walk(&DeValue::Table(table), "", content, &mut locations);
+
+    Some(locations)
+}
+
+/// The path of the field `key` of the field at `path`.
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Line and column, starting at 1, of the byte `offset` in `content`.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Error, Format, StudyConfig};
+    use crate::vizier::study_spec::parameter_spec::ParameterValueSpec;
+    use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::ParentValueCondition;
+
+    #[test]
+    fn it_loads_the_example_configuration() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/study.yaml");
+        let config = StudyConfig::from_path(&path).unwrap();
+
+        let request = config.create_study_request("owner").unwrap();
+        assert_eq!(request.parent, "owners/owner");
+
+        let study = request.study.unwrap();
+        let spec = study.study_spec.unwrap();
+        assert_eq!(spec.metrics.len(), 2);
+        assert!(spec.metrics[1].safety_config.is_some());
+        assert_eq!(spec.parameters.len(), 3);
+        assert!(spec.automated_stopping_spec.is_some());
+
+        let optimizer = &spec.parameters[2];
+        assert!(matches!(
+            optimizer.parameter_value_spec,
+            Some(ParameterValueSpec::CategoricalValueSpec(_))
+        ));
+        assert!(matches!(
+            &optimizer.conditional_parameter_specs[0].parent_value_condition,
+            Some(ParentValueCondition::ParentCategoricalValues(c)) if c.values == ["sgd"]
+        ));
+    }
+
+    #[test]
+    fn it_parses_toml_and_json() {
+        let toml = r#"
+            display_name = "s"
+
+            [[metrics]]
+            id = "m1"
+            goal = "maximize"
+
+            [[parameters]]
+            id = "a"
+            type = "double"
+            min = 0
+            max = 1
+        "#;
+        let json = r#"{
+            "display_name": "s",
+            "metrics": [{"id": "m1", "goal": "maximize"}],
+            "parameters": [{"id": "a", "type": "double", "min": 0.0, "max": 1.0}]
+        }"#;
+
+        assert_eq!(
+            StudyConfig::parse(toml, Format::Toml).unwrap(),
+            StudyConfig::parse(json, Format::Json).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_reports_error_locations() {
+        let yaml = "display_name: s\nmetrics:\n  - id: m1\n    goal: maximise\n";
+        match StudyConfig::parse(yaml, Format::Yaml) {
+            Err(Error::Parse { location, .. }) => assert_eq!(location.line, Some(4)),
+            r => panic!("unexpected {r:?}"),
+        }
+
+        let toml = "display_name = \"s\"\nmetrics = 3\n";
+        match StudyConfig::parse(toml, Format::Toml) {
+            Err(Error::Parse { location, .. }) => assert_eq!(location.line, Some(2)),
+            r => panic!("unexpected {r:?}"),
+        }
+
+        let yaml = r#"
+display_name: s
+metrics:
+  - id: m1
+    goal: maximize
+parameters:
+  - id: a
+    type: categorical
+    values: [x, y]
+    children:
+      - when: [z]
+        parameters:
+          - id: b
+            type: integer
+            min: 1
+            max: 2
+"#;
+        match StudyConfig::parse(yaml, Format::Yaml) {
+            Err(Error::Invalid { location, .. }) => {
+                assert_eq!(
+                    location.path.as_deref(),
+                    Some("parameters[0].children[0].when[0]")
+                );
+                assert_eq!((location.line, location.column), (Some(11), Some(16)));
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+
+        // validation errors are located in every format
+        let toml = r#"display_name = "s"
+
+[[metrics]]
+id = "m1"
+goal = "maximize"
+
+[[parameters]]
+id = "a"
+type = "double"
+min = 2
+max = 1
+"#;
+        match StudyConfig::parse(toml, Format::Toml) {
+            Err(Error::Invalid { location, .. }) => {
+                assert_eq!(location.path.as_deref(), Some("parameters[0]"));
+                assert_eq!(location.line, Some(7));
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+
+        let json = r#"{
+  "display_name": "s",
+  "metrics": [{"id": "m1", "goal": "maximize"}],
+  "parameters": [
+    {"id": "a", "type": "double", "min": 0, "max": 1, "default": 2}
+  ]
+}"#;
+        match StudyConfig::parse(json, Format::Json) {
+            Err(Error::Invalid { location, .. }) => {
+                assert_eq!(location.path.as_deref(), Some("parameters[0].default"));
+                assert_eq!((location.line, location.column), (Some(5), Some(55)));
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_children_of_double_parameters() {
+        let yaml = r#"
+display_name: s
+metrics:
+  - id: m1
+    goal: maximize
+parameters:
+  - id: a
+    type: double
+    min: 0
+    max: 1
+    children:
+      - when: [0.5]
+        parameters:
+          - id: b
+            type: integer
+            min: 1
+            max: 2
+"#;
+        match StudyConfig::parse(yaml, Format::Yaml) {
+            Err(Error::Invalid { location, message }) => {
+                assert_eq!(location.path.as_deref(), Some("parameters[0].children"));
+                assert!(message.contains("double"), "{message}");
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_non_finite_bounds() {
+        let parse = |domain: &str| {
+            let yaml = format!(
+                "display_name: s\nmetrics:\n  - id: m1\n    goal: maximize\nparameters:\n  - id: a\n{domain}"
+            );
+            StudyConfig::parse(&yaml, Format::Yaml)
+        };
+
+        for (domain, path) in [
+            (
+                "    type: double\n    min: .nan\n    max: 1\n",
+                "parameters[0].min",
+            ),
+            (
+                "    type: double\n    min: 0\n    max: .nan\n",
+                "parameters[0].max",
+            ),
+            (
+                "    type: double\n    min: 0\n    max: .inf\n",
+                "parameters[0].max",
+            ),
+            (
+                "    type: double\n    min: 0\n    max: 1\n    default: .nan\n",
+                "parameters[0].default",
+            ),
+            (
+                "    type: discrete\n    values: [1, .nan]\n",
+                "parameters[0].values",
+            ),
+        ] {
+            match parse(domain) {
+                Err(Error::Invalid { location, .. }) => {
+                    assert_eq!(location.path.as_deref(), Some(path), "{domain}")
+                }
+                r => panic!("unexpected {r:?} for {domain}"),
+            }
+        }
+    }
+
+    #[test]
+    fn it_rejects_unknown_parameter_fields() {
+        let parse = |parameter: &str| {
+            let yaml = format!(
+                "display_name: s\nmetrics:\n  - id: m1\n    goal: maximize\nparameters:\n{parameter}"
+            );
+            StudyConfig::parse(&yaml, Format::Yaml)
+        };
+
+        parse("  - id: a\n    type: double\n    min: 0.1\n    max: 1\n    scale: log\n").unwrap();
+
+        for parameter in [
+            "  - id: a\n    type: double\n    min: 0\n    max: 1\n    min_vale: 2\n",
+            "  - id: a\n    type: double\n    min: 0\n    max: 1\n    scael: log\n",
+            "  - id: a\n    type: categorical\n    values: [x]\n    min: 0\n",
+        ] {
+            match parse(parameter) {
+                Err(Error::Parse { location, message }) => {
+                    assert_eq!(location.line, Some(6), "{message}");
+                    assert!(message.contains("unknown field"), "{message}");
+                }
+                r => panic!("unexpected {r:?}"),
+            }
+        }
+    }
+}
@@ -17,13 +17,16 @@
 //! See <https://github.com/google/vizier> for OSS Vizier backend.
 //!
 //! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::vizier::vizier_service_client::VizierServiceClient;
+//! # async fn example() {
 //! let endpoint = std::env::var("ENDPOINT").unwrap_or_else(|_| "http://localhost:28080".to_string());
 //!
 //! let service = VizierServiceClient::connect(endpoint).await.unwrap();
 //!
 //! let owner = "owner".to_string();
 //!
-//! let mut client = VizierClient::new_with_service(owner, service)
+//! let mut client = VizierClient::new(owner, service);
 //!
 //! let request = client
 //!     .mk_list_studies_request_builder()
@@ -35,30 +38,79 @@
 //! for t in study_list {
 //!     println!("- {}", &t.display_name);
 //! }
+//! # }
 //! ```
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use futures_util::TryStreamExt;
 use prost::bytes::Bytes;
 pub use prost_types;
-use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
 use tonic::codegen::http::uri::InvalidUri;
 use tonic::codegen::{Body, StdError};
 
+use crate::builder::{ClientBuilder, InterceptedChannel};
 use crate::google::longrunning::{GetOperationRequest, Operation, operation};
+use crate::model::operation::OperationHandle;
 use crate::model::{study, trial};
+use crate::poll::PollPolicy;
+use crate::reporter::TrialReporter;
+use crate::retry::{Idempotency, RetryCounts, RetryPolicy, RetryStats};
 use crate::study::StudyName;
 use crate::trial::complete::FinalMeasurementOrReason;
 use crate::trial::{TrialName, early_stopping, optimal, stop};
 use crate::vizier::vizier_service_client::VizierServiceClient;
 use crate::vizier::{
-    AddTrialMeasurementRequest, CheckTrialEarlyStoppingStateRequest, CompleteTrialRequest,
+    AddTrialMeasurementRequest, CheckTrialEarlyStoppingStateRequest,
+    CheckTrialEarlyStoppingStateResponse, CompleteTrialRequest, CreateStudyRequest,
     CreateTrialRequest, DeleteStudyRequest, DeleteTrialRequest, GetStudyRequest, GetTrialRequest,
-    ListOptimalTrialsRequest, Measurement, StopTrialRequest, SuggestTrialsRequest,
-    SuggestTrialsResponse, Trial,
+    ListOptimalTrialsRequest, ListOptimalTrialsResponse, ListStudiesRequest, ListStudiesResponse,
+    ListTrialsRequest, ListTrialsResponse, Measurement, SetStudyStateRequest, StopTrialRequest,
+    Study, StudySpec, SuggestTrialsRequest, SuggestTrialsResponse, Trial,
 };
 
+/// Sends `$request` with the `$method` RPC of the service, retrying it according to
+/// the [RetryPolicy] of the client, if any. Evaluates to the result of the last attempt
+/// and the number of retries.
+macro_rules! call_with_retries {
+    ($self:ident, $idempotency:expr, $method:ident, $request:expr) => {{
+        let request = $request;
+        let mut attempts = retry::Attempts::new($self.retry_policy.as_ref(), &$self.retry_stats);
+        loop {
+            match $self.service.$method(request.clone()).await {
+                Ok(resp) => break (Ok(resp.into_inner()), attempts.retries()),
+                Err(status) => {
+                    if !attempts.retry(&status, $idempotency).await {
+                        break (Err(status), attempts.retries());
+                    }
+                }
+            }
+        }
+    }};
+}
+
+pub mod analysis;
+pub mod benchmarks;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod builder;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod model;
+pub mod optimizer;
+pub mod pagination;
+pub mod poll;
+pub mod report;
+pub mod reporter;
+pub mod retry;
+pub mod runner;
+#[cfg(feature = "serde")]
+mod serde_wkt;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+pub mod table;
 pub mod util;
 
 /// google protos.
@@ -95,12 +147,16 @@ pub mod vizier {
 #[derive(Clone)]
 pub struct VizierClient<T> {
     owner: String,
+    poll_policy: PollPolicy,
+    retry_policy: Option<RetryPolicy>,
+    retry_stats: Arc<RetryStats>,
     /// The Vizier service client.
     pub service: VizierServiceClient<T>,
 }
 
 /// Errors that can occur when using [VizierClient].
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Transport error
     #[error("tonic transport error - {0}")]
@@ -114,6 +170,58 @@ pub enum Error {
     /// Vizier service error.
     #[error("Status: {}", .0.message())]
     Status(#[from] tonic::Status),
+    /// Invalid metadata key or value.
+    #[error("invalid metadata: {0}")]
+    InvalidMetadata(String),
+    /// Waiting for an operation took longer than the [PollPolicy] deadline.
+    #[error("operation timed out after {0:?}")]
+    Timeout(Duration),
+    /// Waiting for an operation was cancelled.
+    #[error("operation cancelled")]
+    Cancelled,
+    /// The service returned a page token it had already returned while listing.
+    #[error("repeated page token {0}")]
+    RepeatedPageToken(String),
+    /// Invalid resource name.
+    #[error("{0}")]
+    InvalidName(#[from] model::InvalidName),
+    /// Invalid study creation request.
+    #[error("{0}")]
+    InvalidStudyRequest(#[from] study::create::Error),
+    /// The study already has trials - not in the imported snapshot, or from an earlier
+    /// benchmark run.
+    #[error("study {0} already has trials")]
+    StudyNotEmpty(String),
+    /// An existing study has a spec incompatible with the requested one.
+    #[error("study {study} has an incompatible spec: {reason}")]
+    IncompatibleStudySpec {
+        /// Name of the existing study.
+        study: String,
+        /// Why the specs are not compatible.
+        reason: study::spec::Incompatibility,
+    },
+    /// Error reading or writing a study saved to local files.
+    #[cfg(feature = "serde")]
+    #[error("{0}")]
+    Snapshot(#[from] snapshot::Error),
+    /// Local optimizer error.
+    #[error("{0}")]
+    Optimizer(#[from] optimizer::Error),
+    /// Error evaluating a benchmark problem.
+    #[error("{0}")]
+    Benchmark(#[from] benchmarks::Error),
+    /// The runtime of the blocking client could not be started.
+    #[cfg(feature = "blocking")]
+    #[error("failed to start the runtime: {0}")]
+    Runtime(std::io::Error),
+}
+
+impl VizierClient<InterceptedChannel> {
+    /// Creates a [ClientBuilder] to configure the connection to the service at
+    /// `endpoint` for the studies of `owner`.
+    pub fn builder(endpoint: impl Into<String>, owner: impl Into<String>) -> ClientBuilder {
+        ClientBuilder::new(endpoint, owner)
+    }
 }
 
 impl<T> VizierClient<T>
@@ -125,7 +233,30 @@ where
 {
     /// Creates a new Vizier client.
     pub fn new(owner: String, service: VizierServiceClient<T>) -> Self {
-        Self { owner, service }
+        Self {
+            owner,
+            poll_policy: PollPolicy::default(),
+            retry_policy: None,
+            retry_stats: Arc::new(RetryStats::default()),
+            service,
+        }
+    }
+
+    /// Sets the [RetryPolicy] of the RPC methods of the client - see [retry].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// The counters of RPCs sent by the RPC methods of this client and its clones.
+    pub fn retry_counts(&self) -> RetryCounts {
+        self.retry_stats.counts()
+    }
+
+    /// Sets the [PollPolicy] used to wait for long-running operations.
+    pub fn with_poll_policy(mut self, poll_policy: PollPolicy) -> Self {
+        self.poll_policy = poll_policy;
+        self
     }
 
     /// Creates a new [crate::vizier::CreateStudyRequest] builder.
@@ -143,6 +274,15 @@ where
         study::delete::RequestBuilder::new(study_name).build()
     }
 
+    /// Creates a new [SetStudyStateRequest].
+    pub fn mk_set_study_state_request(
+        &self,
+        study_name: StudyName,
+        state: vizier::study::State,
+    ) -> SetStudyStateRequest {
+        study::set_state::RequestBuilder::new(study_name, state).build()
+    }
+
     /// Creates a new [crate::vizier::ListStudiesRequest] builder.
     pub fn mk_list_studies_request_builder(&self) -> study::list::RequestBuilder {
         study::list::RequestBuilder::new(self.owner.clone())
@@ -224,6 +364,14 @@ where
         optimal::RequestBuilder::new(study_name).build()
     }
 
+    /// Creates a new [ListOptimalTrialsRequest] builder.
+    pub fn mk_list_optimal_trials_request_builder(
+        &self,
+        study_name: StudyName,
+    ) -> optimal::RequestBuilder {
+        optimal::RequestBuilder::new(study_name)
+    }
+
     /// Creates a [TrialName] (of the form
     /// "owners/{owner}/studies/{study}/trials/{trial}"). #
     /// Arguments
@@ -253,36 +401,59 @@ where
         StudyName::new(self.owner.clone(), study.into())
     }
 
-    /// Waits for an operation to be completed.
+    /// Creates a [TrialReporter] to report intermediate measurements of the trial
+    /// `trial_name` and check its early stopping state.
+    pub fn trial_reporter(&self, trial_name: TrialName) -> TrialReporter<T>
+    where
+        T: Clone,
+    {
+        TrialReporter::new(self.clone(), trial_name)
+    }
+
+    /// Waits for an operation to be completed, polling it according to the client
+    /// [PollPolicy].
     /// Makes `retries` attempts and return the error if it still fails.
     /// # Arguments
     /// * `retries` - The number of retries.
     /// * `operation` - The operation to wait for.
     pub async fn wait_for_operation(
+        &mut self,
+        retries: usize,
+        operation: Operation,
+    ) -> Result<Option<operation::Result>, Error> {
+        self.wait_for_operation_until_cancelled(retries, operation, &CancellationToken::new())
+            .await
+    }
+
+    /// Waits for an operation to be completed, polling it according to the client
+    /// [PollPolicy], until `cancellation_token` is cancelled.
+    /// Makes `retries` attempts and return the error if it still fails.
+    /// # Arguments
+    /// * `retries` - The number of retries.
+    /// * `operation` - The operation to wait for.
+    /// * `cancellation_token` - Cancelling it makes this fail with [Error::Cancelled].
+    pub async fn wait_for_operation_until_cancelled(
         &mut self,
         mut retries: usize,
         mut operation: Operation,
+        cancellation_token: &CancellationToken,
     ) -> Result<Option<operation::Result>, Error> {
-        while !operation.done {
-            let mut wait_ms = 500;
-            let resp = loop {
-                match self
-                    .service
-                    .get_operation(GetOperationRequest {
-                        name: operation.name.clone(),
-                    })
-                    .await
-                {
-                    Err(_) if retries > 0 => {
-                        retries -= 1;
-                        sleep(Duration::from_millis(wait_ms)).await;
-                        wait_ms *= 2;
-                    }
-                    res => break res,
-                }
-            }?;
+        let mut poller = self.poll_policy.start();
 
-            operation = resp.into_inner();
+        while !operation.done {
+            poller.wait(cancellation_token).await?;
+
+            match self
+                .service
+                .get_operation(GetOperationRequest {
+                    name: operation.name.clone(),
+                })
+                .await
+            {
+                Ok(resp) => operation = resp.into_inner(),
+                Err(_) if retries > 0 => retries -= 1,
+                Err(e) => return Err(e.into()),
+            }
         }
 
         Ok(operation.result)
@@ -310,26 +481,251 @@ where
     }
 
     /// Suggests trials to a study.
+    ///
+    /// Waits for the suggestion operation according to the client [PollPolicy].
     pub async fn suggest_trials(
         &mut self,
         request: SuggestTrialsRequest,
     ) -> Result<SuggestTrialsResponse, Error> {
-        let trials = self.service.suggest_trials(request).await?;
-        let operation = trials.into_inner();
+        self.suggest_trials_until_cancelled(request, &CancellationToken::new())
+            .await
+    }
+
+    /// Suggests trials to a study, waiting for the suggestion operation until
+    /// `cancellation_token` is cancelled.
+    pub async fn suggest_trials_until_cancelled(
+        &mut self,
+        request: SuggestTrialsRequest,
+        cancellation_token: &CancellationToken,
+    ) -> Result<SuggestTrialsResponse, Error> {
+        // the service hands ACTIVE trials back to the same client_id: safe to retry
+        let (operation, _) =
+            call_with_retries!(self, Idempotency::Idempotent, suggest_trials, request);
 
-        let result = loop {
-            if let Some(result) = self.get_operation(operation.name.clone()).await? {
-                break result;
-            }
-            sleep(Duration::from_millis(100)).await;
-        };
+        let result = self
+            .wait_for_operation_until_cancelled(0, operation?, cancellation_token)
+            .await?
+            .ok_or(util::Error::MissingResult)?;
 
         // parse the result into trials
-        let resp: SuggestTrialsResponse =
-            util::decode_operation_result_as(result, "SuggestTrialsResponse")?;
+        let resp: SuggestTrialsResponse = util::decode_operation_result(result)?;
 
         Ok(resp)
     }
+
+    /// Starts suggesting trials to a study and returns a handle on the suggestion
+    /// operation.
+    pub async fn suggest_trials_operation(
+        &mut self,
+        request: SuggestTrialsRequest,
+    ) -> Result<OperationHandle<T, SuggestTrialsResponse>, Error>
+    where
+        T: Clone,
+    {
+        let (operation, _) =
+            call_with_retries!(self, Idempotency::Idempotent, suggest_trials, request);
+        let operation = operation?;
+
+        Ok(OperationHandle::new(self.clone(), operation))
+    }
+
+    /// Creates a study - or returns the existing one with the same display name.
+    pub async fn create_study(&mut self, request: CreateStudyRequest) -> Result<Study, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, create_study, request);
+        Ok(resp?)
+    }
+
+    /// Finds the study of the owner with the given display name, scanning all the pages
+    /// of `ListStudies`.
+    pub async fn find_study_by_display_name(
+        &self,
+        display_name: &str,
+    ) -> Result<Option<Study>, Error>
+    where
+        T: Clone,
+    {
+        let studies = std::pin::pin!(self.list_studies_stream(0));
+
+        studies
+            .try_filter(|study| futures_util::future::ready(study.display_name == display_name))
+            .try_next()
+            .await
+    }
+
+    /// Gets the study with the given display name or creates it with `study_spec` if
+    /// there is none.
+    ///
+    /// Fails with [Error::IncompatibleStudySpec] if the existing study has a spec that
+    /// is not compatible with `study_spec` - see [study::spec::check_compatibility].
+    pub async fn get_or_create_study(
+        &mut self,
+        display_name: String,
+        study_spec: StudySpec,
+    ) -> Result<Study, Error>
+    where
+        T: Clone,
+    {
+        let study = match self.find_study_by_display_name(&display_name).await? {
+            Some(study) => study,
+            None => {
+                let request = self
+                    .mk_study_request_builder()
+                    .with_display_name(display_name)
+                    .with_study_spec(study_spec.clone())
+                    .build()?;
+
+                // returns the existing study if another client created it in between
+                self.create_study(request).await?
+            }
+        };
+
+        let existing_spec = study.study_spec.clone().unwrap_or_default();
+        study::spec::check_compatibility(&existing_spec, &study_spec).map_err(|reason| {
+            Error::IncompatibleStudySpec {
+                study: study.name.clone(),
+                reason,
+            }
+        })?;
+
+        Ok(study)
+    }
+
+    /// Gets a study.
+    pub async fn get_study(&mut self, request: GetStudyRequest) -> Result<Study, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, get_study, request);
+        Ok(resp?)
+    }
+
+    /// Lists a page of studies.
+    pub async fn list_studies(
+        &mut self,
+        request: ListStudiesRequest,
+    ) -> Result<ListStudiesResponse, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, list_studies, request);
+        Ok(resp?)
+    }
+
+    /// Deletes a study.
+    pub async fn delete_study(&mut self, request: DeleteStudyRequest) -> Result<(), Error> {
+        match call_with_retries!(self, Idempotency::Idempotent, delete_study, request) {
+            (Ok(()), _) => Ok(()),
+            // deleted by a previous attempt
+            (Err(status), retries) if retries > 0 && status.code() == tonic::Code::NotFound => {
+                Ok(())
+            }
+            (Err(status), _) => Err(status.into()),
+        }
+    }
+
+    /// Sets the state of a study - e.g. to pause or resume it.
+    pub async fn set_study_state(&mut self, request: SetStudyStateRequest) -> Result<Study, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, set_study_state, request);
+        Ok(resp?)
+    }
+
+    /// Creates a trial. Only retried if the [RetryPolicy] allows non-idempotent retries.
+    pub async fn create_trial(&mut self, request: CreateTrialRequest) -> Result<Trial, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::NonIdempotent, create_trial, request);
+        Ok(resp?)
+    }
+
+    /// Gets a trial.
+    pub async fn get_trial(&mut self, request: GetTrialRequest) -> Result<Trial, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, get_trial, request);
+        Ok(resp?)
+    }
+
+    /// Lists a page of trials.
+    pub async fn list_trials(
+        &mut self,
+        request: ListTrialsRequest,
+    ) -> Result<ListTrialsResponse, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, list_trials, request);
+        Ok(resp?)
+    }
+
+    /// Adds a measurement to a trial. Only retried if the [RetryPolicy] allows
+    /// non-idempotent retries.
+    pub async fn add_trial_measurement(
+        &mut self,
+        request: AddTrialMeasurementRequest,
+    ) -> Result<Trial, Error> {
+        let (resp, _) = call_with_retries!(
+            self,
+            Idempotency::NonIdempotent,
+            add_trial_measurement,
+            request
+        );
+        Ok(resp?)
+    }
+
+    /// Completes a trial.
+    ///
+    /// When a retry fails, the trial is returned if it has been completed by a previous
+    /// attempt.
+    pub async fn complete_trial(&mut self, request: CompleteTrialRequest) -> Result<Trial, Error> {
+        let name = request.name.clone();
+
+        match call_with_retries!(self, Idempotency::Idempotent, complete_trial, request) {
+            (Ok(trial), _) => Ok(trial),
+            (Err(status), 0) => Err(status.into()),
+            (Err(status), _) => {
+                let trial = self.get_trial(GetTrialRequest { name }).await?;
+
+                let completed = [
+                    vizier::trial::State::Succeeded as i32,
+                    vizier::trial::State::Infeasible as i32,
+                ];
+                if completed.contains(&trial.state) {
+                    Ok(trial)
+                } else {
+                    Err(status.into())
+                }
+            }
+        }
+    }
+
+    /// Deletes a trial.
+    pub async fn delete_trial(&mut self, request: DeleteTrialRequest) -> Result<(), Error> {
+        match call_with_retries!(self, Idempotency::Idempotent, delete_trial, request) {
+            (Ok(()), _) => Ok(()),
+            // deleted by a previous attempt
+            (Err(status), retries) if retries > 0 && status.code() == tonic::Code::NotFound => {
+                Ok(())
+            }
+            (Err(status), _) => Err(status.into()),
+        }
+    }
+
+    /// Checks whether a trial should stop.
+    pub async fn check_trial_early_stopping_state(
+        &mut self,
+        request: CheckTrialEarlyStoppingStateRequest,
+    ) -> Result<CheckTrialEarlyStoppingStateResponse, Error> {
+        let (resp, _) = call_with_retries!(
+            self,
+            Idempotency::Idempotent,
+            check_trial_early_stopping_state,
+            request
+        );
+        Ok(resp?)
+    }
+
+    /// Stops a trial.
+    pub async fn stop_trial(&mut self, request: StopTrialRequest) -> Result<Trial, Error> {
+        let (resp, _) = call_with_retries!(self, Idempotency::Idempotent, stop_trial, request);
+        Ok(resp?)
+    }
+
+    /// Lists a page of the optimal trials of a study.
+    pub async fn list_optimal_trials(
+        &mut self,
+        request: ListOptimalTrialsRequest,
+    ) -> Result<ListOptimalTrialsResponse, Error> {
+        let (resp, _) =
+            call_with_retries!(self, Idempotency::Idempotent, list_optimal_trials, request);
+        Ok(resp?)
+    }
 }
 
 #[cfg(test)]
@@ -340,6 +736,11 @@ mod trials {
 
     use super::common::{create_dummy_study, test_client};
     use crate::SuggestTrialsResponse;
+    use crate::optimizer::Optimizer;
+    use crate::optimizer::remote::RemoteOptimizer;
+    use crate::pagination::collect_all;
+    use crate::runner::Runner;
+    use crate::trial::ToTrialName;
     use crate::trial::complete::FinalMeasurementOrReason;
     use crate::util::decode_operation_result_as;
     use crate::vizier::{Measurement, measurement};
@@ -468,6 +869,36 @@ mod trials {
         dbg!(resp);
     }
 
+    #[tokio::test]
+    async fn it_waits_for_a_suggestion_operation() {
+        let mut client = test_client().await;
+
+        let study_name = "it_waits_for_a_suggestion_operation".to_string();
+
+        // create a study
+        create_dummy_study(
+            &mut client,
+            "ALGORITHM_UNSPECIFIED".to_string(),
+            study_name.clone(),
+        )
+        .await;
+
+        let study_name = client.study_name(study_name);
+
+        let client_id = "it_waits_for_a_suggestion_operation".to_string();
+        let request = client.mk_suggest_trials_request(study_name, 1, client_id);
+
+        let mut handle = client.suggest_trials_operation(request).await.unwrap();
+        dbg!(handle.name());
+
+        // a single poll, `wait` backs off according to the poll policy
+        let polled = handle.poll().await.unwrap();
+        assert_eq!(polled.is_some(), handle.is_done());
+
+        let resp = handle.wait().await.unwrap();
+        assert_eq!(resp.trials.len(), 1);
+    }
+
     #[tokio::test]
     async fn it_lists_trials() {
         let mut client = test_client().await;
@@ -525,6 +956,40 @@ mod trials {
         }
     }
 
+    #[tokio::test]
+    async fn it_streams_trials() {
+        let mut client = test_client().await;
+
+        let study_name = "it_streams_trials".to_string();
+
+        // create a study
+        create_dummy_study(
+            &mut client,
+            "ALGORITHM_UNSPECIFIED".to_string(),
+            study_name.clone(),
+        )
+        .await;
+
+        let study_name = client.study_name(study_name);
+
+        // suggest 3 trials
+        let client_id = "it_streams_trials".to_string();
+        let request = client.mk_suggest_trials_request(study_name.clone(), 3, client_id);
+
+        let _resp = client.suggest_trials(request).await.unwrap();
+
+        // list the trials, 2 by 2
+        let trials = collect_all(client.list_trials_stream(study_name.clone(), 2))
+            .await
+            .unwrap();
+        assert!(trials.len() >= 3);
+
+        let optimal_trials = collect_all(client.list_optimal_trials_stream(study_name, 2))
+            .await
+            .unwrap();
+        dbg!(optimal_trials.len());
+    }
+
     #[tokio::test]
     async fn it_can_add_trial_measurement() {
         let mut client = test_client().await;
@@ -583,6 +1048,97 @@ mod trials {
         dbg!(trial);
     }
 
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn it_exports_and_imports_a_study() {
+        let mut client = test_client().await;
+
+        let study_name = "it_exports_and_imports_a_study".to_string();
+
+        // create a study
+        create_dummy_study(&mut client, "RANDOM_SEARCH".to_string(), study_name.clone()).await;
+
+        let study_name = client.study_name(study_name);
+
+        // suggest 2 trials and complete the first one
+        let client_id = "it_exports_and_imports_a_study".to_string();
+        let request = client.mk_suggest_trials_request(study_name.clone(), 2, client_id);
+        let resp = client.suggest_trials(request).await.unwrap();
+
+        let request = client.mk_complete_trial_request(
+            resp.trials[0].to_trial_name(),
+            FinalMeasurementOrReason::FinalMeasurement(Measurement {
+                elapsed_duration: None,
+                step_count: 1,
+                metrics: vec![measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: 0.5,
+                }],
+            }),
+        );
+        client.complete_trial(request).await.unwrap();
+
+        let snapshot = client.export_study(study_name).await.unwrap();
+        assert!(snapshot.trials.len() >= 2);
+
+        let report = client
+            .import_study(
+                &snapshot,
+                Some("it_exports_and_imports_a_study_copy".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.trial_names.len(), snapshot.trials.len());
+
+        // importing again resumes the import - there is nothing left to do
+        let again = client
+            .import_study(
+                &snapshot,
+                Some("it_exports_and_imports_a_study_copy".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(again.trial_names, report.trial_names);
+        assert_eq!(again.resumed_trials, snapshot.trials.len());
+
+        let copy = client
+            .export_study(crate::study::ToStudyName::to_study_name(&report.study))
+            .await
+            .unwrap();
+        let states = |trials: &[crate::Trial]| trials.iter().map(|t| t.state).collect::<Vec<_>>();
+        assert_eq!(states(&copy.trials), states(&snapshot.trials));
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn it_refuses_to_import_into_an_incompatible_study() {
+        let mut client = test_client().await;
+
+        let study_name = "it_refuses_to_import_into_an_incompatible_study".to_string();
+        create_dummy_study(&mut client, "RANDOM_SEARCH".to_string(), study_name.clone()).await;
+        let snapshot = client
+            .export_study(client.study_name(study_name))
+            .await
+            .unwrap();
+
+        // an empty study with the same display name but another search space
+        let display_name = "it_refuses_to_import_into_an_incompatible_study_copy".to_string();
+        let mut study_spec = snapshot.study.study_spec.clone().unwrap();
+        study_spec.parameters.truncate(1);
+        let request = client
+            .mk_study_request_builder()
+            .with_display_name(display_name.clone())
+            .with_study_spec(study_spec)
+            .build()
+            .unwrap();
+        client.create_study(request).await.unwrap();
+
+        assert!(matches!(
+            client.import_study(&snapshot, Some(display_name)).await,
+            Err(crate::Error::IncompatibleStudySpec { .. })
+        ));
+    }
+
     #[tokio::test]
     async fn it_can_complete_a_trial() {
         let mut client = test_client().await;
@@ -667,6 +1223,235 @@ mod trials {
         dbg!(result);
     }
 
+    #[tokio::test]
+    async fn it_reports_measurements_with_a_trial_reporter() {
+        let mut client = test_client().await;
+
+        let study_name = "it_reports_measurements_with_a_trial_reporter".to_string();
+
+        // create a study
+        create_dummy_study(&mut client, "RANDOM_SEARCH".to_string(), study_name.clone()).await;
+
+        let study_name = client.study_name(study_name);
+
+        let client_id = "it_reports_measurements_with_a_trial_reporter".to_string();
+        let request = client.mk_suggest_trials_request(study_name, 1, client_id);
+
+        let resp = client.suggest_trials(request).await.unwrap();
+        assert_eq!(resp.trials.len(), 1);
+
+        let trial_name = resp.trials[0].to_trial_name();
+
+        let mut reporter = client
+            .trial_reporter(trial_name)
+            .with_min_report_interval(Duration::from_secs(60));
+
+        // the first measurement is sent right away, the next ones are held back by the
+        // report interval, each replacing the previous one as the pending measurement
+        for step in 1..=3 {
+            let measurement = Measurement {
+                elapsed_duration: Some(Duration::from_secs(step).try_into().unwrap()),
+                step_count: step as i64,
+                metrics: vec![measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: step as f64,
+                }],
+            };
+
+            let should_stop = reporter.report(measurement).await.unwrap();
+            assert_eq!(should_stop, reporter.cancellation_token().is_cancelled());
+        }
+
+        // step 1 was sent, step 2 was replaced by step 3 which is pending
+        assert_eq!(reporter.last_measurement().unwrap().step_count, 3);
+
+        // completing flushes step 3 and uses it as the final measurement
+        let trial = reporter.complete(None).await.unwrap();
+        dbg!(&trial);
+
+        // steps 1 and 3
+        assert_eq!(trial.measurements.len(), 2);
+        assert_eq!(trial.final_measurement.unwrap().step_count, 3);
+    }
+
+    #[tokio::test]
+    async fn it_resumes_active_trials() {
+        let mut client = test_client().await;
+
+        let study_name = "it_resumes_active_trials".to_string();
+
+        // create a study
+        create_dummy_study(&mut client, "RANDOM_SEARCH".to_string(), study_name.clone()).await;
+
+        let study_name = client.study_name(study_name);
+        let client_id = "it_resumes_active_trials".to_string();
+
+        // a first run of the worker starts a trial and reports a measurement
+        let mut runner = Runner::new(client.clone(), study_name.clone(), client_id.clone());
+        let assignments = runner.next_trials(1).await.unwrap();
+        assert_eq!(assignments.len(), 1);
+        assert!(!assignments[0].is_resumed());
+
+        let mut reporter = runner.reporter(&assignments[0]);
+        reporter
+            .report(Measurement {
+                elapsed_duration: None,
+                step_count: 7,
+                metrics: vec![measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: 1.0,
+                }],
+            })
+            .await
+            .unwrap();
+
+        // the trial is in flight and is not handed out twice
+        assert!(runner.next_trials(1).await.unwrap().is_empty());
+
+        // a restarted worker gets it back as resumed
+        let mut runner = Runner::new(client.clone(), study_name, client_id);
+        let resumed = runner.next_trials(1).await.unwrap();
+        assert_eq!(resumed.len(), 1);
+        assert!(resumed[0].is_resumed());
+        assert_eq!(resumed[0].trial_name(), assignments[0].trial_name());
+        assert_eq!(resumed[0].last_step(), Some(7));
+
+        let reporter = runner.reporter(&resumed[0]);
+        runner.complete(reporter, None).await.unwrap();
+        assert_eq!(runner.completed_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_asks_and_tells_a_remote_optimizer() {
+        let mut client = test_client().await;
+
+        let study_name = "it_asks_and_tells_a_remote_optimizer".to_string();
+
+        // create a study
+        create_dummy_study(&mut client, "RANDOM_SEARCH".to_string(), study_name.clone()).await;
+
+        let study_name = client.study_name(study_name);
+        let mut optimizer = RemoteOptimizer::new(
+            client,
+            study_name,
+            "it_asks_and_tells_a_remote_optimizer".to_string(),
+        );
+
+        let trials = optimizer.ask(2).await.unwrap();
+        assert_eq!(trials.len(), 2);
+
+        for trial in trials {
+            let completed = optimizer
+                .tell(
+                    &trial,
+                    Measurement {
+                        elapsed_duration: None,
+                        step_count: 1,
+                        metrics: vec![measurement::Metric {
+                            metric_id: "m1".to_string(),
+                            value: 0.5,
+                        }],
+                    },
+                )
+                .await
+                .unwrap();
+            assert_eq!(completed.name, trial.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_refuses_to_rerun_a_benchmark_study() {
+        use crate::benchmarks::functions::Branin;
+        use crate::benchmarks::harness::{Harness, ServiceBackend};
+        use crate::study::ToStudyName;
+
+        let mut client = test_client().await;
+
+        let prefix = "it_refuses_to_rerun_a_benchmark_study".to_string();
+
+        // delete the study of a previous test run
+        let display_name = format!("{prefix}-branin-RANDOM_SEARCH-0");
+        if let Some(study) = client
+            .find_study_by_display_name(&display_name)
+            .await
+            .unwrap()
+        {
+            let request = client.mk_delete_study_request(study.to_study_name());
+            client.delete_study(request).await.unwrap();
+        }
+
+        let harness = Harness::new(2)
+            .with_problems(vec![Box::new(Branin)])
+            .with_seeds(vec![0]);
+        let mut backend = ServiceBackend::new(client, prefix);
+
+        let results = harness.run(&mut backend).await.unwrap();
+        assert_eq!(results.runs.len(), 1);
+
+        // the study of the first run already has trials
+        assert!(matches!(
+            harness.run(&mut backend).await,
+            Err(crate::Error::StudyNotEmpty(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn it_uploads_an_offline_study() {
+        use crate::optimizer::local::RandomSearch;
+        use crate::optimizer::offline::{OfflineStudy, Storage};
+        use crate::study::spec::StudySpecBuilder;
+        use crate::vizier::study_spec::ObservationNoise;
+
+        let mut client = test_client().await;
+
+        let path = std::env::temp_dir().join(format!(
+            "it_uploads_an_offline_study-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let study_spec =
+            StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::Low).build();
+        let mut study = OfflineStudy::open(
+            Storage::File(path.clone()),
+            "it_uploads_an_offline_study",
+            study_spec,
+            RandomSearch::new(1),
+        )
+        .unwrap();
+
+        let trials = study.ask(2).await.unwrap();
+        study
+            .tell(
+                &trials[0],
+                Measurement {
+                    elapsed_duration: None,
+                    step_count: 1,
+                    metrics: vec![measurement::Metric {
+                        metric_id: "m1".to_string(),
+                        value: 0.5,
+                    }],
+                },
+            )
+            .await
+            .unwrap();
+
+        let report = study
+            .upload(
+                &mut client,
+                Some(format!(
+                    "it_uploads_an_offline_study-{}",
+                    std::process::id()
+                )),
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.trial_names.len(), 2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[tokio::test]
     async fn it_can_stop_a_trial() {
         let mut client = test_client().await;
@@ -721,13 +1506,80 @@ mod studies {
     use tonic::Code;
 
     use super::common::{create_dummy_study, test_client};
+    use crate::Error;
+    use crate::pagination::collect_all;
+    use crate::retry::RetryPolicy;
     use crate::study::spec::StudySpecBuilder;
+    use crate::vizier::study::State;
     use crate::vizier::study_spec::metric_spec::GoalType;
     use crate::vizier::study_spec::parameter_spec::{
         DoubleValueSpec, IntegerValueSpec, ParameterValueSpec, ScaleType,
     };
     use crate::vizier::study_spec::{MetricSpec, ObservationNoise, ParameterSpec};
 
+    #[tokio::test]
+    async fn it_gets_or_creates_a_study() {
+        let mut client = test_client().await;
+
+        let study_spec = |max_value: f64| {
+            StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::Low)
+                .with_metric_specs(vec![MetricSpec {
+                    metric_id: "m1".to_string(),
+                    goal: GoalType::Maximize as i32,
+                    safety_config: None,
+                }])
+                .with_parameters(vec![ParameterSpec {
+                    parameter_id: "a".to_string(),
+                    scale_type: ScaleType::Unspecified as i32,
+                    conditional_parameter_specs: vec![],
+                    parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(
+                        DoubleValueSpec {
+                            min_value: 0.0,
+                            max_value,
+                            default_value: None,
+                        },
+                    )),
+                }])
+                .build()
+        };
+
+        let display_name = "it_gets_or_creates_a_study".to_string();
+
+        let study = client
+            .get_or_create_study(display_name.clone(), study_spec(1.0))
+            .await
+            .unwrap();
+
+        let found = client
+            .find_study_by_display_name(&display_name)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.name, study.name);
+
+        let again = client
+            .get_or_create_study(display_name.clone(), study_spec(1.0))
+            .await
+            .unwrap();
+        assert_eq!(again.name, study.name);
+
+        let incompatible = client
+            .get_or_create_study(display_name, study_spec(2.0))
+            .await;
+        assert!(matches!(
+            incompatible,
+            Err(Error::IncompatibleStudySpec { .. })
+        ));
+
+        assert!(
+            client
+                .find_study_by_display_name("it_gets_or_creates_a_missing_study")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
     #[tokio::test]
     async fn it_lists_studies() {
         let mut client = test_client().await;
@@ -783,6 +1635,26 @@ mod studies {
         }
     }
 
+    #[tokio::test]
+    async fn it_streams_studies() {
+        let mut client = test_client().await;
+
+        // create a study
+        create_dummy_study(
+            &mut client,
+            "ALGORITHM_UNSPECIFIED".to_string(),
+            "it_streams_studies".to_string(),
+        )
+        .await;
+
+        let studies = collect_all(client.list_studies_stream(1)).await.unwrap();
+        assert!(
+            studies
+                .iter()
+                .any(|s| s.display_name == "it_streams_studies")
+        );
+    }
+
     #[tokio::test]
     async fn it_creates_studies() {
         let mut client = test_client().await;
@@ -863,6 +1735,57 @@ mod studies {
         dbg!(study);
     }
 
+    #[tokio::test]
+    async fn it_gets_a_study_with_retries() {
+        let client = test_client().await;
+        let mut client = client.with_retry_policy(RetryPolicy::new());
+
+        let study_name = "it_gets_a_study_with_retries".to_string();
+
+        // create a study
+        create_dummy_study(
+            &mut client,
+            "ALGORITHM_UNSPECIFIED".to_string(),
+            study_name.clone(),
+        )
+        .await;
+
+        let study_name = client.study_name(study_name);
+
+        let request = client.mk_get_study_request(study_name);
+
+        let study = client.get_study(request).await.unwrap();
+        dbg!(&study);
+
+        let counts = client.retry_counts();
+        assert_eq!(counts.calls, 1);
+        assert_eq!(counts.retries, 0);
+    }
+
+    #[tokio::test]
+    async fn it_sets_the_state_of_a_study() {
+        let mut client = test_client().await;
+
+        let study_name = "it_sets_the_state_of_a_study".to_string();
+
+        create_dummy_study(
+            &mut client,
+            "ALGORITHM_UNSPECIFIED".to_string(),
+            study_name.clone(),
+        )
+        .await;
+
+        let study_name = client.study_name(study_name);
+
+        let request = client.mk_set_study_state_request(study_name.clone(), State::Inactive);
+        let study = client.set_study_state(request).await.unwrap();
+        assert_eq!(study.state(), State::Inactive);
+
+        let request = client.mk_set_study_state_request(study_name, State::Active);
+        let study = client.set_study_state(request).await.unwrap();
+        assert_eq!(study.state(), State::Active);
+    }
+
     #[tokio::test]
     async fn it_deletes_a_study() {
         let mut client = test_client().await;
@@ -884,6 +1807,86 @@ mod studies {
     }
 }
 
+#[cfg(all(test, feature = "blocking"))]
+mod blocking_client {
+    use std::str::FromStr;
+
+    use crate::StudyName;
+    use crate::model::trial::ToTrialName;
+    use crate::model::trial::complete::FinalMeasurementOrReason;
+    use crate::study::spec::StudySpecBuilder;
+    use crate::vizier::study_spec::metric_spec::GoalType;
+    use crate::vizier::study_spec::parameter_spec::{
+        DoubleValueSpec, ParameterValueSpec, ScaleType,
+    };
+    use crate::vizier::study_spec::{MetricSpec, ObservationNoise, ParameterSpec};
+    use crate::vizier::{Measurement, measurement};
+
+    #[test]
+    fn it_suggests_and_completes_trials() {
+        let endpoint =
+            std::env::var("ENDPOINT").unwrap_or_else(|_| "http://localhost:28080".to_string());
+
+        let mut client = crate::blocking::VizierClient::builder(endpoint, "owner")
+            .connect_blocking()
+            .unwrap();
+
+        let study_spec = StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::Low)
+            .with_metric_specs(vec![MetricSpec {
+                metric_id: "m1".to_string(),
+                goal: GoalType::Maximize as i32,
+                safety_config: None,
+            }])
+            .with_parameters(vec![ParameterSpec {
+                parameter_id: "a".to_string(),
+                scale_type: ScaleType::Unspecified as i32,
+                conditional_parameter_specs: vec![],
+                parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(DoubleValueSpec {
+                    min_value: 0.0,
+                    max_value: 1.0,
+                    default_value: None,
+                })),
+            }])
+            .build();
+
+        let study = client
+            .get_or_create_study("it_suggests_trials_blocking".to_string(), study_spec)
+            .unwrap();
+        let study_name = StudyName::from_str(&study.name).unwrap();
+
+        let request =
+            client.mk_suggest_trials_request(study_name.clone(), 2, "blocking".to_string());
+        let mut trials = client.suggest_trials(request).unwrap().trials;
+        assert!(!trials.is_empty());
+
+        let request =
+            client.mk_suggest_trials_request(study_name.clone(), 1, "blocking_op".to_string());
+        let operation = client.suggest_trials_operation(request).unwrap();
+        assert!(operation.operation_name().is_ok());
+        trials.extend(operation.wait().unwrap().trials);
+
+        for trial in trials {
+            let final_measurement = FinalMeasurementOrReason::FinalMeasurement(Measurement {
+                elapsed_duration: None,
+                step_count: 1,
+                metrics: vec![measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: 0.5,
+                }],
+            });
+            let request =
+                client.mk_complete_trial_request(trial.to_trial_name(), final_measurement);
+            client.complete_trial(request).unwrap();
+        }
+
+        let trials = client
+            .list_trials_iter(study_name, 0)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(!trials.is_empty());
+    }
+}
+
 #[cfg(test)]
 mod common {
 
@@ -14,5 +14,48 @@
 
 //! Model for Vizier API.
 
+pub mod operation;
 pub mod study;
 pub mod trial;
+
+/// Error returned when parsing a resource name that does not match its pattern.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid resource name {name:?} - expected {pattern}")]
+pub struct InvalidName {
+    /// The name that failed to parse.
+    pub name: String,
+    /// The expected pattern.
+    pub pattern: &'static str,
+}
+
+/// Splits `name` into the values of the `{...}` segments of `pattern`, checking the
+/// literal segments and that no value is empty.
+pub(crate) fn parse_name<'a>(
+    name: &'a str,
+    pattern: &'static str,
+) -> Result<Vec<&'a str>, InvalidName> {
+    let invalid = || InvalidName {
+        name: name.to_string(),
+        pattern,
+    };
+
+    let segments: Vec<&str> = name.split('/').collect();
+    let expected: Vec<&str> = pattern.split('/').collect();
+    if segments.len() != expected.len() {
+        return Err(invalid());
+    }
+
+    let mut values = vec![];
+    for (segment, expected) in segments.into_iter().zip(expected) {
+        if expected.starts_with('{') {
+            if segment.is_empty() {
+                return Err(invalid());
+            }
+            values.push(segment);
+        } else if segment != expected {
+            return Err(invalid());
+        }
+    }
+
+    Ok(values)
+}
@@ -0,0 +1,221 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Long-running operation model.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use prost::bytes::Bytes;
+use tokio_util::sync::CancellationToken;
+use tonic::codegen::{Body, StdError};
+
+use crate::google::longrunning::{GetOperationRequest, Operation};
+use crate::model::{InvalidName, parse_name};
+use crate::{Error, StudyName, VizierClient, util};
+
+/// Pattern of the names of suggestion operations.
+pub const OPERATION_NAME_PATTERN: &str =
+    "owners/{owner}/studies/{study}/clients/{client}/operations/{operation}";
+
+/// The name of a suggestion operation.
+#[derive(Clone, PartialEq, Debug, Eq, Hash)]
+pub struct OperationName(String);
+
+impl OperationName {
+    /// Creates a new OperationName from its parts.
+    pub fn new(owner: String, study: String, client: String, operation: String) -> Self {
+        OperationName(format!(
+            "owners/{}/studies/{}/clients/{}/operations/{}",
+            owner, study, client, operation
+        ))
+    }
+
+    /// The owner - {owner} in the pattern.
+    pub fn owner(&self) -> &str {
+        self.segment(1)
+    }
+
+    /// The study - {study} in the pattern.
+    pub fn study(&self) -> &str {
+        self.segment(3)
+    }
+
+    /// The client id - {client} in the pattern.
+    pub fn client(&self) -> &str {
+        self.segment(5)
+    }
+
+    /// The operation number - {operation} in the pattern.
+    pub fn operation(&self) -> &str {
+        self.segment(7)
+    }
+
+    /// The name of the study of the operation.
+    pub fn study_name(&self) -> StudyName {
+        StudyName::new(self.owner().to_string(), self.study().to_string())
+    }
+
+    /// The name as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn segment(&self, index: usize) -> &str {
+        self.0.split('/').nth(index).unwrap_or_default()
+    }
+}
+
+impl FromStr for OperationName {
+    type Err = InvalidName;
+
+    /// Parses a name matching [OPERATION_NAME_PATTERN].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(s, OPERATION_NAME_PATTERN)?;
+        Ok(OperationName(s.to_string()))
+    }
+}
+
+impl fmt::Display for OperationName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<OperationName> for String {
+    fn from(operation_name: OperationName) -> String {
+        operation_name.0
+    }
+}
+
+/// Typed handle on a long-running [Operation] whose response is a `M`.
+pub struct OperationHandle<T, M> {
+    client: VizierClient<T>,
+    operation: Operation,
+    response: PhantomData<fn() -> M>,
+}
+
+impl<T, M> OperationHandle<T, M>
+where
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    M: prost::Message + prost::Name + Default,
+{
+    /// Creates a new [OperationHandle] from an [Operation] returned by the service.
+    pub fn new(client: VizierClient<T>, operation: Operation) -> Self {
+        Self {
+            client,
+            operation,
+            response: PhantomData,
+        }
+    }
+
+    /// The name of the operation.
+    pub fn name(&self) -> &str {
+        &self.operation.name
+    }
+
+    /// The name of the operation, parsed as an [OperationName].
+    pub fn operation_name(&self) -> Result<OperationName, InvalidName> {
+        self.operation.name.parse()
+    }
+
+    /// The last known state of the operation.
+    pub fn operation(&self) -> &Operation {
+        &self.operation
+    }
+
+    /// Whether the operation was done the last time it was polled.
+    pub fn is_done(&self) -> bool {
+        self.operation.done
+    }
+
+    /// The response of the operation if it was done the last time it was polled.
+    ///
+    /// Fails with [util::Error::OperationFailed] if the operation failed and
+    /// [util::Error::InvalidType] if the response is not a `M`.
+    pub fn result(&self) -> Option<Result<M, util::Error>> {
+        if !self.operation.done {
+            return None;
+        }
+
+        Some(match &self.operation.result {
+            Some(result) => util::decode_operation_result(result.clone()),
+            None => Err(util::Error::MissingResult),
+        })
+    }
+
+    /// Polls the operation once and returns its response if it is done.
+    pub async fn poll(&mut self) -> Result<Option<M>, Error> {
+        if !self.operation.done {
+            let resp = self
+                .client
+                .service
+                .get_operation(GetOperationRequest {
+                    name: self.operation.name.clone(),
+                })
+                .await?;
+
+            self.operation = resp.into_inner();
+        }
+
+        Ok(self.result().transpose()?)
+    }
+
+    /// Waits for the operation to be done, polling it according to the client
+    /// [PollPolicy](crate::poll::PollPolicy), and returns its response.
+    pub async fn wait(self) -> Result<M, Error> {
+        self.wait_until_cancelled(&CancellationToken::new()).await
+    }
+
+    /// Same as [OperationHandle::wait] but fails with [Error::Cancelled] when
+    /// `cancellation_token` is cancelled.
+    pub async fn wait_until_cancelled(
+        mut self,
+        cancellation_token: &CancellationToken,
+    ) -> Result<M, Error> {
+        let result = self
+            .client
+            .wait_for_operation_until_cancelled(0, self.operation, cancellation_token)
+            .await?
+            .ok_or(util::Error::MissingResult)?;
+
+        Ok(util::decode_operation_result(result)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OperationName;
+
+    #[test]
+    fn it_parses_operation_names() {
+        let name = "owners/owner/studies/study/clients/worker_1/operations/2";
+        let operation_name: OperationName = name.parse().unwrap();
+
+        assert_eq!(operation_name.client(), "worker_1");
+        assert_eq!(operation_name.operation(), "2");
+        assert_eq!(operation_name.study_name().study(), "study");
+        assert_eq!(operation_name.to_string(), name);
+
+        assert!(
+            "owners/owner/studies/study/operations/2"
+                .parse::<OperationName>()
+                .is_err()
+        );
+    }
+}
@@ -14,14 +14,22 @@
 
 //! Study model.
 
+use std::fmt;
+use std::str::FromStr;
+
+use crate::model::{InvalidName, parse_name};
 use crate::vizier::Study;
 
 pub mod create;
 pub mod delete;
 pub mod get;
 pub mod list;
+pub mod set_state;
 pub mod spec;
 
+/// Pattern of study names.
+pub const STUDY_NAME_PATTERN: &str = "owners/{owner}/studies/{study}";
+
 /// The name of a study.
 #[derive(Clone, PartialEq, Debug, Eq, Hash)]
 pub struct StudyName(String);
@@ -31,6 +39,41 @@ impl StudyName {
     pub fn new(owner: String, study: String) -> Self {
         StudyName(format!("owners/{}/studies/{}", owner, study))
     }
+
+    /// The owner - {owner} in the pattern.
+    pub fn owner(&self) -> &str {
+        self.segment(1)
+    }
+
+    /// The study - {study} in the pattern.
+    pub fn study(&self) -> &str {
+        self.segment(3)
+    }
+
+    /// The name as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn segment(&self, index: usize) -> &str {
+        self.0.split('/').nth(index).unwrap_or_default()
+    }
+}
+
+impl FromStr for StudyName {
+    type Err = InvalidName;
+
+    /// Parses a name matching [STUDY_NAME_PATTERN].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(s, STUDY_NAME_PATTERN)?;
+        Ok(StudyName(s.to_string()))
+    }
+}
+
+impl fmt::Display for StudyName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 /// Can be converted to a [StudyName].
@@ -56,3 +99,31 @@ impl From<&StudyName> for String {
         study_name.0.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::StudyName;
+    use crate::model::InvalidName;
+
+    #[test]
+    fn it_parses_study_names() {
+        let study_name: StudyName = "owners/owner/studies/study".parse().unwrap();
+
+        assert_eq!(study_name.owner(), "owner");
+        assert_eq!(study_name.study(), "study");
+        assert_eq!(
+            study_name,
+            StudyName::new("owner".to_string(), "study".to_string())
+        );
+        assert_eq!(study_name.to_string(), "owners/owner/studies/study");
+
+        for name in [
+            "owners/owner/studies/",
+            "owners//studies/study",
+            "owners/owner/study/study",
+            "owners/owner/studies/study/trials/1",
+        ] {
+            assert!(matches!(name.parse::<StudyName>(), Err(InvalidName { .. })));
+        }
+    }
+}
@@ -19,6 +19,64 @@ use crate::vizier::study_spec::{
 };
 use crate::vizier::{KeyValue, StudySpec};
 
+/// Why a [StudySpec] is not compatible with another one - see [check_compatibility].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// The algorithms differ.
+    #[error("algorithm is {existing} instead of {requested}")]
+    Algorithm {
+        /// Algorithm of the existing spec.
+        existing: String,
+        /// Requested algorithm.
+        requested: String,
+    },
+    /// A metric is missing or has a different definition.
+    #[error("metric {0} is missing or differs")]
+    Metric(String),
+    /// A parameter is missing or has a different definition.
+    #[error("parameter {0} is missing or differs")]
+    Parameter(String),
+    /// The number of metrics or parameters differs.
+    #[error("{0} count differs")]
+    Count(&'static str),
+}
+
+/// Checks that the `existing` [StudySpec] of a study can be used in place of the
+/// `requested` one: same algorithm, metrics and parameters - in any order.
+///
+/// The observation noise, automated stopping spec and metadata are not compared.
+pub fn check_compatibility(
+    existing: &StudySpec,
+    requested: &StudySpec,
+) -> Result<(), Incompatibility> {
+    if existing.algorithm != requested.algorithm {
+        return Err(Incompatibility::Algorithm {
+            existing: existing.algorithm.clone(),
+            requested: requested.algorithm.clone(),
+        });
+    }
+
+    if existing.metrics.len() != requested.metrics.len() {
+        return Err(Incompatibility::Count("metric"));
+    }
+    for metric in &requested.metrics {
+        if !existing.metrics.contains(metric) {
+            return Err(Incompatibility::Metric(metric.metric_id.clone()));
+        }
+    }
+
+    if existing.parameters.len() != requested.parameters.len() {
+        return Err(Incompatibility::Count("parameter"));
+    }
+    for parameter in &requested.parameters {
+        if !existing.parameters.contains(parameter) {
+            return Err(Incompatibility::Parameter(parameter.parameter_id.clone()));
+        }
+    }
+
+    Ok(())
+}
+
 /// [StudySpec] builder.
 pub struct StudySpecBuilder {
     metrics: Vec<MetricSpec>,
@@ -93,3 +151,65 @@ impl StudySpecBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Incompatibility, StudySpecBuilder, check_compatibility};
+    use crate::vizier::study_spec::metric_spec::GoalType;
+    use crate::vizier::study_spec::parameter_spec::{
+        DoubleValueSpec, ParameterValueSpec, ScaleType,
+    };
+    use crate::vizier::study_spec::{MetricSpec, ObservationNoise, ParameterSpec};
+
+    fn parameter(parameter_id: &str, max_value: f64) -> ParameterSpec {
+        ParameterSpec {
+            parameter_id: parameter_id.to_string(),
+            scale_type: ScaleType::Unspecified as i32,
+            conditional_parameter_specs: vec![],
+            parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(DoubleValueSpec {
+                min_value: 0.0,
+                max_value,
+                default_value: None,
+            })),
+        }
+    }
+
+    #[test]
+    fn it_checks_compatibility() {
+        let metric = MetricSpec {
+            metric_id: "m1".to_string(),
+            goal: GoalType::Maximize as i32,
+            safety_config: None,
+        };
+
+        let spec = StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::Low)
+            .with_metric_specs(vec![metric.clone()])
+            .with_parameters(vec![parameter("a", 1.0), parameter("b", 1.0)])
+            .build();
+
+        let reordered = StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::High)
+            .with_metric_specs(vec![metric.clone()])
+            .with_parameters(vec![parameter("b", 1.0), parameter("a", 1.0)])
+            .build();
+        assert_eq!(check_compatibility(&spec, &reordered), Ok(()));
+
+        let changed = StudySpecBuilder::new("RANDOM_SEARCH".to_string(), ObservationNoise::Low)
+            .with_metric_specs(vec![metric.clone()])
+            .with_parameters(vec![parameter("a", 2.0), parameter("b", 1.0)])
+            .build();
+        assert_eq!(
+            check_compatibility(&spec, &changed),
+            Err(Incompatibility::Parameter("a".to_string()))
+        );
+
+        let other_algorithm =
+            StudySpecBuilder::new("GRID_SEARCH".to_string(), ObservationNoise::Low)
+                .with_metric_specs(vec![metric])
+                .with_parameters(vec![parameter("a", 1.0), parameter("b", 1.0)])
+                .build();
+        assert!(matches!(
+            check_compatibility(&spec, &other_algorithm),
+            Err(Incompatibility::Algorithm { .. })
+        ));
+    }
+}
@@ -14,6 +14,10 @@
 
 //! Trial model.
 
+use std::fmt;
+use std::str::FromStr;
+
+use crate::model::{InvalidName, parse_name};
 use crate::{StudyName, Trial};
 
 pub mod add_measurement;
@@ -27,6 +31,9 @@ pub mod optimal;
 pub mod stop;
 pub mod suggest;
 
+/// Pattern of trial names.
+pub const TRIAL_NAME_PATTERN: &str = "owners/{owner}/studies/{study}/trials/{trial}";
+
 /// The name of a trial.
 #[derive(Clone, PartialEq, Debug, Eq, Hash)]
 pub struct TrialName(String);
@@ -35,7 +42,7 @@ impl TrialName {
     /// Creates a new TrialName from its parts.
     pub fn new(owner: String, study: String, trial: String) -> Self {
         TrialName(format!(
-            "owners/{}//studies/{}/trials/{}",
+            "owners/{}/studies/{}/trials/{}",
             owner, study, trial
         ))
     }
@@ -45,6 +52,51 @@ impl TrialName {
         let study: String = study_name.into();
         TrialName(format!("{}/trials/{}", study, trial))
     }
+
+    /// The owner - {owner} in the pattern.
+    pub fn owner(&self) -> &str {
+        self.segment(1)
+    }
+
+    /// The study - {study} in the pattern.
+    pub fn study(&self) -> &str {
+        self.segment(3)
+    }
+
+    /// The trial - {trial} in the pattern.
+    pub fn trial(&self) -> &str {
+        self.segment(5)
+    }
+
+    /// The name of the study of the trial.
+    pub fn study_name(&self) -> StudyName {
+        StudyName::new(self.owner().to_string(), self.study().to_string())
+    }
+
+    /// The name as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn segment(&self, index: usize) -> &str {
+        self.0.split('/').nth(index).unwrap_or_default()
+    }
+}
+
+impl FromStr for TrialName {
+    type Err = InvalidName;
+
+    /// Parses a name matching [TRIAL_NAME_PATTERN].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(s, TRIAL_NAME_PATTERN)?;
+        Ok(TrialName(s.to_string()))
+    }
+}
+
+impl fmt::Display for TrialName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 /// Can be converted to a [TrialName].
@@ -70,3 +122,37 @@ impl From<&TrialName> for String {
         trial_name.0.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TrialName;
+    use crate::StudyName;
+
+    #[test]
+    fn it_parses_trial_names() {
+        let trial_name: TrialName = "owners/owner/studies/study/trials/3".parse().unwrap();
+
+        assert_eq!(trial_name.owner(), "owner");
+        assert_eq!(trial_name.study(), "study");
+        assert_eq!(trial_name.trial(), "3");
+        assert_eq!(
+            trial_name.study_name(),
+            StudyName::new("owner".to_string(), "study".to_string())
+        );
+        assert_eq!(
+            trial_name,
+            TrialName::new("owner".to_string(), "study".to_string(), "3".to_string())
+        );
+        assert_eq!(
+            trial_name,
+            TrialName::from_study(&trial_name.study_name(), "3".to_string())
+        );
+
+        assert!(
+            "owners/owner/studies/study/trials/"
+                .parse::<TrialName>()
+                .is_err()
+        );
+        assert!("owners/owner/studies/study".parse::<TrialName>().is_err());
+    }
+}
@@ -0,0 +1,628 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-process [Optimizer] with local policies - no service needed.
+//!
+//! A [LocalOptimizer] keeps the trials of a study in memory and asks a [Policy] for
+//! the parameters of new trials. Conditional parameters are only set when their
+//! parent value matches their condition.
+
+use std::time::SystemTime;
+
+use prost_types::Value;
+use prost_types::value::Kind;
+
+use crate::analysis::{XorShift, unscale};
+use crate::optimizer::{Error, Optimizer};
+use crate::vizier::study_spec::ParameterSpec;
+use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::ParentValueCondition;
+use crate::vizier::study_spec::parameter_spec::{ParameterValueSpec, ScaleType};
+use crate::vizier::{StudySpec, trial};
+use crate::{Measurement, StudyName, Trial, TrialName};
+
+/// Suggests the parameters of new trials.
+pub trait Policy {
+    /// The parameters of the next trial given the trials of the study so far, or
+    /// `None` when the search space is exhausted.
+    fn suggest(
+        &mut self,
+        study_spec: &StudySpec,
+        trials: &[Trial],
+    ) -> Result<Option<Vec<trial::Parameter>>, Error>;
+}
+
+/// Uniform random sampling of the search space - `RANDOM_SEARCH`.
+///
+/// Double and integer parameters are sampled uniformly in their scaled range. The
+/// parameters of a trial only depend on the seed and on its position in the study, so
+/// a restarted study does not repeat earlier suggestions.
+#[derive(Clone, Debug)]
+pub struct RandomSearch {
+    seed: u64,
+}
+
+impl RandomSearch {
+    /// Creates a new [RandomSearch] - the same seed gives the same suggestions.
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn sample(
+        rng: &mut XorShift,
+        specs: &[&ParameterSpec],
+        out: &mut Vec<trial::Parameter>,
+    ) -> Result<(), Error> {
+        for spec in specs {
+            let value = Self::sample_value(rng, spec)?;
+            let children = active_children(spec, &value);
+            out.push(parameter(spec, value));
+            Self::sample(rng, &children, out)?;
+        }
+        Ok(())
+    }
+
+    fn sample_value(rng: &mut XorShift, spec: &ParameterSpec) -> Result<Kind, Error> {
+        let scale_type = spec.scale_type();
+        match &spec.parameter_value_spec {
+            None => Err(Error::MissingValueSpec(spec.parameter_id.clone())),
+            Some(ParameterValueSpec::DoubleValueSpec(s)) => {
+                check_range(spec, s.min_value <= s.max_value)?;
+                let u = rng.next_f64();
+                Ok(Kind::NumberValue(unscale(
+                    u,
+                    s.min_value,
+                    s.max_value,
+                    scale_type,
+                )))
+            }
+            Some(ParameterValueSpec::IntegerValueSpec(s)) => {
+                check_range(spec, s.min_value <= s.max_value)?;
+                let value = match scale_type {
+                    ScaleType::UnitLogScale | ScaleType::UnitReverseLogScale => {
+                        let u = rng.next_f64();
+                        unscale(u, s.min_value as f64, s.max_value as f64, scale_type).round()
+                    }
+                    _ => {
+                        let span = s.max_value.abs_diff(s.min_value);
+                        match span.checked_add(1).and_then(|n| usize::try_from(n).ok()) {
+                            Some(n) => {
+                                let offset = rng.below(n) as u64;
+                                s.min_value.saturating_add_unsigned(offset) as f64
+                            }
+                            // too many values to count - sample the scaled range
+                            None => {
+                                let u = rng.next_f64();
+                                unscale(u, s.min_value as f64, s.max_value as f64, scale_type)
+                                    .round()
+                            }
+                        }
+                    }
+                };
+                Ok(Kind::NumberValue(value))
+            }
+            Some(ParameterValueSpec::DiscreteValueSpec(s)) => {
+                check_range(spec, !s.values.is_empty())?;
+                Ok(Kind::NumberValue(s.values[rng.below(s.values.len())]))
+            }
+            Some(ParameterValueSpec::CategoricalValueSpec(s)) => {
+                check_range(spec, !s.values.is_empty())?;
+                let value = s.values[rng.below(s.values.len())].clone();
+                Ok(Kind::StringValue(value))
+            }
+        }
+    }
+}
+
+impl Policy for RandomSearch {
+    fn suggest(
+        &mut self,
+        study_spec: &StudySpec,
+        trials: &[Trial],
+    ) -> Result<Option<Vec<trial::Parameter>>, Error> {
+        let specs: Vec<&ParameterSpec> = study_spec.parameters.iter().collect();
+
+        let position = trials.len() as u64 + 1;
+        let mut rng = XorShift::new(self.seed ^ position.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        rng.next_u64();
+
+        let mut parameters = vec![];
+        Self::sample(&mut rng, &specs, &mut parameters)?;
+        Ok(Some(parameters))
+    }
+}
+
+/// Exhaustive search of a grid of the search space - `GRID_SEARCH`.
+///
+/// Double parameters take `resolution` evenly spaced values in their scaled range,
+/// integer parameters all their values or `resolution` of them. Discrete and
+/// categorical parameters take all their values. The grid point of a trial is its
+/// position in the study, so a study is never suggested the same point twice.
+#[derive(Clone, Debug)]
+pub struct GridSearch {
+    resolution: usize,
+}
+
+impl GridSearch {
+    /// Creates a new [GridSearch] with `resolution` values per continuous parameter.
+    pub fn new(resolution: usize) -> Self {
+        Self {
+            resolution: resolution.max(1),
+        }
+    }
+
+    /// Number of grid points of `specs` - saturates at `usize::MAX`.
+    fn count(&self, specs: &[&ParameterSpec]) -> Result<usize, Error> {
+        let mut count: usize = 1;
+        for spec in specs {
+            count = count.saturating_mul(self.options(spec)?);
+        }
+        Ok(count)
+    }
+
+    /// Number of grid points of `spec` and its conditional parameters.
+    fn options(&self, spec: &ParameterSpec) -> Result<usize, Error> {
+        let mut options: usize = 0;
+        for value in self.values(spec)? {
+            options = options.saturating_add(self.count(&active_children(spec, &value))?);
+        }
+        Ok(options)
+    }
+
+    /// Adds the parameters of the `index`-th grid point of `specs` to `out` - the last
+    /// parameter varies fastest. The grid is never materialized, so large grids are
+    /// fine.
+    fn point(
+        &self,
+        specs: &[&ParameterSpec],
+        mut index: usize,
+        out: &mut Vec<trial::Parameter>,
+    ) -> Result<(), Error> {
+        let mut indices = vec![0; specs.len()];
+        for (i, spec) in specs.iter().enumerate().rev() {
+            let options = self.options(spec)?;
+            indices[i] = index % options;
+            index /= options;
+        }
+
+        for (spec, mut index) in specs.iter().zip(indices) {
+            for value in self.values(spec)? {
+                let children = active_children(spec, &value);
+                let count = self.count(&children)?;
+                if index < count {
+                    out.push(parameter(spec, value));
+                    self.point(&children, index, out)?;
+                    break;
+                }
+                index -= count;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn values(&self, spec: &ParameterSpec) -> Result<Vec<Kind>, Error> {
+        let scale_type = spec.scale_type();
+        let steps = |min: f64, max: f64| -> Vec<f64> {
+            if self.resolution == 1 || max <= min {
+                return vec![unscale(0.5, min, max, scale_type)];
+            }
+            (0..self.resolution)
+                .map(|i| i as f64 / (self.resolution - 1) as f64)
+                .map(|u| unscale(u, min, max, scale_type))
+                .collect()
+        };
+
+        let values: Vec<Kind> = match &spec.parameter_value_spec {
+            None => return Err(Error::MissingValueSpec(spec.parameter_id.clone())),
+            Some(ParameterValueSpec::DoubleValueSpec(s)) => {
+                check_range(spec, s.min_value <= s.max_value)?;
+                steps(s.min_value, s.max_value)
+                    .into_iter()
+                    .map(Kind::NumberValue)
+                    .collect()
+            }
+            Some(ParameterValueSpec::IntegerValueSpec(s)) => {
+                check_range(spec, s.min_value <= s.max_value)?;
+                let mut values: Vec<i64> =
+                    if s.max_value.abs_diff(s.min_value) < self.resolution as u64 {
+                        (s.min_value..=s.max_value).collect()
+                    } else {
+                        steps(s.min_value as f64, s.max_value as f64)
+                            .into_iter()
+                            .map(|v| v.round() as i64)
+                            .collect()
+                    };
+                values.dedup();
+                values
+                    .into_iter()
+                    .map(|v| Kind::NumberValue(v as f64))
+                    .collect()
+            }
+            Some(ParameterValueSpec::DiscreteValueSpec(s)) => {
+                s.values.iter().copied().map(Kind::NumberValue).collect()
+            }
+            Some(ParameterValueSpec::CategoricalValueSpec(s)) => {
+                s.values.iter().cloned().map(Kind::StringValue).collect()
+            }
+        };
+
+        check_range(spec, !values.is_empty())?;
+        Ok(values)
+    }
+}
+
+impl Policy for GridSearch {
+    fn suggest(
+        &mut self,
+        study_spec: &StudySpec,
+        trials: &[Trial],
+    ) -> Result<Option<Vec<trial::Parameter>>, Error> {
+        let specs: Vec<&ParameterSpec> = study_spec.parameters.iter().collect();
+        if trials.len() >= self.count(&specs)? {
+            return Ok(None);
+        }
+
+        let mut parameters = vec![];
+        self.point(&specs, trials.len(), &mut parameters)?;
+        Ok(Some(parameters))
+    }
+}
+
+/// Resolution of the [GridSearch] of [Algorithm::from_name].
+pub const DEFAULT_GRID_RESOLUTION: usize = 10;
+
+/// The local [Policy] of an `algorithm` of a [StudySpec].
+#[derive(Clone, Debug)]
+pub enum Algorithm {
+    /// `RANDOM_SEARCH`.
+    RandomSearch(RandomSearch),
+    /// `GRID_SEARCH`.
+    GridSearch(GridSearch),
+}
+
+impl Algorithm {
+    /// The policy of `algorithm` seeded with `seed` - fails with
+    /// [Error::UnsupportedAlgorithm] for the algorithms only available on the service.
+    pub fn from_name(algorithm: &str, seed: u64) -> Result<Self, Error> {
+        match algorithm {
+            "RANDOM_SEARCH" => Ok(Algorithm::RandomSearch(RandomSearch::new(seed))),
+            "GRID_SEARCH" => Ok(Algorithm::GridSearch(GridSearch::new(
+                DEFAULT_GRID_RESOLUTION,
+            ))),
+            _ => Err(Error::UnsupportedAlgorithm(algorithm.to_string())),
+        }
+    }
+}
+
+impl Policy for Algorithm {
+    fn suggest(
+        &mut self,
+        study_spec: &StudySpec,
+        trials: &[Trial],
+    ) -> Result<Option<Vec<trial::Parameter>>, Error> {
+        match self {
+            Algorithm::RandomSearch(policy) => policy.suggest(study_spec, trials),
+            Algorithm::GridSearch(policy) => policy.suggest(study_spec, trials),
+        }
+    }
+}
+
+/// [Optimizer] keeping the trials of a study in memory.
+///
+/// Like the service, ACTIVE trials of the `client_id` are handed out again by
+/// [Optimizer::ask] until they are told their measurement.
+#[derive(Clone, Debug)]
+pub struct LocalOptimizer<P> {
+    study_name: StudyName,
+    study_spec: StudySpec,
+    client_id: String,
+    policy: P,
+    trials: Vec<Trial>,
+}
+
+impl<P: Policy> LocalOptimizer<P> {
+    /// Creates a new [LocalOptimizer] for a study without trials.
+    pub fn new(study_spec: StudySpec, policy: P) -> Self {
+        Self {
+            study_name: StudyName::new("local".to_string(), "local".to_string()),
+            study_spec,
+            client_id: "local".to_string(),
+            policy,
+            trials: vec![],
+        }
+    }
+
+    /// Sets the name of the study - the prefix of the names of its trials.
+    pub fn with_study_name(mut self, study_name: StudyName) -> Self {
+        self.study_name = study_name;
+        self
+    }
+
+    /// Sets the trials of the study - e.g. to resume a study.
+    pub fn with_trials(mut self, trials: Vec<Trial>) -> Self {
+        self.trials = trials;
+        self
+    }
+
+    /// Sets the `client_id` of the suggested trials.
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    /// The name of the study.
+    pub fn study_name(&self) -> &StudyName {
+        &self.study_name
+    }
+
+    /// The spec of the study.
+    pub fn study_spec(&self) -> &StudySpec {
+        &self.study_spec
+    }
+
+    /// The trials of the study, in creation order.
+    pub fn trials(&self) -> &[Trial] {
+        &self.trials
+    }
+
+    /// Consumes the optimizer and returns the trials of the study.
+    pub fn into_trials(self) -> Vec<Trial> {
+        self.trials
+    }
+
+    fn next_id(&self) -> u64 {
+        self.trials
+            .iter()
+            .filter_map(|t| t.id.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+}
+
+impl<P: Policy> Optimizer for LocalOptimizer<P> {
+    async fn ask(&mut self, count: usize) -> Result<Vec<Trial>, crate::Error> {
+        let mut trials: Vec<Trial> = self
+            .trials
+            .iter()
+            .filter(|t| t.state() == trial::State::Active && t.client_id == self.client_id)
+            .take(count)
+            .cloned()
+            .collect();
+
+        while trials.len() < count {
+            let Some(parameters) = self.policy.suggest(&self.study_spec, &self.trials)? else {
+                break;
+            };
+
+            let id = self.next_id().to_string();
+            let trial = Trial {
+                name: TrialName::from_study(&self.study_name, id.clone()).into(),
+                id,
+                state: trial::State::Active as i32,
+                parameters,
+                client_id: self.client_id.clone(),
+                start_time: Some(SystemTime::now().into()),
+                ..Default::default()
+            };
+
+            self.trials.push(trial.clone());
+            trials.push(trial);
+        }
+
+        Ok(trials)
+    }
+
+    async fn tell(
+        &mut self,
+        trial: &Trial,
+        measurement: Measurement,
+    ) -> Result<Trial, crate::Error> {
+        let known = self
+            .trials
+            .iter_mut()
+            .find(|t| t.name == trial.name)
+            .ok_or_else(|| Error::UnknownTrial(trial.name.clone()))?;
+
+        if known.state() != trial::State::Active {
+            return Err(Error::TrialNotActive(trial.name.clone()).into());
+        }
+
+        known.state = trial::State::Succeeded as i32;
+        known.final_measurement = Some(measurement);
+        known.end_time = Some(SystemTime::now().into());
+
+        Ok(known.clone())
+    }
+}
+
+fn check_range(spec: &ParameterSpec, ok: bool) -> Result<(), Error> {
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::EmptyDomain(spec.parameter_id.clone()))
+    }
+}
+
+fn parameter(spec: &ParameterSpec, kind: Kind) -> trial::Parameter {
+    trial::Parameter {
+        parameter_id: spec.parameter_id.clone(),
+        value: Some(Value { kind: Some(kind) }),
+    }
+}
+
+/// The conditional parameters of `spec` whose condition matches `value`.
+fn active_children<'a>(spec: &'a ParameterSpec, value: &Kind) -> Vec<&'a ParameterSpec> {
+    spec.conditional_parameter_specs
+        .iter()
+        .filter(|c| match (&c.parent_value_condition, value) {
+            (None, _) => true,
+            (Some(ParentValueCondition::ParentDiscreteValues(c)), Kind::NumberValue(v)) => {
+                c.values.contains(v)
+            }
+            (Some(ParentValueCondition::ParentIntValues(c)), Kind::NumberValue(v)) => {
+                c.values.contains(&(*v as i64))
+            }
+            (Some(ParentValueCondition::ParentCategoricalValues(c)), Kind::StringValue(v)) => {
+                c.values.contains(v)
+            }
+            _ => false,
+        })
+        .filter_map(|c| c.parameter_spec.as_ref())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::value::Kind;
+
+    use super::{Algorithm, GridSearch, LocalOptimizer, RandomSearch};
+    use crate::optimizer::testing::{measurement, study_spec};
+    use crate::optimizer::{Error, Optimizer, run};
+    use crate::vizier::study_spec::ParameterSpec;
+    use crate::vizier::study_spec::parameter_spec::{IntegerValueSpec, ParameterValueSpec};
+    use crate::vizier::{StudySpec, Trial, trial};
+
+    fn value<'a>(trial: &'a Trial, parameter_id: &str) -> Option<&'a Kind> {
+        trial
+            .parameters
+            .iter()
+            .find(|p| p.parameter_id == parameter_id)
+            .and_then(|p| p.value.as_ref())
+            .and_then(|v| v.kind.as_ref())
+    }
+
+    #[tokio::test]
+    async fn it_samples_the_search_space() {
+        let mut optimizer = LocalOptimizer::new(study_spec(), RandomSearch::new(7));
+
+        let trials = run(&mut optimizer, 50, |t| {
+            measurement(match value(t, "lr") {
+                Some(Kind::NumberValue(lr)) => *lr,
+                _ => 0.0,
+            })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(trials.len(), 50);
+        for t in &trials {
+            assert_eq!(t.state(), trial::State::Succeeded);
+            let Some(Kind::NumberValue(lr)) = value(t, "lr") else {
+                panic!("missing lr");
+            };
+            assert!((1e-4..=1e-1).contains(lr));
+
+            match value(t, "model") {
+                Some(Kind::StringValue(m)) if m == "tree" => {
+                    let Some(Kind::NumberValue(depth)) = value(t, "depth") else {
+                        panic!("missing depth");
+                    };
+                    assert!((1.0..=3.0).contains(depth));
+                }
+                Some(Kind::StringValue(_)) => assert!(value(t, "depth").is_none()),
+                _ => panic!("missing model"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_exhausts_the_grid() {
+        let mut optimizer = LocalOptimizer::new(study_spec(), GridSearch::new(3));
+
+        let trials = run(&mut optimizer, 100, |_| measurement(1.0))
+            .await
+            .unwrap();
+
+        // 3 learning rates x (linear + 3 depths of tree)
+        assert_eq!(trials.len(), 12);
+        assert!(optimizer.ask(1).await.unwrap().is_empty());
+        for (i, t) in trials.iter().enumerate() {
+            assert!(trials[..i].iter().all(|o| o.parameters != t.parameters));
+        }
+
+        assert!(matches!(
+            Algorithm::from_name("GRID_SEARCH", 0),
+            Ok(Algorithm::GridSearch(_))
+        ));
+        assert_eq!(
+            Algorithm::from_name("GAUSSIAN_PROCESS_BANDIT", 0).unwrap_err(),
+            Error::UnsupportedAlgorithm("GAUSSIAN_PROCESS_BANDIT".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_hands_out_active_trials_again() {
+        let mut optimizer = LocalOptimizer::new(study_spec(), RandomSearch::new(1));
+
+        let first = optimizer.ask(1).await.unwrap();
+        let again = optimizer.ask(2).await.unwrap();
+        assert_eq!(again.len(), 2);
+        assert_eq!(again[0].name, first[0].name);
+        assert_eq!(again[1].id, "2");
+
+        optimizer.tell(&first[0], measurement(1.0)).await.unwrap();
+        assert!(matches!(
+            optimizer.tell(&first[0], measurement(1.0)).await,
+            Err(crate::Error::Optimizer(Error::TrialNotActive(_)))
+        ));
+
+        let unknown = Trial {
+            name: "owners/local/studies/local/trials/42".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            optimizer.tell(&unknown, measurement(1.0)).await,
+            Err(crate::Error::Optimizer(Error::UnknownTrial(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_handles_the_full_integer_range() {
+        let spec = StudySpec {
+            parameters: vec![ParameterSpec {
+                parameter_id: "seed".to_string(),
+                parameter_value_spec: Some(ParameterValueSpec::IntegerValueSpec(
+                    IntegerValueSpec {
+                        min_value: i64::MIN,
+                        max_value: i64::MAX,
+                        default_value: None,
+                    },
+                )),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let mut optimizer = LocalOptimizer::new(spec.clone(), RandomSearch::new(3));
+        let trials = optimizer.ask(10).await.unwrap();
+        assert_eq!(trials.len(), 10);
+        assert!(
+            trials
+                .iter()
+                .all(|t| matches!(value(t, "seed"), Some(Kind::NumberValue(_))))
+        );
+
+        let mut optimizer = LocalOptimizer::new(spec, GridSearch::new(3));
+        let trials = run(&mut optimizer, 10, |_| measurement(1.0)).await.unwrap();
+        let seeds: Vec<f64> = trials
+            .iter()
+            .filter_map(|t| match value(t, "seed") {
+                Some(Kind::NumberValue(v)) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(seeds, vec![i64::MIN as f64, 0.0, i64::MAX as f64]);
+    }
+}
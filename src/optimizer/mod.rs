@@ -0,0 +1,201 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ask-and-tell optimizers - the same experiment code against the service or fully
+//! in-process.
+//!
+//! An [Optimizer] hands out trials with [Optimizer::ask] and is told their final
+//! measurement with [Optimizer::tell]. It is implemented by
+//! [remote::RemoteOptimizer] over a [crate::VizierClient] and by
+//! [local::LocalOptimizer] with local policies, for unit tests and laptops - saved to
+//! local files by `offline::OfflineStudy`.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::Error;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::study::StudyName;
+//! # use oss_vizier::optimizer::Optimizer;
+//! # use oss_vizier::optimizer::local::{LocalOptimizer, RandomSearch};
+//! # use oss_vizier::optimizer::remote::RemoteOptimizer;
+//! # use oss_vizier::vizier::{Measurement, StudySpec, Trial};
+//! # fn train(trial: &Trial) -> Measurement { Measurement::default() }
+//! # async fn example(
+//! #     client: VizierClient<InterceptedChannel>,
+//! #     study_name: StudyName,
+//! #     study_spec: StudySpec,
+//! # ) -> Result<(), Error> {
+//! async fn tune(optimizer: &mut impl Optimizer) -> Result<(), Error> {
+//!     for trial in optimizer.ask(2).await? {
+//!         let measurement = train(&trial);
+//!         optimizer.tell(&trial, measurement).await?;
+//!     }
+//!     Ok(())
+//! }
+//!
+//! tune(&mut RemoteOptimizer::new(client, study_name, "worker".to_string())).await?;
+//! tune(&mut LocalOptimizer::new(study_spec, RandomSearch::new(42))).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+
+use crate::{Measurement, Trial};
+
+pub mod local;
+#[cfg(feature = "serde")]
+pub mod offline;
+pub mod remote;
+
+/// Errors of the local optimizers.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    /// The trial was not handed out by this optimizer.
+    #[error("unknown trial {0}")]
+    UnknownTrial(String),
+    /// The trial has already been completed.
+    #[error("trial {0} is not active")]
+    TrialNotActive(String),
+    /// A parameter spec has no value spec.
+    #[error("parameter {0} has no value spec")]
+    MissingValueSpec(String),
+    /// A parameter spec has an empty or inverted range or no values.
+    #[error("parameter {0} has an empty domain")]
+    EmptyDomain(String),
+    /// The algorithm has no local policy.
+    #[error("unsupported algorithm {0}")]
+    UnsupportedAlgorithm(String),
+}
+
+/// Ask-and-tell interface of an optimizer.
+pub trait Optimizer {
+    /// Asks for up to `count` trials to evaluate.
+    ///
+    /// Fewer trials are returned when the search space is exhausted.
+    fn ask(&mut self, count: usize) -> impl Future<Output = Result<Vec<Trial>, crate::Error>>;
+
+    /// Tells the final measurement of a trial handed out by [Optimizer::ask] and
+    /// returns the completed trial.
+    fn tell(
+        &mut self,
+        trial: &Trial,
+        measurement: Measurement,
+    ) -> impl Future<Output = Result<Trial, crate::Error>>;
+}
+
+/// Evaluates up to `num_trials` trials one at a time with `evaluate` and returns the
+/// completed trials - stops early when the optimizer runs out of suggestions.
+pub async fn run<O, F>(
+    optimizer: &mut O,
+    num_trials: usize,
+    mut evaluate: F,
+) -> Result<Vec<Trial>, crate::Error>
+where
+    O: Optimizer,
+    F: FnMut(&Trial) -> Measurement,
+{
+    let mut completed = Vec::with_capacity(num_trials);
+
+    while completed.len() < num_trials {
+        let trials = optimizer.ask(1).await?;
+        if trials.is_empty() {
+            break;
+        }
+
+        for trial in trials {
+            let measurement = evaluate(&trial);
+            completed.push(optimizer.tell(&trial, measurement).await?);
+        }
+    }
+
+    Ok(completed)
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::vizier::study_spec::ParameterSpec;
+    use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::{
+        CategoricalValueCondition, ParentValueCondition,
+    };
+    use crate::vizier::study_spec::parameter_spec::{
+        CategoricalValueSpec, ConditionalParameterSpec, DoubleValueSpec, IntegerValueSpec,
+        ParameterValueSpec, ScaleType,
+    };
+    use crate::vizier::{Measurement, StudySpec, measurement};
+
+    /// A study with a log-scaled `lr` and a categorical `model` with a conditional
+    /// `depth`.
+    pub(crate) fn study_spec() -> StudySpec {
+        let depth = ParameterSpec {
+            parameter_id: "depth".to_string(),
+            parameter_value_spec: Some(ParameterValueSpec::IntegerValueSpec(IntegerValueSpec {
+                min_value: 1,
+                max_value: 3,
+                default_value: None,
+            })),
+            ..Default::default()
+        };
+
+        StudySpec {
+            parameters: vec![
+                ParameterSpec {
+                    parameter_id: "lr".to_string(),
+                    scale_type: ScaleType::UnitLogScale as i32,
+                    parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(
+                        DoubleValueSpec {
+                            min_value: 1e-4,
+                            max_value: 1e-1,
+                            default_value: None,
+                        },
+                    )),
+                    ..Default::default()
+                },
+                ParameterSpec {
+                    parameter_id: "model".to_string(),
+                    parameter_value_spec: Some(ParameterValueSpec::CategoricalValueSpec(
+                        CategoricalValueSpec {
+                            values: vec!["linear".to_string(), "tree".to_string()],
+                            default_value: None,
+                        },
+                    )),
+                    conditional_parameter_specs: vec![ConditionalParameterSpec {
+                        parameter_spec: Some(depth),
+                        parent_value_condition: Some(
+                            ParentValueCondition::ParentCategoricalValues(
+                                CategoricalValueCondition {
+                                    values: vec!["tree".to_string()],
+                                },
+                            ),
+                        ),
+                    }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// A measurement of metric `m1`.
+    pub(crate) fn measurement(value: f64) -> Measurement {
+        Measurement {
+            elapsed_duration: None,
+            step_count: 1,
+            metrics: vec![measurement::Metric {
+                metric_id: "m1".to_string(),
+                value,
+            }],
+        }
+    }
+}
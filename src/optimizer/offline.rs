@@ -0,0 +1,333 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline studies - a [LocalOptimizer] whose study and trials are saved to local
+//! files, for quick experiments without any server.
+//!
+//! The study is saved after every [Optimizer::ask] and [Optimizer::tell], so an
+//! experiment can be restarted where it stopped: ACTIVE trials of the `client_id` are
+//! handed out again first. A finished study can be uploaded to a server with
+//! [OfflineStudy::upload].
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::optimizer::local::RandomSearch;
+//! # use oss_vizier::optimizer::offline::{OfflineStudy, Storage};
+//! # use oss_vizier::optimizer::run;
+//! # use oss_vizier::vizier::{Measurement, StudySpec, Trial};
+//! # fn train(trial: &Trial) -> Measurement { Measurement::default() }
+//! # async fn example(mut client: VizierClient<InterceptedChannel>, study_spec: StudySpec) -> Result<(), oss_vizier::Error> {
+//! let storage = Storage::Directory("studies/mnist".into());
+//! let mut study = OfflineStudy::open(storage, "mnist", study_spec, RandomSearch::new(42))?;
+//!
+//! run(&mut study, 20, |trial| train(trial)).await?;
+//!
+//! study.upload(&mut client, None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use prost::bytes::Bytes;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tonic::codegen::{Body, StdError};
+
+use crate::optimizer::Optimizer;
+use crate::optimizer::local::{LocalOptimizer, Policy};
+use crate::snapshot::{self, ImportReport, StudySnapshot};
+use crate::vizier::{Study, StudySpec, study};
+use crate::{Error, Measurement, StudyName, Trial, VizierClient};
+
+/// Where an [OfflineStudy] is saved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Storage {
+    /// A single [StudySnapshot] JSON file - rewritten on every change.
+    File(PathBuf),
+    /// A directory with the study in `study.json` and each trial in
+    /// `trials/{id}.json` - only the changed trials are rewritten.
+    Directory(PathBuf),
+}
+
+impl Storage {
+    fn exists(&self) -> bool {
+        match self {
+            Storage::File(path) => path.exists(),
+            Storage::Directory(dir) => dir.join("study.json").exists(),
+        }
+    }
+
+    fn load(&self) -> Result<(Study, Vec<Trial>), snapshot::Error> {
+        match self {
+            Storage::File(path) => {
+                let snapshot = StudySnapshot::load(path)?;
+                Ok((snapshot.study, snapshot.trials))
+            }
+            Storage::Directory(dir) => {
+                let study = read_json(&dir.join("study.json"))?;
+
+                let mut trials: Vec<Trial> = vec![];
+                for entry in fs::read_dir(dir.join("trials"))? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|e| e == "json") {
+                        trials.push(read_json(&path)?);
+                    }
+                }
+                trials.sort_by_key(|t| t.id.parse::<u64>().unwrap_or(u64::MAX));
+
+                Ok((study, trials))
+            }
+        }
+    }
+
+    /// Saves the study and `changed` trials - all the `trials` for a single file.
+    fn save(
+        &self,
+        study: &Study,
+        trials: &[Trial],
+        changed: &[Trial],
+    ) -> Result<(), snapshot::Error> {
+        match self {
+            Storage::File(path) => {
+                write_json(path, &StudySnapshot::new(study.clone(), trials.to_vec()))
+            }
+            Storage::Directory(dir) => {
+                fs::create_dir_all(dir.join("trials"))?;
+                write_json(&dir.join("study.json"), study)?;
+                for trial in changed {
+                    write_json(
+                        &dir.join("trials").join(format!("{}.json", trial.id)),
+                        trial,
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// In-process study saved to local files - see the [module](self) documentation.
+pub struct OfflineStudy<P> {
+    storage: Storage,
+    study: Study,
+    optimizer: LocalOptimizer<P>,
+}
+
+impl<P: Policy> OfflineStudy<P> {
+    /// Opens the study saved in `storage` or creates it with `study_spec`.
+    ///
+    /// Fails with [Error::IncompatibleStudySpec] if the saved study has a spec that is
+    /// not compatible with `study_spec` - see [crate::study::spec::check_compatibility].
+    pub fn open(
+        storage: Storage,
+        display_name: impl Into<String>,
+        study_spec: StudySpec,
+        policy: P,
+    ) -> Result<Self, Error> {
+        let (study, trials) = if storage.exists() {
+            let (study, trials) = storage.load()?;
+            let existing_spec = study.study_spec.clone().unwrap_or_default();
+            crate::study::spec::check_compatibility(&existing_spec, &study_spec).map_err(
+                |reason| Error::IncompatibleStudySpec {
+                    study: study.name.clone(),
+                    reason,
+                },
+            )?;
+            (study, trials)
+        } else {
+            let display_name = display_name.into();
+            let study = Study {
+                name: StudyName::new("local".to_string(), display_name.clone()).to_string(),
+                display_name,
+                study_spec: Some(study_spec),
+                state: study::State::Active as i32,
+                create_time: Some(SystemTime::now().into()),
+                ..Default::default()
+            };
+            storage.save(&study, &[], &[])?;
+            (study, vec![])
+        };
+
+        let study_name = crate::study::ToStudyName::to_study_name(&study);
+        let optimizer = LocalOptimizer::new(study.study_spec.clone().unwrap_or_default(), policy)
+            .with_study_name(study_name)
+            .with_trials(trials);
+
+        Ok(Self {
+            storage,
+            study,
+            optimizer,
+        })
+    }
+
+    /// Sets the `client_id` of the suggested trials.
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.optimizer = self.optimizer.with_client_id(client_id);
+        self
+    }
+
+    /// The study.
+    pub fn study(&self) -> &Study {
+        &self.study
+    }
+
+    /// The trials of the study, in creation order.
+    pub fn trials(&self) -> &[Trial] {
+        self.optimizer.trials()
+    }
+
+    /// A [StudySnapshot] of the study and its trials.
+    pub fn snapshot(&self) -> StudySnapshot {
+        StudySnapshot::new(self.study.clone(), self.trials().to_vec())
+    }
+
+    /// Uploads the study and its trials to the service, under the display name of the
+    /// study or `display_name` - see [VizierClient::import_study].
+    ///
+    /// Fails with [Error::IncompatibleStudySpec] if a study with the same display name
+    /// has a different search space or metrics.
+    pub async fn upload<T>(
+        &self,
+        client: &mut VizierClient<T>,
+        display_name: Option<String>,
+    ) -> Result<ImportReport, Error>
+    where
+        T: tonic::client::GrpcService<tonic::body::Body> + Clone,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        client.import_study(&self.snapshot(), display_name).await
+    }
+
+    fn save(&self, changed: &[Trial]) -> Result<(), Error> {
+        Ok(self.storage.save(&self.study, self.trials(), changed)?)
+    }
+}
+
+impl<P: Policy> Optimizer for OfflineStudy<P> {
+    async fn ask(&mut self, count: usize) -> Result<Vec<Trial>, Error> {
+        let trials = self.optimizer.ask(count).await?;
+        self.save(&trials)?;
+        Ok(trials)
+    }
+
+    async fn tell(&mut self, trial: &Trial, measurement: Measurement) -> Result<Trial, Error> {
+        let trial = self.optimizer.tell(trial, measurement).await?;
+        self.save(std::slice::from_ref(&trial))?;
+        Ok(trial)
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, snapshot::Error> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Writes `value` to a temporary file renamed to `path`, so an interrupted write does
+/// not corrupt the saved study.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), snapshot::Error> {
+    let tmp = path.with_extension("json.tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{OfflineStudy, Storage};
+    use crate::Error;
+    use crate::optimizer::Optimizer;
+    use crate::optimizer::local::RandomSearch;
+    use crate::optimizer::testing::{self, measurement};
+    use crate::vizier::{StudySpec, trial};
+
+    fn study_spec(algorithm: &str) -> StudySpec {
+        StudySpec {
+            algorithm: algorithm.to_string(),
+            ..testing::study_spec()
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("oss-vizier-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    async fn it_resumes(storage: Storage) {
+        let open = || {
+            OfflineStudy::open(
+                storage.clone(),
+                "offline",
+                study_spec("RANDOM_SEARCH"),
+                RandomSearch::new(3),
+            )
+            .unwrap()
+        };
+
+        let mut study = open();
+        let trials = study.ask(2).await.unwrap();
+        study.tell(&trials[0], measurement(1.0)).await.unwrap();
+        drop(study);
+
+        // the restarted study has both trials and hands out the ACTIVE one again
+        let mut study = open();
+        assert_eq!(study.trials().len(), 2);
+        assert_eq!(study.trials()[0].state(), trial::State::Succeeded);
+
+        let resumed = study.ask(2).await.unwrap();
+        assert_eq!(resumed[0].name, trials[1].name);
+        assert_eq!(resumed[1].id, "3");
+        assert_ne!(resumed[1].parameters, trials[0].parameters);
+        assert_eq!(study.snapshot().trials.len(), 3);
+
+        let incompatible = OfflineStudy::open(
+            storage,
+            "offline",
+            study_spec("GRID_SEARCH"),
+            RandomSearch::new(3),
+        );
+        assert!(matches!(
+            incompatible,
+            Err(Error::IncompatibleStudySpec { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_resumes_a_study_saved_in_a_directory() {
+        let path = temp_path("directory");
+        it_resumes(Storage::Directory(path.clone())).await;
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_resumes_a_study_saved_in_a_file() {
+        let path = temp_path("file.json");
+        it_resumes(Storage::File(path.clone())).await;
+        std::fs::remove_file(path).unwrap();
+    }
+}
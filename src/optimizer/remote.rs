@@ -0,0 +1,87 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Optimizer] over the OSS Vizier service.
+
+use prost::bytes::Bytes;
+use tonic::codegen::{Body, StdError};
+
+use crate::optimizer::Optimizer;
+use crate::trial::ToTrialName;
+use crate::trial::complete::FinalMeasurementOrReason;
+use crate::{Error, Measurement, StudyName, Trial, VizierClient};
+
+/// [Optimizer] suggesting the trials of a study of the service on behalf of a
+/// worker identified by its `client_id`.
+pub struct RemoteOptimizer<T> {
+    client: VizierClient<T>,
+    study_name: StudyName,
+    client_id: String,
+}
+
+impl<T> RemoteOptimizer<T> {
+    /// Creates a new [RemoteOptimizer] for the study `study_name`.
+    pub fn new(client: VizierClient<T>, study_name: StudyName, client_id: String) -> Self {
+        Self {
+            client,
+            study_name,
+            client_id,
+        }
+    }
+
+    /// The name of the study.
+    pub fn study_name(&self) -> &StudyName {
+        &self.study_name
+    }
+
+    /// The `client_id` of the worker.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// Consumes the optimizer and returns the client.
+    pub fn into_client(self) -> VizierClient<T> {
+        self.client
+    }
+}
+
+impl<T> Optimizer for RemoteOptimizer<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Suggests trials - ACTIVE trials of the `client_id` are returned first by the
+    /// service.
+    async fn ask(&mut self, count: usize) -> Result<Vec<Trial>, Error> {
+        let request = self.client.mk_suggest_trials_request(
+            self.study_name.clone(),
+            i32::try_from(count).unwrap_or(i32::MAX),
+            self.client_id.clone(),
+        );
+
+        Ok(self.client.suggest_trials(request).await?.trials)
+    }
+
+    /// Completes the trial with its final measurement.
+    async fn tell(&mut self, trial: &Trial, measurement: Measurement) -> Result<Trial, Error> {
+        let request = self.client.mk_complete_trial_request(
+            trial.to_trial_name(),
+            FinalMeasurementOrReason::FinalMeasurement(measurement),
+        );
+
+        self.client.complete_trial(request).await
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Auto-paginating streams over studies and trials.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::pagination::collect_all;
+//! # use oss_vizier::vizier::Study;
+//! # async fn example(client: VizierClient<InterceptedChannel>) -> Result<(), oss_vizier::Error> {
+//! let studies = client.list_studies_stream(10);
+//! let studies: Vec<Study> = collect_all(studies).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+use std::future::Future;
+
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use prost::bytes::Bytes;
+use tonic::codegen::{Body, StdError};
+
+use crate::{Error, StudyName, Trial, VizierClient, vizier};
+
+/// Collects all the items of a paginated stream, stopping at the first error.
+pub async fn collect_all<I>(stream: impl Stream<Item = Result<I, Error>>) -> Result<Vec<I>, Error> {
+    stream.try_collect().await
+}
+
+/// Turns a `fetch` function returning a page of items and the token of the next page
+/// into a stream of items.
+///
+/// `fetch` takes the client by value and gives it back so the stream owns it. The
+/// stream ends with [Error::RepeatedPageToken] if the service hands out a page token
+/// twice, instead of looping forever.
+fn paginate<T, I, F, Fut>(client: VizierClient<T>, fetch: F) -> impl Stream<Item = Result<I, Error>>
+where
+    F: Fn(VizierClient<T>, String) -> Fut,
+    Fut: Future<Output = (VizierClient<T>, Result<(Vec<I>, String), Error>)>,
+{
+    // the state is the client, the token of the next page and the tokens already
+    // followed, None when done
+    let start = Some((client, String::new(), HashSet::new()));
+    stream::unfold(start, move |state| {
+        let next = state.map(|(client, page_token, seen)| (fetch(client, page_token), seen));
+
+        async move {
+            let (page, mut seen) = next?;
+            let (client, page) = page.await;
+
+            match page {
+                Ok((items, next_page_token)) => {
+                    let mut items: Vec<Result<I, Error>> = items.into_iter().map(Ok).collect();
+                    let next_state = if next_page_token.is_empty() {
+                        None
+                    } else if !seen.insert(next_page_token.clone()) {
+                        items.push(Err(Error::RepeatedPageToken(next_page_token)));
+                        None
+                    } else {
+                        Some((client, next_page_token, seen))
+                    };
+                    Some((stream::iter(items), next_state))
+                }
+                Err(e) => Some((stream::iter(vec![Err(e)]), None)),
+            }
+        }
+    })
+    .flatten()
+}
+
+impl<T> VizierClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Streams the studies of the owner, fetching pages of `page_size` studies - 0 for
+    /// the service default.
+    pub fn list_studies_stream(
+        &self,
+        page_size: i32,
+    ) -> impl Stream<Item = Result<vizier::Study, Error>> + use<T> {
+        paginate(self.clone(), move |mut client, page_token| async move {
+            let request = client
+                .mk_list_studies_request_builder()
+                .with_page_size(page_size)
+                .with_page_token(page_token)
+                .build();
+
+            let page = client
+                .list_studies(request)
+                .await
+                .map(|resp| (resp.studies, resp.next_page_token));
+
+            (client, page)
+        })
+    }
+
+    /// Streams the trials of a study, fetching pages of `page_size` trials - 0 for the
+    /// service default.
+    pub fn list_trials_stream(
+        &self,
+        study_name: StudyName,
+        page_size: i32,
+    ) -> impl Stream<Item = Result<Trial, Error>> + use<T> {
+        paginate(self.clone(), move |mut client, page_token| {
+            let study_name = study_name.clone();
+
+            async move {
+                let request = client
+                    .mk_list_trials_request_builder(study_name)
+                    .with_page_size(page_size)
+                    .with_page_token(page_token)
+                    .build();
+
+                let page = client
+                    .list_trials(request)
+                    .await
+                    .map(|resp| (resp.trials, resp.next_page_token));
+
+                (client, page)
+            }
+        })
+    }
+
+    /// Streams the optimal trials of a study, fetching pages of `page_size` trials - 0
+    /// for the service default.
+    pub fn list_optimal_trials_stream(
+        &self,
+        study_name: StudyName,
+        page_size: i32,
+    ) -> impl Stream<Item = Result<Trial, Error>> + use<T> {
+        paginate(self.clone(), move |mut client, page_token| {
+            let study_name = study_name.clone();
+
+            async move {
+                let request = client
+                    .mk_list_optimal_trials_request_builder(study_name)
+                    .with_page_size(page_size)
+                    .with_page_token(page_token)
+                    .build();
+
+                let page = client
+                    .list_optimal_trials(request)
+                    .await
+                    .map(|resp| (resp.optimal_trials, resp.next_page_token));
+
+                (client, page)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tonic::Code;
+
+    use super::{collect_all, paginate};
+    use crate::Error;
+    use crate::builder::ClientBuilder;
+
+    #[tokio::test]
+    async fn it_follows_page_tokens() {
+        let client = ClientBuilder::new("http://localhost:28080", "owner")
+            .connect_lazy()
+            .unwrap();
+
+        let items = paginate(client.clone(), |client, page_token| async move {
+            let page = match page_token.as_str() {
+                "" => Ok((vec![1, 2], "p2".to_string())),
+                "p2" => Ok((vec![], "p3".to_string())),
+                "p3" => Ok((vec![3], String::new())),
+                _ => unreachable!(),
+            };
+            (client, page)
+        });
+
+        assert_eq!(collect_all(items).await.unwrap(), vec![1, 2, 3]);
+
+        let items = paginate(client, |client, page_token| async move {
+            let page = match page_token.as_str() {
+                "" => Ok((vec![1], "p2".to_string())),
+                _ => Err(Error::Status(tonic::Status::new(Code::Unavailable, "down"))),
+            };
+            (client, page)
+        });
+
+        let items: Vec<_> = items.collect().await;
+        assert_eq!(items.len(), 2);
+        assert!(items[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn it_stops_on_a_repeated_page_token() {
+        let client = ClientBuilder::new("http://localhost:28080", "owner")
+            .connect_lazy()
+            .unwrap();
+
+        let items = paginate(client, |client, page_token| async move {
+            let page = match page_token.as_str() {
+                "" => Ok((vec![1], "p2".to_string())),
+                "p2" => Ok((vec![2], "p3".to_string())),
+                _ => Ok((vec![3], "p2".to_string())),
+            };
+            (client, page)
+        });
+
+        let items: Vec<_> = items.collect().await;
+        assert_eq!(items.len(), 4);
+        assert!(matches!(&items[3], Err(Error::RepeatedPageToken(t)) if t == "p2"));
+    }
+}
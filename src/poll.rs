@@ -0,0 +1,277 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Polling policy for long-running operations.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::time::{Instant, sleep, sleep_until};
+use tokio_util::sync::CancellationToken;
+
+use crate::Error;
+
+/// How to poll a long-running operation.
+///
+/// Delays between two polls start at `initial_delay` and are multiplied by
+/// `multiplier` after each poll, up to `max_delay`. Each delay is randomly shifted by up
+/// to `jitter` (a fraction of the delay) to avoid workers polling in lockstep.
+///
+/// When a `deadline` is set, waiting for longer than it fails with [Error::Timeout].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    jitter: f64,
+    deadline: Option<Duration>,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.1,
+            deadline: None,
+        }
+    }
+}
+
+impl PollPolicy {
+    /// Creates a new [PollPolicy] with the default settings: 100ms initial delay, doubled
+    /// up to 5s, 10% jitter and no deadline.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the delay before the second poll.
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Sets the maximum delay between two polls.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets the factor applied to the delay after each poll. Values below 1 or NaN are
+    /// treated as 1 and infinity as [f64::MAX] - the delay then jumps to `max_delay`.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = if multiplier.is_nan() {
+            1.0
+        } else {
+            multiplier.clamp(1.0, f64::MAX)
+        };
+        self
+    }
+
+    /// Sets the jitter as a fraction of the delay - clamped to `[0, 1]`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the overall deadline after which waiting fails with [Error::Timeout].
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// The overall deadline, if any.
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Starts a new sequence of delays following this policy.
+    pub fn start(&self) -> Poller {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64)
+            .unwrap_or_default();
+
+        Poller {
+            policy: self.clone(),
+            next_delay: self.initial_delay,
+            // a deadline too far in the future is no deadline
+            deadline: self.deadline.and_then(|d| Instant::now().checked_add(d)),
+            rng_state: seed | 1,
+        }
+    }
+}
+
+/// A running sequence of delays - see [PollPolicy::start].
+#[derive(Debug)]
+pub struct Poller {
+    policy: PollPolicy,
+    next_delay: Duration,
+    deadline: Option<Instant>,
+    rng_state: u64,
+}
+
+impl Poller {
+    /// Returns the next delay, with jitter, and moves to the following one.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next_delay;
+
+        let max_delay = self.policy.max_delay;
+        self.next_delay = Duration::try_from_secs_f64(delay.as_secs_f64() * self.policy.multiplier)
+            .map_or(max_delay, |d| d.min(max_delay));
+
+        // xorshift64
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        let unit = (self.rng_state >> 11) as f64 / (1u64 << 53) as f64;
+
+        let shift = self.policy.jitter * (2.0 * unit - 1.0);
+        let jitter = Duration::try_from_secs_f64(delay.as_secs_f64() * shift.abs())
+            .unwrap_or(delay)
+            .min(delay);
+        if shift >= 0.0 {
+            delay.saturating_add(jitter)
+        } else {
+            delay.saturating_sub(jitter)
+        }
+    }
+
+    /// Sleeps for the next delay.
+    ///
+    /// Fails with [Error::Timeout] if the deadline would be exceeded and with
+    /// [Error::Cancelled] if `cancellation_token` is cancelled in the meantime.
+    pub async fn wait(&mut self, cancellation_token: &CancellationToken) -> Result<(), Error> {
+        let delay = self.next_delay();
+
+        if let Some(deadline) = self.deadline
+            && Instant::now()
+                .checked_add(delay)
+                .is_none_or(|wake_up| wake_up > deadline)
+        {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => return Err(Error::Cancelled),
+                _ = sleep_until(deadline) => {},
+            }
+            return Err(Error::Timeout(self.policy.deadline.unwrap_or_default()));
+        }
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => Err(Error::Cancelled),
+            _ = sleep(delay) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use super::PollPolicy;
+    use crate::Error;
+
+    #[test]
+    fn it_backs_off_up_to_max_delay() {
+        let policy = PollPolicy::new()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(350))
+            .with_jitter(0.0);
+
+        let mut poller = policy.start();
+        let delays: Vec<_> = (0..4).map(|_| poller.next_delay()).collect();
+
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+                Duration::from_millis(350),
+                Duration::from_millis(350),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_jitters_delays() {
+        let policy = PollPolicy::new()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_multiplier(1.0)
+            .with_jitter(0.5);
+
+        let mut poller = policy.start();
+        for _ in 0..100 {
+            let delay = poller.next_delay();
+            assert!(delay >= Duration::from_millis(50));
+            assert!(delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_handles_unbounded_delays() {
+        let policy = PollPolicy::new()
+            .with_max_delay(Duration::from_secs(5))
+            .with_multiplier(f64::INFINITY)
+            .with_jitter(0.0);
+        let mut poller = policy.start();
+        assert_eq!(poller.next_delay(), Duration::from_millis(100));
+        assert_eq!(poller.next_delay(), Duration::from_secs(5));
+
+        let mut poller = PollPolicy::new().with_multiplier(f64::NAN).start();
+        assert!(poller.next_delay() <= Duration::from_millis(110));
+
+        // 10% jitter around the largest delays
+        let policy = PollPolicy::new()
+            .with_initial_delay(Duration::MAX)
+            .with_max_delay(Duration::MAX)
+            .with_deadline(Duration::from_secs(1));
+        let mut poller = policy.start();
+        for _ in 0..100 {
+            assert!(poller.next_delay() >= Duration::MAX.mul_f64(0.8));
+        }
+        let token = CancellationToken::new();
+        assert!(matches!(poller.wait(&token).await, Err(Error::Timeout(_))));
+
+        let mut poller = PollPolicy::new()
+            .with_initial_delay(Duration::MAX)
+            .with_deadline(Duration::MAX)
+            .start();
+        token.cancel();
+        assert!(matches!(poller.wait(&token).await, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_times_out() {
+        let policy = PollPolicy::new()
+            .with_initial_delay(Duration::from_secs(1))
+            .with_deadline(Duration::from_millis(2500));
+
+        let token = CancellationToken::new();
+        let mut poller = policy.start();
+
+        assert!(poller.wait(&token).await.is_ok());
+        assert!(matches!(poller.wait(&token).await, Err(Error::Timeout(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_can_be_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let mut poller = PollPolicy::new().start();
+
+        assert!(matches!(poller.wait(&token).await, Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,629 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Static study reports - a self-contained HTML file with inline SVG charts, to
+//! share the results of a study without a notebook.
+//!
+//! The report contains:
+//! - a summary of the study spec: algorithm, metrics and parameters,
+//! - the best value so far of the primary metric,
+//! - a parallel-coordinates chart of the parameters and the primary metric,
+//! - a scatter chart of two objectives with their Pareto front,
+//! - the learning curves of the primary metric from the intermediate measurements,
+//! - the table of the trials.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::study::StudyName;
+//! # use oss_vizier::pagination::collect_all;
+//! # use oss_vizier::report::Report;
+//! # async fn example(mut client: VizierClient<InterceptedChannel>, study_name: StudyName) -> Result<(), Box<dyn std::error::Error>> {
+//! let study = client.get_study(client.mk_get_study_request(study_name.clone())).await?;
+//! let trials = collect_all(client.list_trials_stream(study_name, 0)).await?;
+//!
+//! Report::new(study, trials)
+//!     .with_metric("accuracy")
+//!     .save("report.html")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::Trial;
+use crate::analysis::convergence::{best_so_far, sort_by_completion_time};
+use crate::analysis::pareto::non_dominated_sort;
+use crate::analysis::{final_metric, is_safe, objectives, oriented, scale};
+use crate::report::svg::{Axis, Line, Marker, Polyline, format_number};
+use crate::table::Table;
+use crate::vizier::study_spec::parameter_spec::ParameterValueSpec;
+use crate::vizier::study_spec::parameter_spec::conditional_parameter_spec::ParentValueCondition;
+use crate::vizier::study_spec::{MetricSpec, ParameterSpec};
+use crate::vizier::trial::State;
+use crate::vizier::{Study, StudySpec};
+
+mod svg;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;font-size:13px;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:3px 8px;text-align:left}\
+th{background:#f4f4f4}\
+.scroll{overflow-x:auto}\
+.note{color:#777;font-style:italic}";
+
+/// HTML report of a study and its trials.
+#[derive(Clone, Debug)]
+pub struct Report {
+    study: Study,
+    trials: Vec<Trial>,
+    title: Option<String>,
+    metric_id: Option<String>,
+    pareto_objectives: Option<(String, String)>,
+}
+
+impl Report {
+    /// Creates a new [Report] - the trials are ordered by completion time.
+    pub fn new(study: Study, mut trials: Vec<Trial>) -> Self {
+        sort_by_completion_time(&mut trials);
+        Self {
+            study,
+            trials,
+            title: None,
+            metric_id: None,
+            pareto_objectives: None,
+        }
+    }
+
+    /// Sets the title of the report - the display name of the study by default.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the primary metric of the charts - the first objective metric by default.
+    pub fn with_metric(mut self, metric_id: impl Into<String>) -> Self {
+        self.metric_id = Some(metric_id.into());
+        self
+    }
+
+    /// Sets the objectives of the Pareto scatter chart - the first two objective
+    /// metrics by default.
+    pub fn with_pareto_objectives(mut self, x: impl Into<String>, y: impl Into<String>) -> Self {
+        self.pareto_objectives = Some((x.into(), y.into()));
+        self
+    }
+
+    /// Renders the report as HTML.
+    pub fn render(&self) -> String {
+        let spec = self.study.study_spec.clone().unwrap_or_default();
+        let title = self
+            .title
+            .clone()
+            .unwrap_or_else(|| self.study.display_name.clone());
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>{STYLE}</style></head><body><h1>{}</h1>",
+            escape(&title),
+            escape(&title)
+        );
+
+        self.render_summary(&mut out, &spec);
+
+        match self.primary_metric(&spec) {
+            Some(metric) => {
+                self.render_best_so_far(&mut out, &spec, metric);
+                self.render_parallel_coordinates(&mut out, &spec, metric);
+                self.render_pareto(&mut out, &spec);
+                self.render_learning_curves(&mut out, metric);
+            }
+            None => out.push_str("<p class=\"note\">The study has no metric to plot.</p>"),
+        }
+
+        self.render_trials(&mut out);
+
+        out.push_str("</body></html>\n");
+        out
+    }
+
+    /// Writes the report as HTML.
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writer.write_all(self.render().as_bytes())
+    }
+
+    /// Writes the report to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn primary_metric<'a>(&self, spec: &'a StudySpec) -> Option<&'a MetricSpec> {
+        match &self.metric_id {
+            Some(metric_id) => spec.metrics.iter().find(|m| &m.metric_id == metric_id),
+            None => objectives(&spec.metrics)
+                .into_iter()
+                .next()
+                .or(spec.metrics.first()),
+        }
+    }
+
+    fn render_summary(&self, out: &mut String, spec: &StudySpec) {
+        let count = |state: State| self.trials.iter().filter(|t| t.state() == state).count();
+        let _ = write!(
+            out,
+            "<h2>Study</h2><table><tr><th>Name</th><td>{}</td></tr><tr><th>State</th><td>{}</td></tr><tr><th>Algorithm</th><td>{}</td></tr><tr><th>Trials</th><td>{} ({} succeeded, {} infeasible, {} active)</td></tr></table>",
+            escape(&self.study.name),
+            self.study.state().as_str_name(),
+            escape(&spec.algorithm),
+            self.trials.len(),
+            count(State::Succeeded),
+            count(State::Infeasible),
+            count(State::Active),
+        );
+
+        out.push_str(
+            "<h3>Metrics</h3><table><tr><th>Metric</th><th>Goal</th><th>Safety threshold</th></tr>",
+        );
+        for metric in &spec.metrics {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&metric.metric_id),
+                metric.goal().as_str_name(),
+                metric
+                    .safety_config
+                    .as_ref()
+                    .map(|s| format_number(s.safety_threshold))
+                    .unwrap_or_default()
+            );
+        }
+        out.push_str("</table>");
+
+        out.push_str("<h3>Parameters</h3><table><tr><th>Parameter</th><th>Domain</th><th>Scale</th><th>Condition</th></tr>");
+        for (spec, condition) in parameters(&spec.parameters) {
+            let _ = write!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&spec.parameter_id),
+                escape(&domain(spec)),
+                spec.scale_type().as_str_name(),
+                escape(&condition.unwrap_or_default())
+            );
+        }
+        out.push_str("</table>");
+    }
+
+    fn render_best_so_far(&self, out: &mut String, spec: &StudySpec, metric: &MetricSpec) {
+        out.push_str("<h2>Best so far</h2>");
+
+        let series = best_so_far(&self.trials, &spec.metrics)
+            .into_iter()
+            .find(|s| s.name == metric.metric_id)
+            .filter(|s| !s.points.is_empty());
+        let Some(series) = series else {
+            out.push_str("<p class=\"note\">No completed trial yet.</p>");
+            return;
+        };
+
+        let line = Line {
+            label: series.name.clone(),
+            points: series
+                .points
+                .iter()
+                .map(|p| (p.step as f64, p.value))
+                .collect(),
+        };
+        out.push_str(&svg::line_chart(&[line], "trials", &metric.metric_id));
+    }
+
+    fn render_parallel_coordinates(&self, out: &mut String, spec: &StudySpec, metric: &MetricSpec) {
+        out.push_str("<h2>Parameters and metric</h2>");
+
+        let completed: Vec<(&Trial, f64)> = self
+            .trials
+            .iter()
+            .filter(|t| t.state() == State::Succeeded)
+            .filter_map(|t| {
+                final_metric(t, &metric.metric_id)
+                    .filter(|v| v.is_finite())
+                    .map(|v| (t, v))
+            })
+            .collect();
+        if completed.is_empty() {
+            out.push_str("<p class=\"note\">No completed trial yet.</p>");
+            return;
+        }
+
+        let parameters: Vec<&ParameterSpec> = parameters(&spec.parameters)
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        let (low, high) = completed
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, v)| {
+                (lo.min(*v), hi.max(*v))
+            });
+        let metric_position = |v: f64| {
+            if high > low {
+                (v - low) / (high - low)
+            } else {
+                0.5
+            }
+        };
+
+        let mut axes: Vec<Axis> = parameters.iter().map(|p| axis(p)).collect();
+        axes.push(Axis {
+            label: metric.metric_id.clone(),
+            ticks: vec![(0.0, format_number(low)), (1.0, format_number(high))],
+        });
+
+        let lines: Vec<Polyline> = completed
+            .iter()
+            .map(|(trial, value)| {
+                let mut positions: Vec<Option<f64>> =
+                    parameters.iter().map(|p| position(p, trial)).collect();
+                positions.push(Some(metric_position(*value)));
+
+                // greater is better is red
+                let shade = match (
+                    oriented(metric, low) < oriented(metric, high),
+                    metric_position(*value),
+                ) {
+                    (true, p) => p,
+                    (false, p) => 1.0 - p,
+                };
+                Polyline {
+                    positions,
+                    shade,
+                    title: format!("trial {}: {}", trial.id, format_number(*value)),
+                }
+            })
+            .collect();
+
+        out.push_str(&svg::parallel_coordinates(&axes, &lines));
+    }
+
+    fn render_pareto(&self, out: &mut String, spec: &StudySpec) {
+        let objectives: Vec<&MetricSpec> = match &self.pareto_objectives {
+            Some((x, y)) => [x, y]
+                .into_iter()
+                .filter_map(|id| spec.metrics.iter().find(|m| &m.metric_id == id))
+                .collect(),
+            None => objectives(&spec.metrics).into_iter().take(2).collect(),
+        };
+        let [x_metric, y_metric] = objectives[..] else {
+            return;
+        };
+
+        out.push_str("<h2>Pareto front</h2>");
+
+        let points: Vec<(&Trial, f64, f64)> = self
+            .trials
+            .iter()
+            .filter(|t| t.state() == State::Succeeded && is_safe(t, &spec.metrics))
+            .filter_map(|t| {
+                let x = final_metric(t, &x_metric.metric_id).filter(|v| v.is_finite())?;
+                let y = final_metric(t, &y_metric.metric_id).filter(|v| v.is_finite())?;
+                Some((t, x, y))
+            })
+            .collect();
+        if points.is_empty() {
+            out.push_str("<p class=\"note\">No completed trial yet.</p>");
+            return;
+        }
+
+        let ranks = non_dominated_sort(
+            &points
+                .iter()
+                .map(|(_, x, y)| vec![oriented(x_metric, *x), oriented(y_metric, *y)])
+                .collect::<Vec<_>>(),
+        );
+        let markers: Vec<Marker> = points
+            .iter()
+            .zip(ranks)
+            .map(|((trial, x, y), rank)| Marker {
+                x: *x,
+                y: *y,
+                highlighted: rank == 0,
+                title: format!(
+                    "trial {}: {}, {}",
+                    trial.id,
+                    format_number(*x),
+                    format_number(*y)
+                ),
+            })
+            .collect();
+
+        out.push_str(&svg::scatter_chart(
+            &markers,
+            &x_metric.metric_id,
+            &y_metric.metric_id,
+        ));
+    }
+
+    fn render_learning_curves(&self, out: &mut String, metric: &MetricSpec) {
+        out.push_str("<h2>Learning curves</h2>");
+
+        let lines: Vec<Line> = self
+            .trials
+            .iter()
+            .map(|trial| Line {
+                label: format!("trial {}", trial.id),
+                points: trial
+                    .measurements
+                    .iter()
+                    .filter_map(|m| {
+                        m.metrics
+                            .iter()
+                            .find(|x| x.metric_id == metric.metric_id)
+                            .map(|x| (m.step_count as f64, x.value))
+                    })
+                    .collect(),
+            })
+            .filter(|line| !line.points.is_empty())
+            .collect();
+        if lines.is_empty() {
+            out.push_str("<p class=\"note\">No intermediate measurement.</p>");
+            return;
+        }
+
+        out.push_str(&svg::line_chart(&lines, "steps", &metric.metric_id));
+    }
+
+    fn render_trials(&self, out: &mut String) {
+        out.push_str("<h2>Trials</h2><div class=\"scroll\"><table><tr>");
+
+        let table = Table::trials(&self.trials);
+        for column in table.columns() {
+            let _ = write!(out, "<th>{}</th>", escape(&column.name));
+        }
+        out.push_str("</tr>");
+        for row in 0..table.num_rows() {
+            out.push_str("<tr>");
+            for column in table.columns() {
+                let _ = write!(out, "<td>{}</td>", escape(&column.values.format(row)));
+            }
+            out.push_str("</tr>");
+        }
+
+        out.push_str("</table></div>");
+    }
+}
+
+/// Escapes text for HTML and SVG.
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The parameters and their conditional parameters, depth-first, with their
+/// condition.
+fn parameters(specs: &[ParameterSpec]) -> Vec<(&ParameterSpec, Option<String>)> {
+    fn walk<'a>(
+        specs: &'a [ParameterSpec],
+        condition: Option<String>,
+        out: &mut Vec<(&'a ParameterSpec, Option<String>)>,
+    ) {
+        for spec in specs {
+            out.push((spec, condition.clone()));
+            for conditional in &spec.conditional_parameter_specs {
+                if let Some(child) = &conditional.parameter_spec {
+                    let values = match &conditional.parent_value_condition {
+                        Some(ParentValueCondition::ParentDiscreteValues(c)) => join(&c.values),
+                        Some(ParentValueCondition::ParentIntValues(c)) => join(&c.values),
+                        Some(ParentValueCondition::ParentCategoricalValues(c)) => join(&c.values),
+                        None => String::new(),
+                    };
+                    let condition = format!("{} in {{{values}}}", spec.parameter_id);
+                    walk(std::slice::from_ref(child), Some(condition), out);
+                }
+            }
+        }
+    }
+
+    let mut out = vec![];
+    walk(specs, None, &mut out);
+    out
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Human readable domain of a parameter.
+fn domain(spec: &ParameterSpec) -> String {
+    match &spec.parameter_value_spec {
+        Some(ParameterValueSpec::DoubleValueSpec(s)) => {
+            format!("double [{}, {}]", s.min_value, s.max_value)
+        }
+        Some(ParameterValueSpec::IntegerValueSpec(s)) => {
+            format!("integer [{}, {}]", s.min_value, s.max_value)
+        }
+        Some(ParameterValueSpec::DiscreteValueSpec(s)) => {
+            format!("discrete {{{}}}", join(&s.values))
+        }
+        Some(ParameterValueSpec::CategoricalValueSpec(s)) => {
+            format!("categorical {{{}}}", join(&s.values))
+        }
+        None => String::new(),
+    }
+}
+
+/// Axis of a parameter in the parallel-coordinates chart.
+fn axis(spec: &ParameterSpec) -> Axis {
+    let ticks = match &spec.parameter_value_spec {
+        Some(ParameterValueSpec::CategoricalValueSpec(s)) => s
+            .values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (category_position(i, s.values.len()), v.clone()))
+            .collect(),
+        Some(_) => {
+            let (min, max) = numeric_range(spec);
+            vec![(0.0, format_number(min)), (1.0, format_number(max))]
+        }
+        None => vec![],
+    };
+
+    Axis {
+        label: spec.parameter_id.clone(),
+        ticks,
+    }
+}
+
+fn category_position(index: usize, count: usize) -> f64 {
+    if count > 1 {
+        index as f64 / (count - 1) as f64
+    } else {
+        0.5
+    }
+}
+
+fn numeric_range(spec: &ParameterSpec) -> (f64, f64) {
+    match &spec.parameter_value_spec {
+        Some(ParameterValueSpec::DoubleValueSpec(s)) => (s.min_value, s.max_value),
+        Some(ParameterValueSpec::IntegerValueSpec(s)) => (s.min_value as f64, s.max_value as f64),
+        Some(ParameterValueSpec::DiscreteValueSpec(s)) => (
+            s.values.iter().copied().fold(f64::INFINITY, f64::min),
+            s.values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        ),
+        _ => (0.0, 1.0),
+    }
+}
+
+/// Position in `[0, 1]` of the value of a parameter in a trial, scaled per the spec.
+fn position(spec: &ParameterSpec, trial: &Trial) -> Option<f64> {
+    use prost_types::value::Kind;
+
+    let kind = trial
+        .parameters
+        .iter()
+        .find(|p| p.parameter_id == spec.parameter_id)?
+        .value
+        .as_ref()?
+        .kind
+        .as_ref()?;
+
+    match (&spec.parameter_value_spec, kind) {
+        (Some(ParameterValueSpec::CategoricalValueSpec(s)), Kind::StringValue(v)) => s
+            .values
+            .iter()
+            .position(|c| c == v)
+            .map(|i| category_position(i, s.values.len())),
+        (Some(_), Kind::NumberValue(v)) => {
+            let (min, max) = numeric_range(spec);
+            Some(scale(*v, min, max, spec.scale_type()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use super::Report;
+    use crate::analysis::testing::{metric, trial};
+    use crate::vizier::study_spec::ParameterSpec;
+    use crate::vizier::study_spec::metric_spec::GoalType;
+    use crate::vizier::study_spec::parameter_spec::{DoubleValueSpec, ParameterValueSpec};
+    use crate::vizier::{Measurement, Study, StudySpec, measurement, trial};
+
+    #[test]
+    fn it_renders_a_report() {
+        let study = Study {
+            name: "owners/o/studies/1".to_string(),
+            display_name: "<sweep>".to_string(),
+            study_spec: Some(StudySpec {
+                metrics: vec![
+                    metric("accuracy", GoalType::Maximize, None),
+                    metric("latency", GoalType::Minimize, None),
+                ],
+                parameters: vec![ParameterSpec {
+                    parameter_id: "learning_rate".to_string(),
+                    parameter_value_spec: Some(ParameterValueSpec::DoubleValueSpec(
+                        DoubleValueSpec {
+                            min_value: 0.001,
+                            max_value: 0.1,
+                            default_value: None,
+                        },
+                    )),
+                    ..Default::default()
+                }],
+                algorithm: "RANDOM_SEARCH".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let trials = (1..=3)
+            .map(|i| {
+                let mut t = trial(i, &[("accuracy", 0.5 + 0.1 * i as f64), ("latency", 10.0)]);
+                t.parameters = vec![trial::Parameter {
+                    parameter_id: "learning_rate".to_string(),
+                    value: Some(Value {
+                        kind: Some(Kind::NumberValue(0.01 * i as f64)),
+                    }),
+                }];
+                t.measurements = vec![Measurement {
+                    elapsed_duration: None,
+                    step_count: 1,
+                    metrics: vec![measurement::Metric {
+                        metric_id: "accuracy".to_string(),
+                        value: 0.1,
+                    }],
+                }];
+                t
+            })
+            .collect();
+
+        let html = Report::new(study, trials).render();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>&lt;sweep&gt;</h1>"));
+        assert!(html.contains("RANDOM_SEARCH"));
+        for section in [
+            "Best so far",
+            "Parameters and metric",
+            "Pareto front",
+            "Learning curves",
+            "Trials",
+        ] {
+            assert!(html.contains(&format!("<h2>{section}</h2>")), "{section}");
+        }
+        assert_eq!(html.matches("<svg").count(), 4);
+        assert!(!html.contains("<script"));
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal SVG charts of the report.
+
+use std::fmt::Write;
+
+use crate::report::escape;
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 20.0;
+const MARGIN_BOTTOM: f64 = 50.0;
+/// Maximum number of ticks of an axis.
+const MAX_TICKS: usize = 20;
+
+/// A line of a [line_chart].
+pub(crate) struct Line {
+    pub(crate) label: String,
+    pub(crate) points: Vec<(f64, f64)>,
+}
+
+/// A point of a [scatter_chart].
+pub(crate) struct Marker {
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) highlighted: bool,
+    pub(crate) title: String,
+}
+
+/// An axis of a [parallel_coordinates] chart.
+pub(crate) struct Axis {
+    pub(crate) label: String,
+    /// Position in `[0, 1]` and label of the ticks.
+    pub(crate) ticks: Vec<(f64, String)>,
+}
+
+/// A polyline of a [parallel_coordinates] chart.
+pub(crate) struct Polyline {
+    /// Position in `[0, 1]` on each axis - `None` breaks the line.
+    pub(crate) positions: Vec<Option<f64>>,
+    /// Color of the line in `[0, 1]`.
+    pub(crate) shade: f64,
+    pub(crate) title: String,
+}
+
+/// Linear map of `[min, max]` to `[from, to]`.
+struct Scale {
+    min: f64,
+    max: f64,
+    from: f64,
+    to: f64,
+}
+
+impl Scale {
+    fn new(values: impl Iterator<Item = f64>, from: f64, to: f64) -> Self {
+        let (mut min, mut max) = values
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        if min > max {
+            (min, max) = (0.0, 1.0);
+        } else if min == max {
+            // relative to the value - a fixed margin is lost to rounding above 2^53
+            let margin = 0.5 * min.abs().max(1.0);
+            (min, max) = (min - margin, max + margin);
+        }
+        Self { min, max, from, to }
+    }
+
+    fn map(&self, v: f64) -> f64 {
+        self.from + (v - self.min) / (self.max - self.min) * (self.to - self.from)
+    }
+
+    /// About 5 round values in the range - at most [MAX_TICKS].
+    fn ticks(&self) -> Vec<f64> {
+        let raw = (self.max - self.min) / 5.0;
+        let magnitude = 10f64.powf(raw.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|m| m * magnitude)
+            .find(|s| *s >= raw)
+            .unwrap_or(raw);
+        if !(step.is_finite() && step > 0.0) {
+            return vec![];
+        }
+
+        let first = (self.min / step).ceil() * step;
+        let count = ((self.max - first) / step + 1e-9).floor();
+        if count.is_nan() || count < 0.0 {
+            return vec![];
+        }
+
+        // the step can be below the precision of the values - the ticks then collapse
+        let mut ticks: Vec<f64> = (0..=(count as usize).min(MAX_TICKS - 1))
+            .map(|i| first + i as f64 * step)
+            .collect();
+        ticks.dedup();
+        ticks
+    }
+}
+
+/// Compact label of a number.
+pub(crate) fn format_number(v: f64) -> String {
+    if v == 0.0 {
+        "0".to_string()
+    } else if v.abs() >= 1e4 || v.abs() < 1e-3 {
+        format!("{v:.2e}")
+    } else {
+        let s = format!("{v:.4}");
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Color of `shade` in `[0, 1]` - blue to red.
+pub(crate) fn color(shade: f64) -> String {
+    let t = if shade.is_finite() {
+        shade.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let r = (40.0 + 200.0 * t) as u8;
+    let b = (240.0 - 200.0 * t) as u8;
+    format!("rgb({r},80,{b})")
+}
+
+/// Distinct color of the `i`-th series.
+fn palette(i: usize) -> &'static str {
+    const PALETTE: [&str; 8] = [
+        "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    ];
+    PALETTE[i % PALETTE.len()]
+}
+
+fn open(out: &mut String) {
+    let _ = write!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {WIDTH} {HEIGHT}" width="{WIDTH}" height="{HEIGHT}">"#
+    );
+}
+
+fn axes(out: &mut String, x: &Scale, y: &Scale, x_label: &str, y_label: &str) {
+    let bottom = HEIGHT - MARGIN_BOTTOM;
+    let right = WIDTH - MARGIN_RIGHT;
+    let _ = write!(
+        out,
+        r##"<g stroke="#444"><line x1="{MARGIN_LEFT}" y1="{bottom}" x2="{right}" y2="{bottom}"/><line x1="{MARGIN_LEFT}" y1="{MARGIN_TOP}" x2="{MARGIN_LEFT}" y2="{bottom}"/></g>"##
+    );
+
+    out.push_str(r##"<g font-size="11" fill="#444">"##);
+    for tick in x.ticks() {
+        let px = x.map(tick);
+        let _ = write!(
+            out,
+            r##"<line x1="{px:.1}" y1="{bottom}" x2="{px:.1}" y2="{}" stroke="#444"/><text x="{px:.1}" y="{}" text-anchor="middle">{}</text>"##,
+            bottom + 4.0,
+            bottom + 16.0,
+            format_number(tick)
+        );
+    }
+    for tick in y.ticks() {
+        let py = y.map(tick);
+        let _ = write!(
+            out,
+            r##"<line x1="{}" y1="{py:.1}" x2="{right}" y2="{py:.1}" stroke="#eee"/><text x="{}" y="{:.1}" text-anchor="end">{}</text>"##,
+            MARGIN_LEFT,
+            MARGIN_LEFT - 6.0,
+            py + 4.0,
+            format_number(tick)
+        );
+    }
+    let _ = write!(
+        out,
+        r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text><text transform="translate(14,{:.1}) rotate(-90)" text-anchor="middle">{}</text></g>"#,
+        (MARGIN_LEFT + right) / 2.0,
+        HEIGHT - 10.0,
+        escape(x_label),
+        (MARGIN_TOP + bottom) / 2.0,
+        escape(y_label)
+    );
+}
+
+/// Line chart of the lines, with a legend if there are several.
+pub(crate) fn line_chart(lines: &[Line], x_label: &str, y_label: &str) -> String {
+    let x = Scale::new(
+        lines.iter().flat_map(|l| l.points.iter().map(|p| p.0)),
+        MARGIN_LEFT,
+        WIDTH - MARGIN_RIGHT,
+    );
+    let y = Scale::new(
+        lines.iter().flat_map(|l| l.points.iter().map(|p| p.1)),
+        HEIGHT - MARGIN_BOTTOM,
+        MARGIN_TOP,
+    );
+
+    let mut out = String::new();
+    open(&mut out);
+    axes(&mut out, &x, &y, x_label, y_label);
+
+    for (i, line) in lines.iter().enumerate() {
+        let points: Vec<String> = line
+            .points
+            .iter()
+            .filter(|(a, b)| a.is_finite() && b.is_finite())
+            .map(|(a, b)| format!("{:.1},{:.1}", x.map(*a), y.map(*b)))
+            .collect();
+        let _ = write!(
+            out,
+            r#"<polyline fill="none" stroke="{}" stroke-width="1.5" stroke-opacity="0.8" points="{}"><title>{}</title></polyline>"#,
+            palette(i),
+            points.join(" "),
+            escape(&line.label)
+        );
+    }
+
+    if lines.len() > 1 && lines.len() <= 10 {
+        for (i, line) in lines.iter().enumerate() {
+            let py = MARGIN_TOP + 14.0 * i as f64 + 8.0;
+            let _ = write!(
+                out,
+                r#"<text x="{:.1}" y="{py:.1}" font-size="11" fill="{}" text-anchor="end">{}</text>"#,
+                WIDTH - MARGIN_RIGHT - 4.0,
+                palette(i),
+                escape(&line.label)
+            );
+        }
+    }
+
+    out.push_str("</svg>");
+    out
+}
+
+/// Scatter chart - highlighted markers are drawn on top.
+pub(crate) fn scatter_chart(markers: &[Marker], x_label: &str, y_label: &str) -> String {
+    let x = Scale::new(
+        markers.iter().map(|m| m.x),
+        MARGIN_LEFT,
+        WIDTH - MARGIN_RIGHT,
+    );
+    let y = Scale::new(
+        markers.iter().map(|m| m.y),
+        HEIGHT - MARGIN_BOTTOM,
+        MARGIN_TOP,
+    );
+
+    let mut out = String::new();
+    open(&mut out);
+    axes(&mut out, &x, &y, x_label, y_label);
+
+    let mut ordered: Vec<&Marker> = markers.iter().collect();
+    ordered.sort_by_key(|m| m.highlighted);
+    for marker in ordered {
+        let (fill, radius) = if marker.highlighted {
+            ("#d62728", 5.0)
+        } else {
+            ("#1f77b4", 3.5)
+        };
+        let _ = write!(
+            out,
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{radius}" fill="{fill}" fill-opacity="0.8"><title>{}</title></circle>"#,
+            x.map(marker.x),
+            y.map(marker.y),
+            escape(&marker.title)
+        );
+    }
+
+    out.push_str("</svg>");
+    out
+}
+
+/// Parallel coordinates chart - one vertical axis per [Axis].
+pub(crate) fn parallel_coordinates(axes: &[Axis], lines: &[Polyline]) -> String {
+    let mut out = String::new();
+    open(&mut out);
+
+    let bottom = HEIGHT - MARGIN_BOTTOM;
+    let top = MARGIN_TOP + 10.0;
+    let x = |i: usize| {
+        if axes.len() <= 1 {
+            WIDTH / 2.0
+        } else {
+            MARGIN_LEFT
+                + i as f64 * (WIDTH - MARGIN_LEFT - MARGIN_RIGHT - 40.0) / (axes.len() - 1) as f64
+        }
+    };
+    let y = |position: f64| bottom - position.clamp(0.0, 1.0) * (bottom - top);
+
+    for line in lines {
+        let mut path = String::new();
+        let mut pen_down = false;
+        for (i, position) in line.positions.iter().enumerate() {
+            match position {
+                Some(p) => {
+                    let _ = write!(
+                        path,
+                        "{}{:.1},{:.1} ",
+                        if pen_down { "L" } else { "M" },
+                        x(i),
+                        y(*p)
+                    );
+                    pen_down = true;
+                }
+                None => pen_down = false,
+            }
+        }
+        let _ = write!(
+            out,
+            r#"<path d="{}" fill="none" stroke="{}" stroke-opacity="0.6"><title>{}</title></path>"#,
+            path.trim_end(),
+            color(line.shade),
+            escape(&line.title)
+        );
+    }
+
+    out.push_str(r##"<g font-size="11" fill="#444">"##);
+    for (i, axis) in axes.iter().enumerate() {
+        let px = x(i);
+        let _ = write!(
+            out,
+            r##"<line x1="{px:.1}" y1="{top}" x2="{px:.1}" y2="{bottom}" stroke="#444"/><text x="{px:.1}" y="{}" text-anchor="middle" font-weight="bold">{}</text>"##,
+            bottom + 20.0,
+            escape(&axis.label)
+        );
+        for (position, label) in &axis.ticks {
+            let _ = write!(
+                out,
+                r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+                px + 4.0,
+                y(*position) + 4.0,
+                escape(label)
+            );
+        }
+    }
+    out.push_str("</g></svg>");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Line, Scale, format_number, line_chart};
+
+    #[test]
+    fn it_picks_round_ticks() {
+        let scale = Scale::new([0.13, 0.87].into_iter(), 0.0, 100.0);
+        assert_eq!(scale.ticks().len(), 4);
+        assert!((scale.ticks()[0] - 0.2).abs() < 1e-12);
+
+        assert_eq!(format_number(0.25), "0.25");
+        assert_eq!(format_number(2.0), "2");
+        assert_eq!(format_number(123456.0), "1.23e5");
+    }
+
+    #[test]
+    fn it_scales_large_values() {
+        // constant beyond 2^53
+        let scale = Scale::new([1e16, 1e16].into_iter(), 0.0, 100.0);
+        assert_eq!(scale.map(1e16), 50.0);
+        assert!(!scale.ticks().is_empty());
+        assert!(scale.ticks().iter().all(|t| t.is_finite()));
+
+        // a step below the precision of the values
+        let scale = Scale::new([1e17, 1e17 + 16.0].into_iter(), 0.0, 100.0);
+        assert!(scale.map(1e17).is_finite());
+        let ticks = scale.ticks();
+        assert!(!ticks.is_empty() && ticks.len() <= 20);
+        assert!(ticks.iter().all(|t| (1e17..=1e17 + 16.0).contains(t)));
+    }
+
+    #[test]
+    fn it_escapes_labels() {
+        let svg = line_chart(
+            &[Line {
+                label: "a<b".to_string(),
+                points: vec![(1.0, 1.0), (2.0, 3.0)],
+            }],
+            "trials",
+            "loss",
+        );
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("a&lt;b"));
+        assert!(!svg.contains("a<b"));
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Intermediate measurements reporting for a running trial.
+//!
+//! A [TrialReporter] is meant to be handed to the training loop of an objective. It
+//! streams intermediate [Measurement]s to the Vizier service, throttles them, and
+//! periodically asks the service whether the trial should be early-stopped.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::trial::ToTrialName;
+//! # use oss_vizier::vizier::{Measurement, Trial};
+//! # fn train_one_epoch(step: i64) -> Measurement { Measurement::default() }
+//! # async fn example(client: VizierClient<InterceptedChannel>, trial: Trial) -> Result<(), oss_vizier::Error> {
+//! let mut reporter = client.trial_reporter(trial.to_trial_name());
+//!
+//! for step in 0..100 {
+//!     let measurement = train_one_epoch(step);
+//!     if reporter.report(measurement).await? {
+//!         break;
+//!     }
+//! }
+//!
+//! let trial = reporter.complete(None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use prost::bytes::Bytes;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tonic::codegen::{Body, StdError};
+
+use crate::trial::complete::FinalMeasurementOrReason;
+use crate::vizier::CompleteTrialRequest;
+use crate::{Error, Measurement, Trial, TrialName, VizierClient};
+
+/// Default minimum interval between two measurements sent to the service.
+pub const DEFAULT_MIN_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default interval between two early stopping checks.
+pub const DEFAULT_EARLY_STOPPING_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Handle to report intermediate measurements of a trial.
+///
+/// Measurements passed to [TrialReporter::report] are sent to the service at most once
+/// per `min_report_interval`; the ones received in between are coalesced and only
+/// the latest is kept until the next report or [TrialReporter::flush].
+///
+/// Every `early_stopping_check_interval`, the reporter checks the early stopping state
+/// of the trial. Once the service has decided the trial should stop, the
+/// [CancellationToken] returned by [TrialReporter::cancellation_token] is cancelled and
+/// [TrialReporter::should_stop] returns `true`.
+pub struct TrialReporter<T> {
+    client: VizierClient<T>,
+    trial_name: TrialName,
+    min_report_interval: Duration,
+    early_stopping_check_interval: Duration,
+    cancellation_token: CancellationToken,
+    pending: Option<Measurement>,
+    last_measurement: Option<Measurement>,
+    last_report: Option<Instant>,
+    last_check: Option<Instant>,
+    should_stop: bool,
+}
+
+impl<T> TrialReporter<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Creates a new [TrialReporter] for the trial `trial_name`.
+    pub fn new(client: VizierClient<T>, trial_name: TrialName) -> Self {
+        Self {
+            client,
+            trial_name,
+            min_report_interval: DEFAULT_MIN_REPORT_INTERVAL,
+            early_stopping_check_interval: DEFAULT_EARLY_STOPPING_CHECK_INTERVAL,
+            cancellation_token: CancellationToken::new(),
+            pending: None,
+            last_measurement: None,
+            last_report: None,
+            last_check: None,
+            should_stop: false,
+        }
+    }
+
+    /// Sets the minimum interval between two measurements sent to the service.
+    pub fn with_min_report_interval(mut self, min_report_interval: Duration) -> Self {
+        self.min_report_interval = min_report_interval;
+        self
+    }
+
+    /// Sets the interval between two early stopping checks.
+    pub fn with_early_stopping_check_interval(
+        mut self,
+        early_stopping_check_interval: Duration,
+    ) -> Self {
+        self.early_stopping_check_interval = early_stopping_check_interval;
+        self
+    }
+
+    /// Sets the [CancellationToken] cancelled when the trial should stop.
+    ///
+    /// Useful to share a token with other tasks of the training loop.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    /// The name of the reported trial.
+    pub fn trial_name(&self) -> &TrialName {
+        &self.trial_name
+    }
+
+    /// Whether the service decided the trial should stop.
+    pub fn should_stop(&self) -> bool {
+        self.should_stop
+    }
+
+    /// The [CancellationToken] cancelled when the trial should stop.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    /// The latest measurement received by [TrialReporter::report], sent or not.
+    pub fn last_measurement(&self) -> Option<&Measurement> {
+        self.pending.as_ref().or(self.last_measurement.as_ref())
+    }
+
+    /// Reports an intermediate measurement.
+    ///
+    /// The measurement is sent right away unless one has been sent less than
+    /// `min_report_interval` ago. Returns whether the trial should stop.
+    pub async fn report(&mut self, measurement: Measurement) -> Result<bool, Error> {
+        self.pending = Some(measurement);
+
+        if self
+            .last_report
+            .is_none_or(|t| t.elapsed() >= self.min_report_interval)
+        {
+            self.flush().await?;
+        }
+
+        if !self.should_stop
+            && self
+                .last_check
+                .is_none_or(|t| t.elapsed() >= self.early_stopping_check_interval)
+        {
+            self.check_early_stopping_state().await?;
+        }
+
+        Ok(self.should_stop)
+    }
+
+    /// Sends the pending measurement, if any.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        if let Some(measurement) = self.pending.take() {
+            let request = self
+                .client
+                .mk_add_trial_measurement_request(self.trial_name.clone(), measurement.clone());
+
+            if let Err(e) = self.client.add_trial_measurement(request).await {
+                self.pending = Some(measurement);
+                return Err(e);
+            }
+
+            self.last_measurement = Some(measurement);
+            self.last_report = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+
+    /// Checks the early stopping state of the trial with the service.
+    ///
+    /// Cancels the [CancellationToken] if the trial should stop. Returns whether the
+    /// trial should stop.
+    pub async fn check_early_stopping_state(&mut self) -> Result<bool, Error> {
+        let request = self
+            .client
+            .mk_check_trial_early_stopping_state_request(self.trial_name.clone());
+
+        let resp = self
+            .client
+            .check_trial_early_stopping_state(request)
+            .await?;
+
+        self.last_check = Some(Instant::now());
+
+        if resp.should_stop {
+            self.should_stop = true;
+            self.cancellation_token.cancel();
+        }
+
+        Ok(self.should_stop)
+    }
+
+    /// Completes the trial.
+    ///
+    /// Flushes the pending measurement and completes the trial with
+    /// `final_measurement`, or with the last reported measurement when `None`.
+    pub async fn complete(
+        mut self,
+        final_measurement: Option<Measurement>,
+    ) -> Result<Trial, Error> {
+        self.flush().await?;
+
+        let request = match final_measurement.or(self.last_measurement.take()) {
+            Some(measurement) => self.client.mk_complete_trial_request(
+                self.trial_name.clone(),
+                FinalMeasurementOrReason::FinalMeasurement(measurement),
+            ),
+            None => CompleteTrialRequest {
+                name: self.trial_name.clone().into(),
+                ..Default::default()
+            },
+        };
+
+        self.client.complete_trial(request).await
+    }
+
+    /// Completes the trial as infeasible with the given `reason`.
+    pub async fn complete_as_infeasible(mut self, reason: String) -> Result<Trial, Error> {
+        self.flush().await?;
+
+        let request = self.client.mk_complete_trial_request(
+            self.trial_name.clone(),
+            FinalMeasurementOrReason::Reason(reason),
+        );
+
+        self.client.complete_trial(request).await
+    }
+
+    /// Stops the trial before its end and completes it with the last reported
+    /// measurement.
+    ///
+    /// Use this when the objective decides by itself to stop the trial. When the
+    /// service decided it, [TrialReporter::complete] is enough.
+    pub async fn stop(mut self) -> Result<Trial, Error> {
+        self.flush().await?;
+
+        if !self.should_stop {
+            let request = self.client.mk_stop_trial_request(self.trial_name.clone());
+            self.client.stop_trial(request).await?;
+
+            self.should_stop = true;
+            self.cancellation_token.cancel();
+        }
+
+        self.complete(None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::future::poll_fn;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use http_body::Frame;
+    use prost::Message;
+    use prost::bytes::Bytes;
+    use tonic::Code;
+    use tonic::codegen::http::{HeaderMap, HeaderValue, Request, Response};
+    use tonic::codegen::{BoxFuture, Service};
+
+    use super::TrialReporter;
+    use crate::vizier::vizier_service_client::VizierServiceClient;
+    use crate::vizier::{
+        AddTrialMeasurementRequest, CheckTrialEarlyStoppingStateResponse, Measurement,
+    };
+    use crate::{Trial, TrialName, VizierClient};
+
+    /// Body of a response - its frames in order.
+    struct MockBody(VecDeque<Frame<Bytes>>);
+
+    impl http_body::Body for MockBody {
+        type Data = Bytes;
+        type Error = Infallible;
+
+        fn poll_frame(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+            Poll::Ready(self.0.pop_front().map(Ok))
+        }
+    }
+
+    /// An encoded response message or the code of the failure.
+    type Canned = Result<Vec<u8>, Code>;
+
+    /// The method and the encoded message of a request.
+    type Recorded = (String, Vec<u8>);
+
+    /// Vizier service answering with canned responses, in order, and recording the
+    /// method and the message of the requests.
+    #[derive(Clone, Default)]
+    struct MockService {
+        responses: Arc<Mutex<VecDeque<Canned>>>,
+        requests: Arc<Mutex<Vec<Recorded>>>,
+    }
+
+    impl MockService {
+        fn respond(&self, response: Canned) {
+            self.responses.lock().unwrap().push_back(response);
+        }
+
+        fn methods(&self) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests.iter().map(|(method, _)| method.clone()).collect()
+        }
+
+        /// The step count of the measurements sent.
+        fn sent_steps(&self) -> Vec<i64> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .filter(|(method, _)| method == "AddTrialMeasurement")
+                .map(|(_, message)| {
+                    let request = AddTrialMeasurementRequest::decode(&message[..]).unwrap();
+                    request.measurement.unwrap().step_count
+                })
+                .collect()
+        }
+    }
+
+    impl Service<Request<tonic::body::Body>> for MockService {
+        type Response = Response<MockBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<tonic::body::Body>) -> Self::Future {
+            let canned = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("unexpected request");
+            let requests = self.requests.clone();
+
+            Box::pin(async move {
+                let method = request.uri().path().rsplit('/').next().unwrap().to_string();
+                let mut body = request.into_body();
+                let mut bytes = vec![];
+                while let Some(frame) =
+                    poll_fn(|cx| http_body::Body::poll_frame(Pin::new(&mut body), cx)).await
+                {
+                    if let Ok(data) = frame.unwrap().into_data() {
+                        bytes.extend_from_slice(&data);
+                    }
+                }
+                // skip the compression flag and the length of the message
+                requests.lock().unwrap().push((method, bytes[5..].to_vec()));
+
+                let response = Response::builder().header("content-type", "application/grpc");
+                Ok(match canned {
+                    Ok(message) => {
+                        let mut data = vec![0];
+                        data.extend_from_slice(&(message.len() as u32).to_be_bytes());
+                        data.extend_from_slice(&message);
+                        let mut trailers = HeaderMap::new();
+                        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                        let frames = [Frame::data(Bytes::from(data)), Frame::trailers(trailers)];
+                        response.body(MockBody(frames.into())).unwrap()
+                    }
+                    // trailers-only response
+                    Err(code) => response
+                        .header("grpc-status", (code as i32).to_string())
+                        .body(MockBody(VecDeque::new()))
+                        .unwrap(),
+                })
+            })
+        }
+    }
+
+    fn reporter(
+        service: &MockService,
+        min_report_interval: Duration,
+        early_stopping_check_interval: Duration,
+    ) -> TrialReporter<MockService> {
+        let client = VizierClient::new(
+            "owner".to_string(),
+            VizierServiceClient::new(service.clone()),
+        );
+        let trial_name = TrialName::new("owner".to_string(), "study".to_string(), "1".to_string());
+        TrialReporter::new(client, trial_name)
+            .with_min_report_interval(min_report_interval)
+            .with_early_stopping_check_interval(early_stopping_check_interval)
+    }
+
+    fn measurement(step_count: i64) -> Measurement {
+        Measurement {
+            step_count,
+            ..Default::default()
+        }
+    }
+
+    fn added() -> Canned {
+        Ok(Trial::default().encode_to_vec())
+    }
+
+    fn checked(should_stop: bool) -> Canned {
+        Ok(CheckTrialEarlyStoppingStateResponse { should_stop }.encode_to_vec())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_coalesces_the_measurements_reported_within_the_interval() {
+        let service = MockService::default();
+        let mut reporter = reporter(&service, Duration::from_secs(1), Duration::from_secs(60));
+
+        service.respond(added());
+        service.respond(checked(false));
+        assert!(!reporter.report(measurement(1)).await.unwrap());
+
+        // within the interval - kept until the next report
+        assert!(!reporter.report(measurement(2)).await.unwrap());
+        assert!(!reporter.report(measurement(3)).await.unwrap());
+        assert_eq!(reporter.last_measurement().unwrap().step_count, 3);
+        assert_eq!(service.sent_steps(), vec![1]);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        service.respond(added());
+        assert!(!reporter.report(measurement(4)).await.unwrap());
+
+        assert_eq!(
+            service.methods(),
+            vec![
+                "AddTrialMeasurement",
+                "CheckTrialEarlyStoppingState",
+                "AddTrialMeasurement"
+            ]
+        );
+        assert_eq!(service.sent_steps(), vec![1, 4]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_resends_a_measurement_after_a_failed_send() {
+        let service = MockService::default();
+        let mut reporter = reporter(&service, Duration::from_secs(1), Duration::from_secs(60));
+
+        service.respond(Err(Code::Unavailable));
+        assert!(reporter.report(measurement(1)).await.is_err());
+        assert_eq!(reporter.last_measurement().unwrap().step_count, 1);
+
+        service.respond(added());
+        reporter.flush().await.unwrap();
+        assert_eq!(service.sent_steps(), vec![1, 1]);
+
+        // nothing pending
+        reporter.flush().await.unwrap();
+        assert_eq!(service.sent_steps(), vec![1, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_cancels_the_token_when_the_trial_should_stop() {
+        let service = MockService::default();
+        let mut reporter = reporter(&service, Duration::from_secs(1), Duration::from_secs(10));
+        let token = reporter.cancellation_token();
+
+        service.respond(added());
+        service.respond(checked(false));
+        assert!(!reporter.report(measurement(1)).await.unwrap());
+
+        // not checked again before the interval
+        tokio::time::advance(Duration::from_secs(5)).await;
+        service.respond(added());
+        assert!(!reporter.report(measurement(2)).await.unwrap());
+        assert!(!token.is_cancelled());
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        service.respond(added());
+        service.respond(checked(true));
+        assert!(reporter.report(measurement(3)).await.unwrap());
+        assert!(token.is_cancelled());
+        assert!(reporter.should_stop());
+
+        // no more checks once the trial should stop
+        tokio::time::advance(Duration::from_secs(10)).await;
+        service.respond(added());
+        assert!(reporter.report(measurement(4)).await.unwrap());
+        assert_eq!(service.methods().len(), 6);
+    }
+}
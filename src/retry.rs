@@ -0,0 +1,272 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Retries of transient gRPC failures.
+//!
+//! Retries are opt-in: set a [RetryPolicy] with
+//! [VizierClient::with_retry_policy](crate::VizierClient::with_retry_policy). They
+//! apply to the RPC methods of [VizierClient](crate::VizierClient) - not to the raw
+//! `service`.
+//!
+//! Idempotent RPCs (get, list, check, suggest, stop, ...) are retried on transient
+//! failures. Non-idempotent ones are handled as follows:
+//! - `complete_trial` is retried and, if a retry fails, the trial is fetched and
+//!   returned if it has been completed by a previous attempt,
+//! - `delete_study` and `delete_trial` are retried and a `NOT_FOUND` after a retry is
+//!   considered a success,
+//! - `create_trial` and `add_trial_measurement` are only retried when
+//!   [RetryPolicy::with_non_idempotent_retries] is set as they might create duplicates.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio_util::sync::CancellationToken;
+use tonic::Code;
+
+use crate::poll::{PollPolicy, Poller};
+
+/// Whether an RPC can safely be sent several times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Sending the RPC several times has the same effect as sending it once.
+    Idempotent,
+    /// Sending the RPC several times might have a different effect.
+    NonIdempotent,
+}
+
+/// How to retry RPCs failing with transient errors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    backoff: PollPolicy,
+    retryable_codes: Vec<Code>,
+    retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            backoff: PollPolicy::new()
+                .with_initial_delay(std::time::Duration::from_millis(200))
+                .with_max_delay(std::time::Duration::from_secs(10)),
+            retryable_codes: vec![Code::Unavailable, Code::DeadlineExceeded],
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new [RetryPolicy] retrying up to 5 times on `UNAVAILABLE` and
+    /// `DEADLINE_EXCEEDED`, with an exponential backoff starting at 200ms.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of retries of an RPC.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the backoff between two attempts. Its deadline, if any, bounds the total
+    /// time spent retrying.
+    pub fn with_backoff(mut self, backoff: PollPolicy) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the status codes considered transient.
+    pub fn with_retryable_codes(mut self, retryable_codes: Vec<Code>) -> Self {
+        self.retryable_codes = retryable_codes;
+        self
+    }
+
+    /// Allows retrying non-idempotent RPCs - see the [module](self) documentation.
+    pub fn with_non_idempotent_retries(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// The maximum number of retries of an RPC.
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+
+    /// The backoff between two attempts.
+    pub fn backoff(&self) -> &PollPolicy {
+        &self.backoff
+    }
+
+    /// Whether `status` is a transient error according to this policy.
+    pub fn is_transient(&self, status: &tonic::Status) -> bool {
+        self.retryable_codes.contains(&status.code())
+    }
+
+    /// Whether an RPC of the given [Idempotency] that failed with `status` should be
+    /// retried.
+    pub fn should_retry(&self, status: &tonic::Status, idempotency: Idempotency) -> bool {
+        self.is_transient(status)
+            && (idempotency == Idempotency::Idempotent || self.retry_non_idempotent)
+    }
+}
+
+/// Counters of the RPCs sent by a [VizierClient](crate::VizierClient) and its clones.
+#[derive(Debug, Default)]
+pub struct RetryStats {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+impl RetryStats {
+    pub(crate) fn record_call(&self) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_exhausted(&self) {
+        self.exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A snapshot of the counters.
+    pub fn counts(&self) -> RetryCounts {
+        RetryCounts {
+            calls: self.calls.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Attempts of a single RPC.
+pub(crate) struct Attempts {
+    policy: Option<RetryPolicy>,
+    backoff: Option<Poller>,
+    stats: Arc<RetryStats>,
+    retries: usize,
+}
+
+impl Attempts {
+    /// Starts the attempts of an RPC following `policy` - no retries if `None`.
+    pub(crate) fn new(policy: Option<&RetryPolicy>, stats: &Arc<RetryStats>) -> Self {
+        stats.record_call();
+
+        Self {
+            policy: policy.cloned(),
+            backoff: policy.map(|p| p.backoff.start()),
+            stats: stats.clone(),
+            retries: 0,
+        }
+    }
+
+    /// Number of retries so far.
+    pub(crate) fn retries(&self) -> usize {
+        self.retries
+    }
+
+    /// Decides whether to retry after an attempt failed with `status` and, if so,
+    /// waits for the backoff delay.
+    pub(crate) async fn retry(&mut self, status: &tonic::Status, idempotency: Idempotency) -> bool {
+        let (Some(policy), Some(backoff)) = (&self.policy, &mut self.backoff) else {
+            return false;
+        };
+
+        if !policy.should_retry(status, idempotency) {
+            if self.retries > 0 && policy.is_transient(status) {
+                self.stats.record_exhausted();
+            }
+            return false;
+        }
+
+        if self.retries >= policy.max_retries
+            || backoff.wait(&CancellationToken::new()).await.is_err()
+        {
+            self.stats.record_exhausted();
+            return false;
+        }
+
+        self.retries += 1;
+        self.stats.record_retry();
+        true
+    }
+}
+
+/// Snapshot of [RetryStats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetryCounts {
+    /// Number of RPCs, not counting retries.
+    pub calls: u64,
+    /// Number of retries.
+    pub retries: u64,
+    /// Number of RPCs that still failed with a transient error after the last retry.
+    pub exhausted: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tonic::{Code, Status};
+
+    use super::{Attempts, Idempotency, RetryCounts, RetryPolicy, RetryStats};
+    use crate::poll::PollPolicy;
+
+    #[test]
+    fn it_only_retries_transient_errors() {
+        let policy = RetryPolicy::new();
+
+        let unavailable = Status::new(Code::Unavailable, "restarting");
+        let invalid = Status::new(Code::InvalidArgument, "bad request");
+
+        assert!(policy.should_retry(&unavailable, Idempotency::Idempotent));
+        assert!(!policy.should_retry(&invalid, Idempotency::Idempotent));
+        assert!(!policy.should_retry(&unavailable, Idempotency::NonIdempotent));
+
+        let policy = policy.with_non_idempotent_retries(true);
+        assert!(policy.should_retry(&unavailable, Idempotency::NonIdempotent));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_counts_retries() {
+        let policy = RetryPolicy::new()
+            .with_max_retries(2)
+            .with_backoff(PollPolicy::new().with_initial_delay(Duration::from_millis(10)));
+        let stats = Arc::new(RetryStats::default());
+
+        let unavailable = Status::new(Code::Unavailable, "restarting");
+
+        let mut attempts = Attempts::new(Some(&policy), &stats);
+        assert!(attempts.retry(&unavailable, Idempotency::Idempotent).await);
+        assert!(attempts.retry(&unavailable, Idempotency::Idempotent).await);
+        assert!(!attempts.retry(&unavailable, Idempotency::Idempotent).await);
+        assert_eq!(attempts.retries(), 2);
+
+        let mut attempts = Attempts::new(None, &stats);
+        assert!(!attempts.retry(&unavailable, Idempotency::Idempotent).await);
+
+        assert_eq!(
+            stats.counts(),
+            RetryCounts {
+                calls: 2,
+                retries: 2,
+                exhausted: 1,
+            }
+        );
+    }
+}
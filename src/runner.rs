@@ -0,0 +1,295 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Worker-side trial runner.
+//!
+//! OSS Vizier hands the ACTIVE trials of a `client_id` back to it on
+//! `SuggestTrials`. A [Runner] records the ACTIVE trials of its `client_id` when it
+//! first asks for trials, so a worker restarted with the same `client_id` can tell the
+//! trials it resumes from the new ones, and keeps track of the trials it has been
+//! assigned so no trial is handed out twice.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::study::StudyName;
+//! # use oss_vizier::runner::Runner;
+//! # async fn example(client: VizierClient<InterceptedChannel>, study_name: StudyName) -> Result<(), oss_vizier::Error> {
+//! let mut runner = Runner::new(client, study_name, "worker-1".to_string());
+//!
+//! for assignment in runner.next_trials(1).await? {
+//!     let start_step = assignment.last_step().map_or(0, |s| s + 1);
+//!     let reporter = runner.reporter(&assignment);
+//!     // ... restore the checkpoint, train from `start_step` and report ...
+//!     runner.complete(reporter, None).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashSet;
+
+use futures_util::TryStreamExt;
+use prost::bytes::Bytes;
+use tonic::codegen::{Body, StdError};
+
+use crate::reporter::TrialReporter;
+use crate::trial::ToTrialName;
+use crate::vizier::trial::State;
+use crate::{Error, Measurement, StudyName, Trial, TrialName, VizierClient};
+
+/// A trial assigned to a worker by [Runner::next_trials].
+#[derive(Clone, Debug)]
+pub struct Assignment {
+    trial: Trial,
+    resumed: bool,
+}
+
+impl Assignment {
+    /// The assigned trial.
+    pub fn trial(&self) -> &Trial {
+        &self.trial
+    }
+
+    /// Consumes the assignment and returns the assigned trial.
+    pub fn into_trial(self) -> Trial {
+        self.trial
+    }
+
+    /// The name of the assigned trial.
+    pub fn trial_name(&self) -> TrialName {
+        self.trial.to_trial_name()
+    }
+
+    /// Whether the trial was started by a previous run of this worker.
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// The last measurement reported for the trial - the one with the highest
+    /// `step_count`.
+    pub fn last_measurement(&self) -> Option<&Measurement> {
+        self.trial.measurements.iter().max_by_key(|m| m.step_count)
+    }
+
+    /// The last step reported for the trial, if any - where to resume from.
+    pub fn last_step(&self) -> Option<i64> {
+        self.last_measurement().map(|m| m.step_count)
+    }
+}
+
+/// Returns whether `trial` is an ACTIVE trial of `client_id` that was already ACTIVE
+/// when the worker started - i.e., one a previous run of the worker was working on.
+///
+/// `active_at_start` are the names of the ACTIVE trials of the study when the worker
+/// started - comparing names rather than start times is immune to clock skew between
+/// the worker and the service. A trial with measurements is always considered resumed
+/// as fresh suggestions have none.
+pub fn is_resumed_trial(
+    trial: &Trial,
+    client_id: &str,
+    active_at_start: &HashSet<TrialName>,
+) -> bool {
+    if trial.client_id != client_id || trial.state != State::Active as i32 {
+        return false;
+    }
+
+    !trial.measurements.is_empty() || active_at_start.contains(&trial.to_trial_name())
+}
+
+/// Runs the trials of a study on behalf of a worker identified by its `client_id`.
+pub struct Runner<T> {
+    client: VizierClient<T>,
+    study_name: StudyName,
+    client_id: String,
+    active_at_start: Option<HashSet<TrialName>>,
+    in_flight: HashSet<TrialName>,
+    completed: HashSet<TrialName>,
+}
+
+impl<T> Runner<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body>,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Creates a new [Runner] for the study `study_name`.
+    ///
+    /// ACTIVE trials of `client_id` when [Runner::next_trials] is first called are
+    /// reported as resumed.
+    pub fn new(client: VizierClient<T>, study_name: StudyName, client_id: String) -> Self {
+        Self {
+            client,
+            study_name,
+            client_id,
+            active_at_start: None,
+            in_flight: HashSet::new(),
+            completed: HashSet::new(),
+        }
+    }
+
+    /// The `client_id` of the worker.
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// The trials handed out and not completed yet.
+    pub fn in_flight(&self) -> impl Iterator<Item = &TrialName> {
+        self.in_flight.iter()
+    }
+
+    /// The number of trials completed by this runner.
+    pub fn completed_count(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// Asks the service for up to `count` trials.
+    ///
+    /// Trials already handed out by this runner - in flight or completed - are not
+    /// returned again, so fewer than `count` trials might be returned while some are
+    /// still in flight.
+    ///
+    /// The first call lists the trials of the study to record the ACTIVE trials of the
+    /// `client_id` - see [is_resumed_trial].
+    pub async fn next_trials(&mut self, count: i32) -> Result<Vec<Assignment>, Error>
+    where
+        T: Clone,
+    {
+        if self.active_at_start.is_none() {
+            let client_id = self.client_id.clone();
+            let active: Vec<Trial> = self
+                .client
+                .list_trials_stream(self.study_name.clone(), 0)
+                .try_filter(|t| {
+                    futures_util::future::ready(
+                        t.client_id == client_id && t.state == State::Active as i32,
+                    )
+                })
+                .try_collect()
+                .await?;
+            self.active_at_start = Some(active.iter().map(|t| t.to_trial_name()).collect());
+        }
+
+        let request = self.client.mk_suggest_trials_request(
+            self.study_name.clone(),
+            count,
+            self.client_id.clone(),
+        );
+
+        let resp = self.client.suggest_trials(request).await?;
+
+        let mut assignments = vec![];
+        for trial in resp.trials {
+            let trial_name = trial.to_trial_name();
+            if self.in_flight.contains(&trial_name) || self.completed.contains(&trial_name) {
+                continue;
+            }
+
+            let resumed = self
+                .active_at_start
+                .as_ref()
+                .is_some_and(|active| is_resumed_trial(&trial, &self.client_id, active));
+
+            self.in_flight.insert(trial_name);
+            assignments.push(Assignment { trial, resumed });
+        }
+
+        Ok(assignments)
+    }
+
+    /// Creates a [TrialReporter] for an assigned trial.
+    pub fn reporter(&self, assignment: &Assignment) -> TrialReporter<T>
+    where
+        T: Clone,
+    {
+        self.client.trial_reporter(assignment.trial_name())
+    }
+
+    /// Completes an assigned trial through its reporter - see
+    /// [TrialReporter::complete].
+    pub async fn complete(
+        &mut self,
+        reporter: TrialReporter<T>,
+        final_measurement: Option<Measurement>,
+    ) -> Result<Trial, Error> {
+        let trial_name = reporter.trial_name().clone();
+        let trial = reporter.complete(final_measurement).await?;
+        self.mark_completed(trial_name);
+        Ok(trial)
+    }
+
+    /// Records that an assigned trial has been completed by other means than
+    /// [Runner::complete].
+    pub fn mark_completed(&mut self, trial_name: TrialName) {
+        self.in_flight.remove(&trial_name);
+        self.completed.insert(trial_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::is_resumed_trial;
+    use crate::TrialName;
+    use crate::vizier::trial::State;
+    use crate::vizier::{Measurement, Trial};
+
+    fn trial(id: u32, client_id: &str, state: State) -> Trial {
+        Trial {
+            name: format!("owners/o/studies/s/trials/{id}"),
+            id: id.to_string(),
+            client_id: client_id.to_string(),
+            state: state as i32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_detects_resumed_trials() {
+        let active_at_start: HashSet<TrialName> = ["owners/o/studies/s/trials/1"]
+            .iter()
+            .map(|n| n.parse().unwrap())
+            .collect();
+
+        assert!(is_resumed_trial(
+            &trial(1, "w", State::Active),
+            "w",
+            &active_at_start
+        ));
+        assert!(!is_resumed_trial(
+            &trial(2, "w", State::Active),
+            "w",
+            &active_at_start
+        ));
+        assert!(!is_resumed_trial(
+            &trial(1, "other", State::Active),
+            "w",
+            &active_at_start
+        ));
+        assert!(!is_resumed_trial(
+            &trial(1, "w", State::Succeeded),
+            "w",
+            &active_at_start
+        ));
+
+        let mut with_measurements = trial(2, "w", State::Active);
+        with_measurements.measurements.push(Measurement {
+            step_count: 3,
+            ..Default::default()
+        });
+        assert!(is_resumed_trial(&with_measurements, "w", &active_at_start));
+    }
+}
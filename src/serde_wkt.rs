@@ -0,0 +1,302 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serde implementations of the generated protos in the canonical proto3 JSON mapping -
+//! see the `serde` feature in `build.rs`.
+//!
+//! The messages are transcoded to [DynamicMessage]s described by the file descriptor
+//! set written by `build.rs`, which are (de)serialized by `prost-reflect`:
+//! - field names in lowerCamelCase,
+//! - enums as their value names and 64-bit integers as strings,
+//! - oneof fields as fields of the message,
+//! - `Timestamp` as RFC 3339 strings, `Duration` as seconds with an `s` suffix and
+//!   `Value` as the corresponding JSON value,
+//! - non-finite doubles as `"NaN"`, `"Infinity"` and `"-Infinity"`,
+//! - `Any` as the fields of the packed message with its `@type` - the packed message
+//!   must be one of the generated types.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, SerializeOptions};
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::{self, Serializer};
+
+/// The descriptors of the generated protos and of the well-known types.
+fn pool() -> &'static DescriptorPool {
+    static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let bytes = include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+        DescriptorPool::decode(bytes.as_slice()).expect("the file descriptor set of build.rs")
+    })
+}
+
+fn descriptor<M: prost::Name>() -> MessageDescriptor {
+    pool()
+        .get_message_by_name(&M::full_name())
+        .expect("the generated protos are in the file descriptor set")
+}
+
+fn serialize_message<M, S>(message: &M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    M: prost::Message + prost::Name,
+    S: Serializer,
+{
+    let mut dynamic = DynamicMessage::new(descriptor::<M>());
+    dynamic
+        .transcode_from(message)
+        .map_err(ser::Error::custom)?;
+
+    dynamic.serialize_with_options(serializer, &SerializeOptions::new())
+}
+
+fn deserialize_message<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: prost::Message + prost::Name + Default,
+    D: Deserializer<'de>,
+{
+    DynamicMessage::deserialize(descriptor::<M>(), deserializer)?
+        .transcode_to()
+        .map_err(de::Error::custom)
+}
+
+/// Visits an enum value given by name or by number.
+struct EnumVisitor<E>(fn(&str) -> Option<E>, fn(i32) -> Option<E>);
+
+impl<E> Visitor<'_> for EnumVisitor<E> {
+    type Value = E;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an enum value name or number")
+    }
+
+    fn visit_str<Err: de::Error>(self, v: &str) -> Result<E, Err> {
+        (self.0)(v).ok_or_else(|| Err::unknown_variant(v, &[]))
+    }
+
+    fn visit_i64<Err: de::Error>(self, v: i64) -> Result<E, Err> {
+        i32::try_from(v)
+            .ok()
+            .and_then(self.1)
+            .ok_or_else(|| Err::invalid_value(de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<Err: de::Error>(self, v: u64) -> Result<E, Err> {
+        i32::try_from(v)
+            .ok()
+            .and_then(self.1)
+            .ok_or_else(|| Err::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+}
+
+/// Implements `Serialize`/`Deserialize` for a generated message.
+macro_rules! message {
+    ($t:ty) => {
+        impl serde::Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_message(self, serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserialize_message(deserializer)
+            }
+        }
+    };
+}
+
+/// Implements `Serialize`/`Deserialize` for a generated enum - by value name.
+macro_rules! enumeration {
+    ($t:ty) => {
+        impl serde::Serialize for $t {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str_name())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer
+                    .deserialize_any(EnumVisitor(<$t>::from_str_name, |v| <$t>::try_from(v).ok()))
+            }
+        }
+    };
+}
+
+include!(concat!(env!("OUT_DIR"), "/serde_impls.rs"));
+
+#[cfg(test)]
+mod tests {
+    use prost_types::value::Kind;
+    use prost_types::{Any, Duration, Timestamp, Value};
+
+    use crate::google::longrunning::{Operation, operation};
+    use crate::vizier::study_spec::parameter_spec::DiscreteValueSpec;
+    use crate::vizier::{
+        Measurement, Study, SuggestTrialsResponse, Trial, measurement, study, trial,
+    };
+
+    #[test]
+    fn it_uses_the_canonical_encoding_of_well_known_types() {
+        let trial = Trial {
+            name: "owners/o/studies/s/trials/1".to_string(),
+            start_time: Some(Timestamp {
+                seconds: 1_640_995_200,
+                nanos: 0,
+            }),
+            parameters: vec![trial::Parameter {
+                parameter_id: "a".to_string(),
+                value: Some(Value {
+                    kind: Some(Kind::NumberValue(1.5)),
+                }),
+            }],
+            final_measurement: Some(Measurement {
+                elapsed_duration: Some(Duration {
+                    seconds: 1,
+                    nanos: 500_000_000,
+                }),
+                step_count: 3,
+                metrics: vec![measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: 0.5,
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&trial).unwrap();
+
+        assert_eq!(json["startTime"], "2022-01-01T00:00:00Z");
+        assert_eq!(json["parameters"][0]["parameterId"], "a");
+        assert_eq!(json["parameters"][0]["value"], 1.5);
+        assert_eq!(json["finalMeasurement"]["elapsedDuration"], "1.500s");
+        // 64-bit integers are strings
+        assert_eq!(json["finalMeasurement"]["stepCount"], "3");
+        assert!(json["endTime"].is_null());
+
+        let decoded: Trial = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, trial);
+
+        // missing fields take their default value
+        let decoded: Trial = serde_json::from_str(r#"{"name": "t"}"#).unwrap();
+        assert_eq!(decoded.name, "t");
+        assert_eq!(decoded.start_time, None);
+    }
+
+    #[test]
+    fn it_round_trips_non_finite_doubles() {
+        let measurement = Measurement {
+            elapsed_duration: None,
+            step_count: 1,
+            metrics: vec![
+                measurement::Metric {
+                    metric_id: "nan".to_string(),
+                    value: f64::NAN,
+                },
+                measurement::Metric {
+                    metric_id: "inf".to_string(),
+                    value: f64::INFINITY,
+                },
+                measurement::Metric {
+                    metric_id: "m1".to_string(),
+                    value: -0.5,
+                },
+            ],
+        };
+
+        let json = serde_json::to_string(&measurement).unwrap();
+        assert!(json.contains(r#""value":"NaN""#));
+        assert!(json.contains(r#""value":"Infinity""#));
+        assert!(json.contains(r#""value":-0.5"#));
+
+        let decoded: Measurement = serde_json::from_str(&json).unwrap();
+        assert!(decoded.metrics[0].value.is_nan());
+        assert_eq!(decoded.metrics[1].value, f64::INFINITY);
+        assert_eq!(decoded.metrics[2].value, -0.5);
+
+        let spec: DiscreteValueSpec =
+            serde_json::from_str(r#"{"values": [1, "-Infinity"], "defaultValue": "NaN"}"#).unwrap();
+        assert_eq!(spec.values, vec![1.0, f64::NEG_INFINITY]);
+        assert!(spec.default_value.unwrap().is_nan());
+
+        // a NaN parameter value would be read back as a string
+        let parameter = trial::Parameter {
+            parameter_id: "a".to_string(),
+            value: Some(Value {
+                kind: Some(Kind::NumberValue(f64::NAN)),
+            }),
+        };
+        assert!(serde_json::to_string(&parameter).is_err());
+    }
+
+    #[test]
+    fn it_encodes_enums_by_name() {
+        let study = Study {
+            name: "owners/o/studies/s".to_string(),
+            state: study::State::Active as i32,
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&study).unwrap();
+        assert_eq!(json["state"], "ACTIVE");
+
+        let decoded: Study = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, study);
+
+        // numbers are accepted too
+        let decoded: Study = serde_json::from_str(r#"{"state": 3}"#).unwrap();
+        assert_eq!(decoded.state(), study::State::Completed);
+
+        assert_eq!(
+            serde_json::to_value(study::State::Inactive).unwrap(),
+            "INACTIVE"
+        );
+        let state: study::State = serde_json::from_str(r#""COMPLETED""#).unwrap();
+        assert_eq!(state, study::State::Completed);
+        assert!(serde_json::from_str::<study::State>(r#""UNKNOWN""#).is_err());
+    }
+
+    #[test]
+    fn it_expands_any_with_its_type() {
+        let response = SuggestTrialsResponse {
+            trials: vec![Trial {
+                name: "owners/o/studies/s/trials/1".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let op = Operation {
+            name: "op".to_string(),
+            done: true,
+            result: Some(operation::Result::Response(
+                Any::from_msg(&response).unwrap(),
+            )),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&op).unwrap();
+        assert_eq!(
+            json["response"]["@type"],
+            "type.googleapis.com/vizier.SuggestTrialsResponse"
+        );
+        assert_eq!(
+            json["response"]["trials"][0]["name"],
+            "owners/o/studies/s/trials/1"
+        );
+
+        let decoded: Operation = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, op);
+    }
+}
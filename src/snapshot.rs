@@ -0,0 +1,368 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Study export and import - to back up, restore or migrate studies between servers
+//! and owners.
+//!
+//! ```no_run
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::study::StudyName;
+//! # use oss_vizier::snapshot::StudySnapshot;
+//! # async fn example(
+//! #     mut source: VizierClient<InterceptedChannel>,
+//! #     mut target: VizierClient<InterceptedChannel>,
+//! #     study_name: StudyName,
+//! # ) -> Result<(), oss_vizier::Error> {
+//! let snapshot = source.export_study(study_name).await?;
+//! snapshot.save("study.json")?;
+//!
+//! let snapshot = StudySnapshot::load("study.json")?;
+//! let report = target.import_study(&snapshot, None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use prost::bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tonic::codegen::{Body, StdError};
+
+use crate::pagination::collect_all;
+use crate::trial::ToTrialName;
+use crate::vizier::trial::State;
+use crate::vizier::{CompleteTrialRequest, Study};
+use crate::{StudyName, Trial, TrialName, VizierClient, study};
+
+/// Version of the snapshot format written by [StudySnapshot::to_writer].
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Error returned when reading or writing a [StudySnapshot].
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// I/O error.
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    /// Invalid JSON.
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    /// The snapshot was written by a newer version of the format.
+    #[error("unsupported snapshot version {0} - expected at most {SNAPSHOT_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+/// Self-contained copy of a study and all its trials - serialized as JSON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StudySnapshot {
+    /// Version of the snapshot format.
+    pub version: u32,
+    /// The study.
+    pub study: Study,
+    /// The trials of the study, with their measurements and metadata.
+    pub trials: Vec<Trial>,
+}
+
+impl StudySnapshot {
+    /// Creates a new [StudySnapshot].
+    pub fn new(study: Study, trials: Vec<Trial>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            study,
+            trials,
+        }
+    }
+
+    /// Writes the snapshot as JSON.
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), Error> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Reads a snapshot written by [StudySnapshot::to_writer].
+    pub fn from_reader(reader: impl Read) -> Result<Self, Error> {
+        let snapshot: StudySnapshot = serde_json::from_reader(reader)?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to the file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.to_writer(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a snapshot from the file at `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Result of [VizierClient::import_study].
+#[derive(Clone, Debug)]
+pub struct ImportReport {
+    /// The created study.
+    pub study: Study,
+    /// Name of each imported trial in the snapshot and in the created study - the
+    /// service assigns new trial ids.
+    pub trial_names: Vec<(TrialName, TrialName)>,
+    /// Number of trials already created by an interrupted import of the snapshot.
+    pub resumed_trials: usize,
+}
+
+/// The trial of `existing` matching each trial of the snapshot, if any - trials match
+/// when they have the same parameters and client id, in order.
+///
+/// Returns `None` if some existing trials do not match any trial of the snapshot - the
+/// study is not an interrupted import of the snapshot.
+fn match_existing(trials: &[&Trial], existing: &[Trial]) -> Option<Vec<Option<usize>>> {
+    let mut used = vec![false; existing.len()];
+
+    let matches = trials
+        .iter()
+        .map(|trial| {
+            let i = existing.iter().zip(&used).position(|(e, used)| {
+                !used && e.parameters == trial.parameters && e.client_id == trial.client_id
+            })?;
+            used[i] = true;
+            Some(i)
+        })
+        .collect();
+
+    used.iter().all(|u| *u).then_some(matches)
+}
+
+impl<T> VizierClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::Body> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    /// Exports a study and all its trials.
+    pub async fn export_study(
+        &mut self,
+        study_name: StudyName,
+    ) -> Result<StudySnapshot, crate::Error> {
+        let study = self
+            .get_study(self.mk_get_study_request(study_name.clone()))
+            .await?;
+        let trials = collect_all(self.list_trials_stream(study_name, 0)).await?;
+
+        Ok(StudySnapshot::new(study, trials))
+    }
+
+    /// Imports a study exported by [VizierClient::export_study] for the owner of this
+    /// client, under its original display name or `display_name`.
+    ///
+    /// The trials are re-created in order with their parameters, metadata and
+    /// intermediate measurements, then completed, marked infeasible or stopped to
+    /// restore their state.
+    ///
+    /// An interrupted import is resumed by importing the snapshot again: the trials
+    /// already created - with the same parameters and client id - only get their
+    /// missing measurements and state.
+    ///
+    /// Fails with [crate::Error::IncompatibleStudySpec] if a study with the same display
+    /// name has a spec that is not compatible with the one of the snapshot - see
+    /// [study::spec::check_compatibility], and with [crate::Error::StudyNotEmpty] if it
+    /// has trials that are not in the snapshot.
+    pub async fn import_study(
+        &mut self,
+        snapshot: &StudySnapshot,
+        display_name: Option<String>,
+    ) -> Result<ImportReport, crate::Error> {
+        let study = self
+            .get_or_create_study(
+                display_name.unwrap_or_else(|| snapshot.study.display_name.clone()),
+                snapshot.study.study_spec.clone().unwrap_or_default(),
+            )
+            .await?;
+        let study_name = study::ToStudyName::to_study_name(&study);
+
+        let mut trials: Vec<&Trial> = snapshot.trials.iter().collect();
+        trials.sort_by_key(|t| t.id.parse::<u64>().unwrap_or(u64::MAX));
+
+        // the study may already exist with the same display name - maybe from an
+        // interrupted import
+        let mut existing = collect_all(self.list_trials_stream(study_name.clone(), 0)).await?;
+        existing.sort_by_key(|t| t.id.parse::<u64>().unwrap_or(u64::MAX));
+        let matches = match_existing(&trials, &existing)
+            .ok_or_else(|| crate::Error::StudyNotEmpty(study.name.clone()))?;
+
+        let mut trial_names = vec![];
+        let mut resumed_trials = 0;
+        for (trial, matched) in trials.into_iter().zip(matches) {
+            let imported = match matched {
+                Some(i) => {
+                    resumed_trials += 1;
+                    self.restore_trial(existing[i].clone(), trial).await?
+                }
+                None => self.import_trial(&study_name, trial).await?,
+            };
+            trial_names.push((trial.to_trial_name(), imported.to_trial_name()));
+        }
+
+        Ok(ImportReport {
+            study,
+            trial_names,
+            resumed_trials,
+        })
+    }
+
+    async fn import_trial(
+        &mut self,
+        study_name: &StudyName,
+        trial: &Trial,
+    ) -> Result<Trial, crate::Error> {
+        let request = self.mk_create_trial_request(
+            study_name.clone(),
+            Trial {
+                parameters: trial.parameters.clone(),
+                client_id: trial.client_id.clone(),
+                metadata: trial.metadata.clone(),
+                ..Default::default()
+            },
+        );
+        let imported = self.create_trial(request).await?;
+        self.restore_trial(imported, trial).await
+    }
+
+    /// Adds the measurements of `trial` that `imported` does not have yet and restores
+    /// its state - unless `imported` has already been completed.
+    async fn restore_trial(
+        &mut self,
+        mut imported: Trial,
+        trial: &Trial,
+    ) -> Result<Trial, crate::Error> {
+        if !matches!(imported.state(), State::Requested | State::Active) {
+            return Ok(imported);
+        }
+        let trial_name = imported.to_trial_name();
+
+        for measurement in trial.measurements.iter().skip(imported.measurements.len()) {
+            let request =
+                self.mk_add_trial_measurement_request(trial_name.clone(), measurement.clone());
+            imported = self.add_trial_measurement(request).await?;
+        }
+
+        match trial.state() {
+            State::Succeeded => {
+                let request = CompleteTrialRequest {
+                    name: trial_name.into(),
+                    final_measurement: trial.final_measurement.clone(),
+                    ..Default::default()
+                };
+                imported = self.complete_trial(request).await?;
+            }
+            State::Infeasible => {
+                let request = CompleteTrialRequest {
+                    name: trial_name.into(),
+                    trial_infeasible: true,
+                    infeasible_reason: trial.infeasible_reason.clone(),
+                    ..Default::default()
+                };
+                imported = self.complete_trial(request).await?;
+            }
+            State::Stopping => {
+                imported = self
+                    .stop_trial(self.mk_stop_trial_request(trial_name))
+                    .await?;
+            }
+            State::Unspecified | State::Requested | State::Active => {}
+        }
+
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use super::{Error, SNAPSHOT_VERSION, StudySnapshot, match_existing};
+    use crate::vizier::{Study, Trial, trial};
+
+    #[test]
+    fn it_round_trips_snapshots() {
+        let snapshot = StudySnapshot::new(
+            Study {
+                name: "owners/o/studies/1".to_string(),
+                display_name: "s".to_string(),
+                ..Default::default()
+            },
+            vec![Trial {
+                name: "owners/o/studies/1/trials/1".to_string(),
+                id: "1".to_string(),
+                state: trial::State::Infeasible as i32,
+                infeasible_reason: "out of memory".to_string(),
+                ..Default::default()
+            }],
+        );
+
+        let mut buffer = vec![];
+        snapshot.to_writer(&mut buffer).unwrap();
+        assert_eq!(
+            StudySnapshot::from_reader(buffer.as_slice()).unwrap(),
+            snapshot
+        );
+
+        let newer = StudySnapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot
+        };
+        let mut buffer = vec![];
+        newer.to_writer(&mut buffer).unwrap();
+        assert!(matches!(
+            StudySnapshot::from_reader(buffer.as_slice()),
+            Err(Error::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn it_matches_the_trials_of_an_interrupted_import() {
+        let trial = |x: f64| Trial {
+            parameters: vec![trial::Parameter {
+                parameter_id: "x".to_string(),
+                value: Some(Value {
+                    kind: Some(Kind::NumberValue(x)),
+                }),
+            }],
+            ..Default::default()
+        };
+        let snapshot = [trial(1.0), trial(2.0), trial(1.0)];
+        let trials: Vec<&Trial> = snapshot.iter().collect();
+
+        assert_eq!(match_existing(&trials, &[]), Some(vec![None, None, None]));
+        assert_eq!(
+            match_existing(&trials, &[trial(1.0), trial(2.0)]),
+            Some(vec![Some(0), Some(1), None])
+        );
+        assert_eq!(
+            match_existing(&trials, &[trial(1.0), trial(2.0), trial(1.0)]),
+            Some(vec![Some(0), Some(1), Some(2)])
+        );
+
+        // not an import of the snapshot
+        assert_eq!(match_existing(&trials, &[trial(3.0)]), None);
+        assert_eq!(match_existing(&trials, &[trial(2.0), trial(2.0)]), None);
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright 2022 Sebastien Soudan.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flat tables of trials for analysis - written as CSV with the `csv` feature or
+//! Parquet with the `parquet` feature.
+//!
+//! - [Table::trials]: one row per trial with its id, state, client id, start and end
+//!   times, parameters (`param.<id>` columns), final metrics (`metric.<id>` columns)
+//!   and infeasibility reason,
+//! - [Table::measurements]: one row per intermediate measurement - the long format
+//!   for learning curves.
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! # use oss_vizier::VizierClient;
+//! # use oss_vizier::builder::InterceptedChannel;
+//! # use oss_vizier::model::study::StudyName;
+//! # use oss_vizier::pagination::collect_all;
+//! # use oss_vizier::table::Table;
+//! # #[cfg(feature = "csv")]
+//! # async fn example(client: VizierClient<InterceptedChannel>, study_name: StudyName) -> Result<(), Box<dyn std::error::Error>> {
+//! let trials = collect_all(client.list_trials_stream(study_name, 0)).await?;
+//! Table::trials(&trials).write_csv(File::create("trials.csv")?)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use prost_types::value::Kind;
+
+use crate::Trial;
+use crate::vizier::Measurement;
+
+/// Error returned when writing a [Table].
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// CSV error.
+    #[cfg(feature = "csv")]
+    #[error("{0}")]
+    Csv(#[from] csv::Error),
+    /// Parquet error.
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    /// Arrow error.
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+}
+
+/// Values of a [Column] - `None` where a row has no value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Values {
+    /// Integers.
+    Int(Vec<Option<i64>>),
+    /// Doubles.
+    Float(Vec<Option<f64>>),
+    /// Strings.
+    Str(Vec<Option<String>>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Values::Int(v) => v.len(),
+            Values::Float(v) => v.len(),
+            Values::Str(v) => v.len(),
+        }
+    }
+
+    /// The value at `row` formatted as a string - empty if there is none.
+    pub fn format(&self, row: usize) -> String {
+        match self {
+            Values::Int(v) => v[row].map(|x| x.to_string()).unwrap_or_default(),
+            Values::Float(v) => v[row].map(|x| x.to_string()).unwrap_or_default(),
+            Values::Str(v) => v[row].clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A named column of a [Table].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    /// Name of the column.
+    pub name: String,
+    /// Values of the column.
+    pub values: Values,
+}
+
+/// A table of columns of the same length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    columns: Vec<Column>,
+}
+
+impl Table {
+    /// One row per trial.
+    pub fn trials(trials: &[Trial]) -> Self {
+        let mut table = Table::default();
+
+        table.push_str("trial_id", trials.iter().map(|t| Some(t.id.clone())));
+        table.push_str(
+            "state",
+            trials
+                .iter()
+                .map(|t| Some(t.state().as_str_name().to_string())),
+        );
+        table.push_str("client_id", trials.iter().map(|t| non_empty(&t.client_id)));
+        table.push_str(
+            "start_time",
+            trials.iter().map(|t| t.start_time.map(|ts| ts.to_string())),
+        );
+        table.push_str(
+            "end_time",
+            trials.iter().map(|t| t.end_time.map(|ts| ts.to_string())),
+        );
+
+        table.push_parameters(trials);
+
+        let metrics: Vec<Option<&Measurement>> = trials
+            .iter()
+            .map(|t| t.final_measurement.as_ref())
+            .collect();
+        table.push_metrics(&metrics);
+
+        table.push_str(
+            "infeasible_reason",
+            trials.iter().map(|t| non_empty(&t.infeasible_reason)),
+        );
+
+        table
+    }
+
+    /// One row per intermediate measurement of the trials - the long format.
+    pub fn measurements(trials: &[Trial]) -> Self {
+        let rows: Vec<(&Trial, &Measurement)> = trials
+            .iter()
+            .flat_map(|t| t.measurements.iter().map(move |m| (t, m)))
+            .collect();
+
+        let mut table = Table::default();
+
+        table.push_str("trial_id", rows.iter().map(|(t, _)| Some(t.id.clone())));
+        table.push_int("step_count", rows.iter().map(|(_, m)| Some(m.step_count)));
+        table.push_float(
+            "elapsed_secs",
+            rows.iter().map(|(_, m)| {
+                m.elapsed_duration
+                    .map(|d| d.seconds as f64 + d.nanos as f64 * 1e-9)
+            }),
+        );
+
+        let metrics: Vec<Option<&Measurement>> = rows.iter().map(|(_, m)| Some(*m)).collect();
+        table.push_metrics(&metrics);
+
+        table
+    }
+
+    /// A table of `columns` - all of the same length.
+    pub(crate) fn from_columns(columns: Vec<Column>) -> Self {
+        debug_assert!(
+            columns
+                .windows(2)
+                .all(|w| w[0].values.len() == w[1].values.len())
+        );
+        Self { columns }
+    }
+
+    /// The columns.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// The column named `name`, if any.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    /// Number of rows.
+    pub fn num_rows(&self) -> usize {
+        self.columns.first().map_or(0, |c| c.values.len())
+    }
+
+    fn push(&mut self, name: impl Into<String>, values: Values) {
+        self.columns.push(Column {
+            name: name.into(),
+            values,
+        });
+    }
+
+    fn push_str(&mut self, name: &str, values: impl Iterator<Item = Option<String>>) {
+        self.push(name, Values::Str(values.collect()));
+    }
+
+    fn push_int(&mut self, name: &str, values: impl Iterator<Item = Option<i64>>) {
+        self.push(name, Values::Int(values.collect()));
+    }
+
+    fn push_float(&mut self, name: &str, values: impl Iterator<Item = Option<f64>>) {
+        self.push(name, Values::Float(values.collect()));
+    }
+
+    /// Adds a `param.<id>` column per parameter - numerical if all its values are.
+    fn push_parameters(&mut self, trials: &[Trial]) {
+        let mut parameters: BTreeMap<&str, Vec<Option<&Kind>>> = BTreeMap::new();
+        for (row, trial) in trials.iter().enumerate() {
+            for parameter in &trial.parameters {
+                let values = parameters
+                    .entry(parameter.parameter_id.as_str())
+                    .or_insert_with(|| vec![None; trials.len()]);
+                values[row] = parameter.value.as_ref().and_then(|v| v.kind.as_ref());
+            }
+        }
+
+        for (id, values) in parameters {
+            let numerical = values
+                .iter()
+                .flatten()
+                .all(|k| matches!(k, Kind::NumberValue(_)));
+
+            let values = if numerical {
+                Values::Float(
+                    values
+                        .into_iter()
+                        .map(|k| match k {
+                            Some(Kind::NumberValue(x)) => Some(*x),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            } else {
+                Values::Str(
+                    values
+                        .into_iter()
+                        .map(|k| k.and_then(kind_to_string))
+                        .collect(),
+                )
+            };
+
+            self.push(format!("param.{id}"), values);
+        }
+    }
+
+    /// Adds a `metric.<id>` column per metric of the measurements.
+    fn push_metrics(&mut self, measurements: &[Option<&Measurement>]) {
+        let mut metrics: BTreeMap<&str, Vec<Option<f64>>> = BTreeMap::new();
+        for (row, measurement) in measurements.iter().enumerate() {
+            for metric in measurement.iter().flat_map(|m| &m.metrics) {
+                metrics
+                    .entry(metric.metric_id.as_str())
+                    .or_insert_with(|| vec![None; measurements.len()])[row] = Some(metric.value);
+            }
+        }
+
+        for (id, values) in metrics {
+            self.push(format!("metric.{id}"), Values::Float(values));
+        }
+    }
+
+    /// Writes the table as CSV with a header row.
+    #[cfg(feature = "csv")]
+    pub fn write_csv(&self, writer: impl std::io::Write) -> Result<(), Error> {
+        let mut writer = csv::Writer::from_writer(writer);
+
+        writer.write_record(self.columns.iter().map(|c| &c.name))?;
+        for row in 0..self.num_rows() {
+            writer.write_record(self.columns.iter().map(|c| c.values.format(row)))?;
+        }
+
+        writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+
+    /// Writes the table as a Parquet file.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(&self, writer: impl std::io::Write + Send) -> Result<(), Error> {
+        use std::sync::Arc;
+
+        use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray};
+        use arrow_schema::{DataType, Field, Schema};
+        use parquet::arrow::ArrowWriter;
+
+        let fields: Vec<Field> = self
+            .columns
+            .iter()
+            .map(|c| {
+                let data_type = match c.values {
+                    Values::Int(_) => DataType::Int64,
+                    Values::Float(_) => DataType::Float64,
+                    Values::Str(_) => DataType::Utf8,
+                };
+                Field::new(&c.name, data_type, true)
+            })
+            .collect();
+
+        let arrays: Vec<ArrayRef> = self
+            .columns
+            .iter()
+            .map(|c| -> ArrayRef {
+                match &c.values {
+                    Values::Int(v) => Arc::new(Int64Array::from(v.clone())),
+                    Values::Float(v) => Arc::new(Float64Array::from(v.clone())),
+                    Values::Str(v) => Arc::new(StringArray::from(v.clone())),
+                }
+            })
+            .collect();
+
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+        let mut writer = ArrowWriter::try_new(writer, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+fn kind_to_string(kind: &Kind) -> Option<String> {
+    match kind {
+        Kind::NullValue(_) => None,
+        Kind::NumberValue(x) => Some(x.to_string()),
+        Kind::StringValue(s) => Some(s.clone()),
+        Kind::BoolValue(b) => Some(b.to_string()),
+        Kind::StructValue(_) | Kind::ListValue(_) => Some(format!("{kind:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_types::Value;
+    use prost_types::value::Kind;
+
+    use super::{Table, Values};
+    use crate::Trial;
+    use crate::vizier::{Measurement, measurement, trial};
+
+    fn trial(id: &str, a: f64, b: &str, m1: Option<f64>) -> Trial {
+        let parameter = |id: &str, kind: Kind| trial::Parameter {
+            parameter_id: id.to_string(),
+            value: Some(Value { kind: Some(kind) }),
+        };
+        let measurement = |step_count: i64, value: f64| Measurement {
+            elapsed_duration: None,
+            step_count,
+            metrics: vec![measurement::Metric {
+                metric_id: "m1".to_string(),
+                value,
+            }],
+        };
+
+        Trial {
+            id: id.to_string(),
+            state: trial::State::Succeeded as i32,
+            parameters: vec![
+                parameter("a", Kind::NumberValue(a)),
+                parameter("b", Kind::StringValue(b.to_string())),
+            ],
+            final_measurement: m1.map(|v| measurement(2, v)),
+            measurements: vec![measurement(1, 0.1), measurement(2, 0.2)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_flattens_trials() {
+        let trials = vec![trial("1", 0.5, "x", Some(1.0)), trial("2", 1.5, "y", None)];
+
+        let table = Table::trials(&trials);
+        assert_eq!(table.num_rows(), 2);
+        assert_eq!(
+            table.column("param.a").unwrap().values,
+            Values::Float(vec![Some(0.5), Some(1.5)])
+        );
+        assert_eq!(
+            table.column("param.b").unwrap().values,
+            Values::Str(vec![Some("x".to_string()), Some("y".to_string())])
+        );
+        assert_eq!(
+            table.column("metric.m1").unwrap().values,
+            Values::Float(vec![Some(1.0), None])
+        );
+        assert_eq!(table.column("state").unwrap().values.format(0), "SUCCEEDED");
+
+        let table = Table::measurements(&trials);
+        assert_eq!(table.num_rows(), 4);
+        assert_eq!(
+            table.column("step_count").unwrap().values,
+            Values::Int(vec![Some(1), Some(2), Some(1), Some(2)])
+        );
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn it_writes_csv() {
+        let table = Table::trials(&[trial("1", 0.5, "x", Some(1.0))]);
+
+        let mut buffer = vec![];
+        table.write_csv(&mut buffer).unwrap();
+
+        let csv = String::from_utf8(buffer).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "trial_id,state,client_id,start_time,end_time,param.a,param.b,metric.m1,\
+                 infeasible_reason"
+            )
+        );
+        assert_eq!(lines.next(), Some("1,SUCCEEDED,,,,0.5,x,1,"));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn it_writes_parquet() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let table = Table::trials(&[trial("1", 0.5, "x", Some(1.0)), trial("2", 1.5, "y", None)]);
+
+        let mut buffer = vec![];
+        table.write_parquet(&mut buffer).unwrap();
+
+        let reader = SerializedFileReader::new(prost::bytes::Bytes::from(buffer)).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 2);
+        assert_eq!(
+            metadata.file_metadata().schema_descr().num_columns(),
+            table.columns().len()
+        );
+    }
+}
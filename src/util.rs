@@ -15,38 +15,149 @@
 //! Utilities for the Vizier API.
 
 use prost::DecodeError;
+use prost_types::Any;
 
 use crate::google::rpc::Status;
 use crate::operation;
 
 /// Error from decoding operation results.
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Error while decoding
     #[error("{0}")]
     DecodeError(#[from] DecodeError),
-    /// RPC error
-    #[error("Status: {}", .0.message)]
-    RPCStatus(Status),
+    /// The operation failed
+    #[error("{0}")]
+    OperationFailed(OperationError),
     /// Invalid type
     #[error("Invalid type {0}")]
     InvalidType(String),
+    /// The operation is done but has no result
+    #[error("Operation done without result")]
+    MissingResult,
+}
+
+/// Error of a failed operation - from its [`google.rpc.Status`](Status).
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationError {
+    /// The status code.
+    pub code: tonic::Code,
+    /// The developer-facing error message.
+    pub message: String,
+    /// The error details.
+    pub details: Vec<Any>,
+}
+
+impl std::fmt::Display for OperationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Status: {:?} - {}", self.code, self.message)
+    }
+}
+
+impl From<Status> for OperationError {
+    fn from(status: Status) -> Self {
+        Self {
+            code: tonic::Code::from_i32(status.code),
+            message: status.message,
+            details: status.details,
+        }
+    }
 }
 
 /// Decodes the result of an operation as with the specified [`type_url`](Any.type_url) as
 /// the provided (by the generic type parameter `X`) message.
+///
+/// `type_url` can be the full type URL or its suffix - such as the message name. The
+/// check is skipped if the response has no type URL.
 pub fn decode_operation_result_as<X>(
     result: operation::Result,
-    _type_url: impl AsRef<str>,
+    type_url: impl AsRef<str>,
 ) -> Result<X, Error>
 where
     X: prost::Message + Default,
 {
     match result {
-        operation::Result::Error(s) => Err(Error::RPCStatus(s)),
+        operation::Result::Error(s) => Err(Error::OperationFailed(s.into())),
         operation::Result::Response(resp) => {
+            let expected = type_url.as_ref();
+            if !resp.type_url.is_empty()
+                && resp.type_url != expected
+                && !resp.type_url.ends_with(&format!("/{expected}"))
+                && !resp.type_url.ends_with(&format!(".{expected}"))
+            {
+                return Err(Error::InvalidType(resp.type_url));
+            }
+
             let resp: X = X::decode(&resp.value[..])?;
             Ok(resp)
         }
     }
 }
+
+/// Decodes the result of an operation as the message `X`, checking the
+/// [`type_url`](Any.type_url) of the response matches `X`.
+pub fn decode_operation_result<X>(result: operation::Result) -> Result<X, Error>
+where
+    X: prost::Message + prost::Name + Default,
+{
+    decode_operation_result_as(result, X::full_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+    use prost_types::Any;
+
+    use super::{Error, decode_operation_result, decode_operation_result_as};
+    use crate::google::rpc::Status;
+    use crate::operation;
+    use crate::vizier::{SuggestTrialsResponse, Trial};
+
+    fn response(type_url: &str) -> operation::Result {
+        let resp = SuggestTrialsResponse {
+            trials: vec![Trial::default()],
+            ..Default::default()
+        };
+
+        operation::Result::Response(Any {
+            type_url: type_url.to_string(),
+            value: resp.encode_to_vec(),
+        })
+    }
+
+    #[test]
+    fn it_checks_the_type_url() {
+        let url = "type.googleapis.com/vizier.SuggestTrialsResponse";
+
+        let resp: SuggestTrialsResponse = decode_operation_result(response(url)).unwrap();
+        assert_eq!(resp.trials.len(), 1);
+
+        let resp: SuggestTrialsResponse =
+            decode_operation_result_as(response(url), "SuggestTrialsResponse").unwrap();
+        assert_eq!(resp.trials.len(), 1);
+
+        let err = decode_operation_result::<SuggestTrialsResponse>(response(
+            "type.googleapis.com/vizier.Trial",
+        ))
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidType(_)));
+    }
+
+    #[test]
+    fn it_maps_the_status() {
+        let result = operation::Result::Error(Status {
+            code: tonic::Code::Unavailable as i32,
+            message: "down".to_string(),
+            details: vec![],
+        });
+
+        match decode_operation_result::<SuggestTrialsResponse>(result) {
+            Err(Error::OperationFailed(e)) => {
+                assert_eq!(e.code, tonic::Code::Unavailable);
+                assert_eq!(e.message, "down");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}